@@ -7,12 +7,15 @@ use std::{
 
 use session_gen::generate_session_id;
 
-use crate::{StringErr, StringResult, cache::prep_cache, dedent, mapper, report_process_failure};
+use crate::{
+    StringErr, StringResult, cache::prep_cache, dedent, dynamic_err, mapper, report_process_failure,
+};
 
 pub(crate) enum BuildType {
     Run {
         background: bool,
         binary: Option<PathBuf>,
+        resume: Option<String>,
     },
     Build,
 }
@@ -46,19 +49,37 @@ pub(crate) fn build_project(
         .stderr(Stdio::inherit());
     }
 
-    fn setup_maestro_workdir(cmd: &mut Command) -> Result<String, StringErr> {
-        let session_id = generate_session_id();
+    fn setup_resume(cmd: &mut Command, resume: &Option<String>) {
+        if let Some(session_id) = resume {
+            cmd.env("MAESTRO_SESSION_ID", session_id)
+                .env("MAESTRO_RESUME", "1");
+        }
+    }
+
+    fn setup_maestro_workdir(
+        cmd: &mut Command,
+        resume: Option<String>,
+    ) -> Result<String, StringErr> {
+        let session_id = resume.clone().unwrap_or_else(generate_session_id);
         let workdir = Path::new(&env::var("MAESTRO_WORKDIR").unwrap_or("maestro_work".to_string()))
             .join(&session_id);
         let log_path = workdir.join("out.log");
         let err_path = workdir.join("err.log");
 
+        if resume.is_some() && !workdir.is_dir() {
+            return Err(dynamic_err(format!(
+                "Cannot resume session {session_id}: {} does not exist",
+                workdir.display()
+            )));
+        }
         fs::create_dir_all(&workdir)
             .map_err(|e| mapper(&e, "Failed to initialize session workdir"))?;
+        let open_log = |path: &Path| File::options().create(true).append(true).open(path);
         let log_handle =
-            File::create(&log_path).map_err(|e| mapper(&e, "Failed to create log file"))?;
+            open_log(&log_path).map_err(|e| mapper(&e, "Failed to create log file"))?;
         let err_handle =
-            File::create(&err_path).map_err(|e| mapper(&e, "Failed to create err file"))?;
+            open_log(&err_path).map_err(|e| mapper(&e, "Failed to create err file"))?;
+        setup_resume(cmd, &resume);
 
         cmd.env("MAESTRO_SESSION_ID", &session_id)
             .stdout(log_handle)
@@ -92,13 +113,18 @@ pub(crate) fn build_project(
                 return Err(report_process_failure(status, "Building project"));
             }
         }
-        BuildType::Run { background, binary } => match binary {
+        BuildType::Run {
+            background,
+            binary,
+            resume,
+        } => match binary {
             None => {
                 let cache_dir = prep_cache()?;
 
                 let mut run_cmd = Command::new("cargo");
                 setup_cargo_env(&mut run_cmd, true, &cache_dir, cargo_args, program_args);
                 if !background {
+                    setup_resume(&mut run_cmd, &resume);
                     let status = run_cmd
                         .status()
                         .map_err(|e| mapper(&e, "Failed to run project"))?;
@@ -106,7 +132,7 @@ pub(crate) fn build_project(
                         return Err(report_process_failure(status, "Running project"));
                     }
                 } else {
-                    let print_stmt = setup_maestro_workdir(&mut run_cmd)?;
+                    let print_stmt = setup_maestro_workdir(&mut run_cmd, resume)?;
                     run_cmd
                         .spawn()
                         .map_err(|e| mapper(&e, "Failed to spawn background child process"))?;
@@ -119,6 +145,7 @@ pub(crate) fn build_project(
                     .map_err(|e| mapper(&e, "Failed to canonicalize binary path"))?;
                 let mut run_cmd = Command::new(bin_path);
                 if !background {
+                    setup_resume(&mut run_cmd, &resume);
                     let status = run_cmd
                         .stdout(Stdio::inherit())
                         .stderr(Stdio::inherit())
@@ -128,7 +155,7 @@ pub(crate) fn build_project(
                         return Err(report_process_failure(status, "Running binary"));
                     }
                 } else {
-                    let print_stmt = setup_maestro_workdir(&mut run_cmd)?;
+                    let print_stmt = setup_maestro_workdir(&mut run_cmd, resume)?;
                     run_cmd
                         .spawn()
                         .map_err(|e| mapper(&e, "Failed to spawn background child process"))?;
@@ -1,7 +1,13 @@
 use crate::{StringResult, mapper, report_process_failure, static_err};
-use std::{fs, path::Path, process::Command};
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
 
-pub(crate) fn kill_process(path: &Path) -> StringResult {
+pub(crate) fn kill_process(path: &Path, timeout: u64) -> StringResult {
     let mut marker_file_path = Path::new(&path).join(".maestro.active");
     if !marker_file_path.exists() {
         marker_file_path = Path::new("maestro_work").join(path).join(".maestro.active");
@@ -14,18 +20,47 @@ pub(crate) fn kill_process(path: &Path) -> StringResult {
 
     let pid: u64 = fs::read_to_string(&marker_file_path)
         .map_err(|e| mapper(&e, "Failed to read .maestro.active"))?
+        .trim()
         .parse()
         .map_err(|e| mapper(&e, "Failed to parse PID from .maestro.active"))?;
-    let cmd = Command::new("kill")
-        .arg("-9")
-        .arg(pid.to_string())
-        .status()
-        .map_err(|e| mapper(&e, "Failed to kill maestro process"))?;
+
+    // The process cancels its outstanding jobs on SIGTERM, and is only killed if it fails to exit in time
+    let cmd = signal(pid, "TERM").map_err(|e| mapper(&e, "Failed to terminate maestro process"))?;
     if !cmd.success() {
-        return Err(report_process_failure(cmd, "Killing maestro process"));
+        return Err(report_process_failure(cmd, "Terminating maestro process"));
+    }
+    println!("Sent SIGTERM to maestro process {pid}; waiting up to {timeout}s for it to exit");
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    while Instant::now() < deadline {
+        if !is_running(pid) {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(250));
     }
 
-    fs::remove_file(&marker_file_path)
-        .map_err(|e| mapper(&e, "Failed to remove .maestro.active"))?;
+    println!("Maestro process {pid} did not exit in time; killing it");
+    let cmd = signal(pid, "KILL").map_err(|e| mapper(&e, "Failed to kill maestro process"))?;
+    if !cmd.success() && is_running(pid) {
+        return Err(report_process_failure(cmd, "Killing maestro process"));
+    }
+    if marker_file_path.exists() {
+        fs::remove_file(&marker_file_path)
+            .map_err(|e| mapper(&e, "Failed to remove .maestro.active"))?;
+    }
     Ok(())
 }
+
+fn signal(pid: u64, signal: &str) -> std::io::Result<std::process::ExitStatus> {
+    Command::new("kill")
+        .arg(format!("-{signal}"))
+        .arg(pid.to_string())
+        .status()
+}
+
+fn is_running(pid: u64) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
@@ -49,10 +49,19 @@ fn main() {
         Cmd::Run {
             binary,
             background,
+            resume,
             cargo_args,
             args,
-        } => build_project(cargo_args, args, BuildType::Run { background, binary }),
-        Cmd::Kill { target } => kill_process(&target),
+        } => build_project(
+            cargo_args,
+            args,
+            BuildType::Run {
+                background,
+                binary,
+                resume,
+            },
+        ),
+        Cmd::Kill { target, timeout } => kill_process(&target, timeout),
     } {
         eprintln!("{e}");
         process::exit(1);
@@ -98,16 +107,23 @@ pub enum Cmd {
         /// Run detached from the current shell session
         #[arg(short, long, default_value_t = false)]
         background: bool,
+        /// Resume an interrupted session, skipping processes that already completed
+        #[arg(long, value_name = "SESSION_ID")]
+        resume: Option<String>,
         /// Arguments to pass to cargo run
         cargo_args: Vec<String>,
         /// Arguments to pass to the program
         #[arg(trailing_var_arg = true)]
         args: Vec<String>,
     },
-    /// Kill a running maestro process
+    /// Stop a running maestro process, cancelling its outstanding jobs
     Kill {
         /// The process to kill, by name or path
         target: PathBuf,
+        /// Seconds to wait for the process to cancel its jobs and exit
+        /// before it is killed outright
+        #[arg(short, long, default_value_t = 60)]
+        timeout: u64,
     },
     /// Update the libmaestro cache
     UpdateCache,
@@ -0,0 +1,220 @@
+use crate::{LP, Process, executors::Reusable};
+use dagger_lib::result::{NodeError, NodeResult};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Feeds [`Hash`] implementations into a SHA-256 digest
+struct DigestHasher(Sha256);
+impl Hasher for DigestHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+    fn finish(&self) -> u64 {
+        // Only the full digest is ever read
+        0
+    }
+}
+
+impl Process {
+    /// Computes a content-addressed key for this process when run on `executor`
+    ///
+    /// The key covers the script, the contents of all inputs, the args,
+    /// the declared outputs, and the type, container and resources of the executor
+    pub(crate) fn cache_key<E: Reusable>(&self, executor: &E) -> NodeResult<String> {
+        let mut hasher = DigestHasher(Sha256::new());
+        self.script.hash(&mut hasher);
+        for (var, path) in &self.inputs {
+            var.hash(&mut hasher);
+            path.file_name().hash(&mut hasher);
+            hash_contents(path, &mut hasher).map_err(|e| {
+                NodeError::msg(format!(
+                    "Failed to hash input {} for caching: {e}",
+                    path.display()
+                ))
+            })?;
+        }
+        for (var, arg) in &self.args {
+            var.hash(&mut hasher);
+            arg.hash(&mut hasher);
+        }
+        for (var, path) in &self.outputs {
+            var.hash(&mut hasher);
+            path.hash(&mut hasher);
+        }
+        executor.hash_execution(&mut hasher);
+
+        let digest = hasher.0.finalize();
+        Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+
+    /// Reuses the outputs of a cached run, if one exists
+    ///
+    /// On a hit, the process workdir is created with its outputs symlinked
+    /// into the cache, so the returned paths have the same shape as a fresh run
+    pub(crate) fn restore_cached(&self, key: &str) -> NodeResult<Option<Vec<PathBuf>>> {
+        let entry = self.session()?.cache_dir().join(key);
+        if !entry.is_dir()
+            || self
+                .outputs
+                .iter()
+                .any(|(_, p)| fs::symlink_metadata(entry.join(p)).is_err())
+        {
+            return Ok(None);
+        }
+
+        let workdir = self.create_workdir()?;
+        let mut log_handle = fs::File::create(workdir.join(".maestro.log"))
+            .map_err(|e| NodeError::msg(format!("Failed to open log file: {e}")))?;
+        let _ = writeln!(
+            log_handle,
+            "{LP} Cache hit! Reusing outputs from {}",
+            entry.display()
+        );
+
+        let mut outputs = Vec::with_capacity(self.outputs.len() + 1);
+        for (_, path) in &self.outputs {
+            let destination = workdir.join(path);
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            symlink(entry.join(path), &destination).map_err(|e| {
+                NodeError::msg(format!(
+                    "Failed to link cached output {}: {e}",
+                    destination.display()
+                ))
+            })?;
+            outputs.push(destination);
+        }
+        outputs.push(workdir);
+        Ok(Some(outputs))
+    }
+
+    /// Records the outputs of a successful run under `key`
+    ///
+    /// Failures are reported but never fail the process itself
+    pub(crate) fn store_cached(&self, key: &str, workdir: &Path) {
+        if let Err(e) = self.try_store_cached(key, workdir) {
+            eprintln!("{LP} Failed to cache outputs of process {}: {e}", self.name);
+        }
+    }
+
+    fn try_store_cached(&self, key: &str, workdir: &Path) -> io::Result<()> {
+        if self.outputs.iter().any(|(_, p)| p.is_absolute()) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "absolute output paths cannot be cached",
+            ));
+        }
+        let cache_dir = self
+            .session()
+            .map_err(|_| io::Error::other("the process has no session"))?
+            .cache_dir();
+        let entry = cache_dir.join(key);
+        if entry.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(&cache_dir)?;
+
+        // Populate a private staging directory and move it into place,
+        // so concurrent sessions never observe a partial entry
+        let staging = cache_dir.join(format!(".{key}.{}.{}", process::id(), self.name));
+        let populate = || -> io::Result<()> {
+            fs::create_dir(&staging)?;
+            for (_, path) in &self.outputs {
+                copy_recursive(&workdir.join(path), &staging.join(path))?;
+            }
+            fs::write(
+                staging.join(".maestro.origin"),
+                workdir.display().to_string(),
+            )
+        };
+        let result = populate().and_then(|_| fs::rename(&staging, &entry));
+        if staging.exists() {
+            let _ = fs::remove_dir_all(&staging);
+        }
+        match result {
+            Err(_) if entry.exists() => Ok(()),
+            result => result,
+        }
+    }
+}
+
+fn hash_contents(path: &Path, hasher: &mut DigestHasher) -> io::Result<()> {
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+        for entry in entries {
+            entry.file_name().hash(hasher);
+            hash_contents(&entry, hasher)?;
+        }
+    } else {
+        let mut file = fs::File::open(path)?;
+        io::copy(&mut file, &mut hasher.0)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn copy_recursive(source: &Path, destination: &Path) -> io::Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if source.is_dir() {
+        fs::create_dir_all(destination)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, destination)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Process,
+        executors::{local::LocalExecutor, slurm::SlurmExecutor},
+    };
+    use std::{path::PathBuf, time::Duration};
+
+    fn process() -> Process {
+        Process::new(
+            "cached".to_string(),
+            vec![],
+            vec![("greeting".into(), "hello".to_string())],
+            vec![("out".into(), PathBuf::from("out.txt"))],
+            "echo \"$greeting\" > out.txt".into(),
+        )
+    }
+
+    #[test]
+    fn key_ignores_settings_that_do_not_affect_execution() {
+        let base = SlurmExecutor::default();
+        let key = process().cache_key(&base).unwrap();
+        let tuned = SlurmExecutor::default()
+            .with_cache(true)
+            .with_poll_rate(Duration::from_secs(60))
+            .with_submit_ahead(true)
+            .with_modules(["gcc"])
+            .map_retry(|retry| retry.with_retries(5));
+        assert_eq!(process().cache_key(&tuned).unwrap(), key);
+    }
+
+    #[test]
+    fn key_covers_resources_and_executor_type() {
+        let key = process().cache_key(&SlurmExecutor::default()).unwrap();
+        let more_cpus = SlurmExecutor::default().map_config(|config| config.with_cpus(8));
+        assert_ne!(process().cache_key(&more_cpus).unwrap(), key);
+        assert_ne!(process().cache_key(&LocalExecutor::default()).unwrap(), key);
+    }
+}
@@ -2,15 +2,24 @@ use crate::{
     Container,
     executors::{
         GenericExecutor,
+        condor::CondorExecutor,
+        kubernetes::{KubernetesCluster, KubernetesExecutor, KubernetesVolume},
         local::LocalExecutor,
-        slurm::{SlurmConfig, SlurmExecutor},
+        lsf::{LsfConfig, LsfExecutor},
+        pbs::{PbsConfig, PbsExecutor},
+        retry::{Escalation, RetryPolicy},
+        slurm::{Memory, SlurmAllocationExecutor, SlurmConfig, SlurmExecutor, SlurmRest},
+        ssh::SshExecutor,
+        template::{TemplateExecutor, TemplateStatus},
     },
     process::StagingMode,
 };
+use dagger_lib::result::{NodeError, NodeResult};
 use serde::Deserialize;
 use std::{
     collections::{HashMap, HashSet},
     env, fs,
+    path::{Path, PathBuf},
     process::exit,
     sync::LazyLock,
     time::Duration,
@@ -22,55 +31,181 @@ use std::{
 pub enum MaybeInheritingExecutor {
     Inherit {
         inherit: String,
+        /// The overrides as written, read once the type of the inherited executor is known
         #[serde(flatten)]
-        overrides: Box<Option<PartialExecutor>>,
+        raw_overrides: toml::Table,
     },
     Executor(GenericExecutor),
 }
 
 #[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 #[doc(hidden)]
 pub struct PartialExecutor {
     // Either
     container: Option<Container>,
     staging_mode: Option<StagingMode>,
+    cache: Option<bool>,
+    retries: Option<u32>,
+    retry_backoff: Option<Duration>,
+    escalate: Option<Escalation>,
+    preemption_retries: Option<u32>,
+    // Local
+    max_parallel: Option<u64>,
     // Slurm
+    submit_ahead: Option<bool>,
     poll_rate: Option<Duration>,
     modules: Option<Vec<String>>,
+    rest: Option<SlurmRest>,
     #[serde(flatten)]
     config: SlurmConfig,
+    // SlurmAllocation
+    allocation: Option<SlurmConfig>,
+    // Pbs and Lsf
+    queue: Option<String>,
+    // Lsf
+    project: Option<String>,
+    // Condor
+    disk: Option<Memory>,
+    file_transfer: Option<bool>,
+    // Kubernetes
+    kubeconfig: Option<PathBuf>,
+    context: Option<String>,
+    namespace: Option<String>,
+    volume: Option<KubernetesVolume>,
+    // Ssh
+    host: Option<String>,
+    user: Option<String>,
+    port: Option<u16>,
+    identity_file: Option<PathBuf>,
+    remote_workdir: Option<PathBuf>,
+    // Template
+    header: Option<String>,
+    submit: Option<String>,
+    job_id_regex: Option<String>,
+    status: Option<TemplateStatus>,
+    cancel: Option<String>,
+}
+
+impl PartialExecutor {
+    /// Whether scheduling options only sbatch understands are set
+    fn sets_slurm_options(&self) -> bool {
+        let config = &self.config;
+        config.constraint.is_some()
+            || config.qos.is_some()
+            || config.exclusive.is_some()
+            || config.gres.is_some()
+            || config.reservation.is_some()
+            || config.exclude.is_some()
+            || config.begin.is_some()
+            || config.dependency.is_some()
+            || config.nice.is_some()
+            || config.requeue.is_some()
+    }
+    fn sets_ssh_options(&self) -> bool {
+        self.host.is_some()
+            || self.user.is_some()
+            || self.port.is_some()
+            || self.identity_file.is_some()
+            || self.remote_workdir.is_some()
+    }
+    fn sets_condor_options(&self) -> bool {
+        self.disk.is_some() || self.file_transfer.is_some()
+    }
+    fn sets_kubernetes_options(&self) -> bool {
+        self.kubeconfig.is_some()
+            || self.context.is_some()
+            || self.namespace.is_some()
+            || self.volume.is_some()
+    }
+    fn sets_template_options(&self) -> bool {
+        self.header.is_some()
+            || self.submit.is_some()
+            || self.job_id_regex.is_some()
+            || self.status.is_some()
+            || self.cancel.is_some()
+    }
 }
 
 impl GenericExecutor {
-    pub(crate) fn merge(self, other: Option<PartialExecutor>) -> Option<GenericExecutor> {
-        if let Some(other_overrides) = other {
-            match self {
-                Self::Local(local_exe) => {
-                    local_exe.merge(other_overrides).map(GenericExecutor::Local)
-                }
-                Self::Slurm(slurm_exe) => slurm_exe
-                    .merge(other_overrides)
-                    .map(|exec| GenericExecutor::Slurm(Box::new(exec))),
-            }
-        } else {
-            Some(self)
+    /// Lays `raw_overrides` over the executor, or returns `None` if they set options of another type
+    pub(crate) fn merge(
+        self,
+        raw_overrides: &toml::Table,
+    ) -> Result<Option<GenericExecutor>, String> {
+        if let Self::Plugin(plugin_exe) = &self {
+            return plugin_exe
+                .merge(raw_overrides)
+                .map(|exec| Some(GenericExecutor::Plugin(Box::new(exec))))
+                .map_err(|e| format!("Failed to apply inherited overrides: {e}"));
+        }
+        let other_overrides =
+            PartialExecutor::deserialize(toml::Value::Table(raw_overrides.clone()))
+                .map_err(|e| format!("Invalid inherited overrides: {e}"))?;
+        Ok(match self {
+            Self::Local(local_exe) => local_exe.merge(other_overrides).map(GenericExecutor::Local),
+            Self::Slurm(slurm_exe) => slurm_exe
+                .merge(other_overrides)
+                .map(|exec| GenericExecutor::Slurm(Box::new(exec))),
+            Self::SlurmAllocation(allocation_exe) => allocation_exe
+                .merge(other_overrides)
+                .map(|exec| GenericExecutor::SlurmAllocation(Box::new(exec))),
+            Self::Ssh(ssh_exe) => ssh_exe
+                .merge(other_overrides)
+                .map(|exec| GenericExecutor::Ssh(Box::new(exec))),
+            Self::Condor(condor_exe) => condor_exe
+                .merge(other_overrides)
+                .map(|exec| GenericExecutor::Condor(Box::new(exec))),
+            Self::Kubernetes(kubernetes_exe) => kubernetes_exe
+                .merge(other_overrides)
+                .map(|exec| GenericExecutor::Kubernetes(Box::new(exec))),
+            Self::Lsf(lsf_exe) => lsf_exe
+                .merge(other_overrides)
+                .map(|exec| GenericExecutor::Lsf(Box::new(exec))),
+            Self::Pbs(pbs_exe) => pbs_exe
+                .merge(other_overrides)
+                .map(|exec| GenericExecutor::Pbs(Box::new(exec))),
+            Self::Template(template_exe) => template_exe
+                .merge(other_overrides)
+                .map(|exec| GenericExecutor::Template(Box::new(exec))),
+            Self::Plugin(_) => unreachable!("Plugin executors merge their raw overrides"),
+        })
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) fn merge(self, other: &PartialExecutor) -> RetryPolicy {
+        RetryPolicy {
+            retries: other.retries.unwrap_or(self.retries),
+            retry_backoff: other.retry_backoff.unwrap_or(self.retry_backoff),
+            escalate: other.escalate.or(self.escalate),
+            preemption_retries: other.preemption_retries.unwrap_or(self.preemption_retries),
         }
     }
 }
 
 impl LocalExecutor {
     pub(crate) fn merge(self, other: PartialExecutor) -> Option<LocalExecutor> {
-        if other.poll_rate.is_some() || other.modules.is_some() {
+        if other.submit_ahead.is_some()
+            || other.rest.is_some()
+            || other.allocation.is_some()
+            || other.poll_rate.is_some()
+            || other.modules.is_some()
+            || other.queue.is_some()
+            || other.project.is_some()
+            || other.sets_slurm_options()
+            || other.sets_ssh_options()
+            || other.sets_condor_options()
+            || other.sets_kubernetes_options()
+            || other.sets_template_options()
+        {
             return None;
         }
-        let slurm_config = other.config;
-        if slurm_config.cpus.is_some()
-            || slurm_config.memory.is_some()
-            || slurm_config.gpus.is_some()
+        let slurm_config = &other.config;
+        if slurm_config.gpus.is_some()
             || slurm_config.tasks.is_some()
             || slurm_config.nodes.is_some()
             || slurm_config.partition.is_some()
-            || slurm_config.time.is_some()
             || slurm_config.account.is_some()
             || slurm_config.mail_user.is_some()
             || slurm_config.mail_type.is_some()
@@ -79,16 +214,38 @@ impl LocalExecutor {
             None
         } else {
             Some(LocalExecutor {
+                retry: self.retry.merge(&other),
                 staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
                 container: other.container.or(self.container),
+                cache: other.cache.unwrap_or(self.cache),
+                cpus: other.config.cpus.or(self.cpus),
+                memory: other.config.memory.or(self.memory),
+                time: other.config.time.or(self.time),
+                max_parallel: other.max_parallel.or(self.max_parallel),
+                slots: Default::default(),
             })
         }
     }
 }
 impl SlurmExecutor {
     pub(crate) fn merge(mut self, other: PartialExecutor) -> Option<SlurmExecutor> {
+        if other.max_parallel.is_some()
+            || other.allocation.is_some()
+            || other.queue.is_some()
+            || other.project.is_some()
+            || other.sets_ssh_options()
+            || other.sets_condor_options()
+            || other.sets_kubernetes_options()
+            || other.sets_template_options()
+        {
+            return None;
+        }
         Some(SlurmExecutor {
+            retry: self.retry.merge(&other),
             container: other.container.or(self.container),
+            cache: other.cache.unwrap_or(self.cache),
+            submit_ahead: other.submit_ahead.unwrap_or(self.submit_ahead),
+            rest: other.rest.or(self.rest),
             poll_rate: other.poll_rate.unwrap_or(self.poll_rate),
             staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
             modules: {
@@ -108,6 +265,16 @@ impl SlurmExecutor {
                     account: other.config.account.or(self.config.account),
                     mail_user: other.config.mail_user.or(self.config.mail_user),
                     mail_type: other.config.mail_type.or(self.config.mail_type),
+                    constraint: other.config.constraint.or(self.config.constraint),
+                    qos: other.config.qos.or(self.config.qos),
+                    exclusive: other.config.exclusive.or(self.config.exclusive),
+                    gres: other.config.gres.or(self.config.gres),
+                    reservation: other.config.reservation.or(self.config.reservation),
+                    exclude: other.config.exclude.or(self.config.exclude),
+                    begin: other.config.begin.or(self.config.begin),
+                    dependency: other.config.dependency.or(self.config.dependency),
+                    nice: other.config.nice.or(self.config.nice),
+                    requeue: other.config.requeue.or(self.config.requeue),
                     additional_options: {
                         let mut other_options = other.config.additional_options;
                         other_options.append(&mut self.config.additional_options);
@@ -119,6 +286,321 @@ impl SlurmExecutor {
     }
 }
 
+impl SlurmAllocationExecutor {
+    pub(crate) fn merge(mut self, other: PartialExecutor) -> Option<SlurmAllocationExecutor> {
+        let slurm_config = &other.config;
+        if other.max_parallel.is_some()
+            || other.submit_ahead.is_some()
+            || other.rest.is_some()
+            || other.poll_rate.is_some()
+            || other.queue.is_some()
+            || other.project.is_some()
+            || other.sets_slurm_options()
+            || other.sets_ssh_options()
+            || other.sets_condor_options()
+            || other.sets_kubernetes_options()
+            || other.sets_template_options()
+            || slurm_config.tasks.is_some()
+            || slurm_config.nodes.is_some()
+            || slurm_config.partition.is_some()
+            || slurm_config.account.is_some()
+            || slurm_config.mail_user.is_some()
+            || slurm_config.mail_type.is_some()
+            || !slurm_config.additional_options.is_empty()
+        {
+            return None;
+        }
+        Some(SlurmAllocationExecutor {
+            retry: self.retry.merge(&other),
+            container: other.container.or(self.container),
+            cache: other.cache.unwrap_or(self.cache),
+            staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
+            modules: {
+                let mut other_modules = other.modules.unwrap_or_default();
+                other_modules.append(&mut self.modules);
+                other_modules
+            },
+            allocation: other.allocation.unwrap_or(self.allocation),
+            cpus: other.config.cpus.or(self.cpus),
+            memory: other.config.memory.or(self.memory),
+            gpus: other.config.gpus.or(self.gpus),
+            time: other.config.time.or(self.time),
+        })
+    }
+}
+
+impl SshExecutor {
+    pub(crate) fn merge(self, other: PartialExecutor) -> Option<SshExecutor> {
+        let slurm_config = &other.config;
+        if other.max_parallel.is_some()
+            || other.submit_ahead.is_some()
+            || other.rest.is_some()
+            || other.allocation.is_some()
+            || other.poll_rate.is_some()
+            || other.modules.is_some()
+            || other.queue.is_some()
+            || other.project.is_some()
+            || other.sets_slurm_options()
+            || other.sets_condor_options()
+            || other.sets_kubernetes_options()
+            || other.sets_template_options()
+            || slurm_config.cpus.is_some()
+            || slurm_config.memory.is_some()
+            || slurm_config.gpus.is_some()
+            || slurm_config.tasks.is_some()
+            || slurm_config.nodes.is_some()
+            || slurm_config.partition.is_some()
+            || slurm_config.time.is_some()
+            || slurm_config.account.is_some()
+            || slurm_config.mail_user.is_some()
+            || slurm_config.mail_type.is_some()
+            || !slurm_config.additional_options.is_empty()
+        {
+            return None;
+        }
+        Some(SshExecutor {
+            retry: self.retry.merge(&other),
+            host: other.host.unwrap_or(self.host),
+            user: other.user.or(self.user),
+            port: other.port.or(self.port),
+            identity_file: other.identity_file.or(self.identity_file),
+            remote_workdir: other.remote_workdir.unwrap_or(self.remote_workdir),
+            container: other.container.or(self.container),
+            staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
+            cache: other.cache.unwrap_or(self.cache),
+        })
+    }
+}
+
+impl LsfExecutor {
+    pub(crate) fn merge(mut self, other: PartialExecutor) -> Option<LsfExecutor> {
+        let slurm_config = &other.config;
+        if other.max_parallel.is_some()
+            || other.submit_ahead.is_some()
+            || other.rest.is_some()
+            || other.allocation.is_some()
+            || other.sets_slurm_options()
+            || other.sets_ssh_options()
+            || other.sets_condor_options()
+            || other.sets_kubernetes_options()
+            || other.sets_template_options()
+            || slurm_config.tasks.is_some()
+            || slurm_config.nodes.is_some()
+            || slurm_config.partition.is_some()
+            || slurm_config.account.is_some()
+            || slurm_config.mail_user.is_some()
+            || slurm_config.mail_type.is_some()
+        {
+            return None;
+        }
+        Some(LsfExecutor {
+            retry: self.retry.merge(&other),
+            container: other.container.or(self.container),
+            cache: other.cache.unwrap_or(self.cache),
+            poll_rate: other.poll_rate.unwrap_or(self.poll_rate),
+            staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
+            modules: {
+                let mut other_modules = other.modules.unwrap_or_default();
+                other_modules.append(&mut self.modules);
+                other_modules
+            },
+            config: LsfConfig {
+                cpus: other.config.cpus.or(self.config.cpus),
+                memory: other.config.memory.or(self.config.memory),
+                gpus: other.config.gpus.or(self.config.gpus),
+                queue: other.queue.or(self.config.queue),
+                time: other.config.time.or(self.config.time),
+                project: other.project.or(self.config.project),
+                additional_options: {
+                    let mut other_options = other.config.additional_options;
+                    other_options.append(&mut self.config.additional_options);
+                    other_options
+                },
+            },
+        })
+    }
+}
+
+impl PbsExecutor {
+    pub(crate) fn merge(mut self, other: PartialExecutor) -> Option<PbsExecutor> {
+        let slurm_config = &other.config;
+        if other.max_parallel.is_some()
+            || other.submit_ahead.is_some()
+            || other.rest.is_some()
+            || other.allocation.is_some()
+            || other.project.is_some()
+            || other.sets_slurm_options()
+            || other.sets_ssh_options()
+            || other.sets_condor_options()
+            || other.sets_kubernetes_options()
+            || other.sets_template_options()
+            || slurm_config.tasks.is_some()
+            || slurm_config.partition.is_some()
+            || slurm_config.mail_user.is_some()
+            || slurm_config.mail_type.is_some()
+        {
+            return None;
+        }
+        Some(PbsExecutor {
+            retry: self.retry.merge(&other),
+            container: other.container.or(self.container),
+            cache: other.cache.unwrap_or(self.cache),
+            poll_rate: other.poll_rate.unwrap_or(self.poll_rate),
+            staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
+            modules: {
+                let mut other_modules = other.modules.unwrap_or_default();
+                other_modules.append(&mut self.modules);
+                other_modules
+            },
+            config: PbsConfig {
+                cpus: other.config.cpus.or(self.config.cpus),
+                memory: other.config.memory.or(self.config.memory),
+                gpus: other.config.gpus.or(self.config.gpus),
+                nodes: other.config.nodes.or(self.config.nodes),
+                queue: other.queue.or(self.config.queue),
+                time: other.config.time.or(self.config.time),
+                account: other.config.account.or(self.config.account),
+                additional_options: {
+                    let mut other_options = other.config.additional_options;
+                    other_options.append(&mut self.config.additional_options);
+                    other_options
+                },
+            },
+        })
+    }
+}
+
+impl CondorExecutor {
+    pub(crate) fn merge(mut self, other: PartialExecutor) -> Option<CondorExecutor> {
+        let slurm_config = &other.config;
+        if other.max_parallel.is_some()
+            || other.submit_ahead.is_some()
+            || other.rest.is_some()
+            || other.allocation.is_some()
+            || other.modules.is_some()
+            || other.queue.is_some()
+            || other.project.is_some()
+            || other.sets_slurm_options()
+            || other.sets_ssh_options()
+            || other.sets_kubernetes_options()
+            || other.sets_template_options()
+            || slurm_config.tasks.is_some()
+            || slurm_config.nodes.is_some()
+            || slurm_config.partition.is_some()
+            || slurm_config.account.is_some()
+            || slurm_config.mail_user.is_some()
+            || slurm_config.mail_type.is_some()
+        {
+            return None;
+        }
+        Some(CondorExecutor {
+            retry: self.retry.merge(&other),
+            container: other.container.or(self.container),
+            cache: other.cache.unwrap_or(self.cache),
+            poll_rate: other.poll_rate.unwrap_or(self.poll_rate),
+            staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
+            file_transfer: other.file_transfer.unwrap_or(self.file_transfer),
+            cpus: other.config.cpus.or(self.cpus),
+            memory: other.config.memory.or(self.memory),
+            disk: other.disk.or(self.disk),
+            gpus: other.config.gpus.or(self.gpus),
+            time: other.config.time.or(self.time),
+            additional_options: {
+                let mut other_options = other.config.additional_options;
+                other_options.append(&mut self.additional_options);
+                other_options
+            },
+        })
+    }
+}
+
+impl KubernetesExecutor {
+    pub(crate) fn merge(self, other: PartialExecutor) -> Option<KubernetesExecutor> {
+        let slurm_config = &other.config;
+        if other.max_parallel.is_some()
+            || other.submit_ahead.is_some()
+            || other.rest.is_some()
+            || other.allocation.is_some()
+            || other.modules.is_some()
+            || other.queue.is_some()
+            || other.project.is_some()
+            || other.sets_slurm_options()
+            || other.sets_ssh_options()
+            || other.sets_condor_options()
+            || other.sets_template_options()
+            || slurm_config.tasks.is_some()
+            || slurm_config.nodes.is_some()
+            || slurm_config.partition.is_some()
+            || slurm_config.account.is_some()
+            || slurm_config.mail_user.is_some()
+            || slurm_config.mail_type.is_some()
+            || !slurm_config.additional_options.is_empty()
+        {
+            return None;
+        }
+        Some(KubernetesExecutor {
+            retry: self.retry.merge(&other),
+            container: other.container.or(self.container),
+            cache: other.cache.unwrap_or(self.cache),
+            poll_rate: other.poll_rate.unwrap_or(self.poll_rate),
+            staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
+            cluster: KubernetesCluster {
+                kubeconfig: other.kubeconfig.or(self.cluster.kubeconfig),
+                context: other.context.or(self.cluster.context),
+                namespace: other.namespace.or(self.cluster.namespace),
+            },
+            volume: other.volume.unwrap_or(self.volume),
+            cpus: other.config.cpus.or(self.cpus),
+            memory: other.config.memory.or(self.memory),
+            gpus: other.config.gpus.or(self.gpus),
+            time: other.config.time.or(self.time),
+        })
+    }
+}
+
+impl TemplateExecutor {
+    pub(crate) fn merge(self, other: PartialExecutor) -> Option<TemplateExecutor> {
+        let slurm_config = &other.config;
+        if other.max_parallel.is_some()
+            || other.submit_ahead.is_some()
+            || other.rest.is_some()
+            || other.allocation.is_some()
+            || other.modules.is_some()
+            || other.queue.is_some()
+            || other.project.is_some()
+            || other.sets_slurm_options()
+            || other.sets_ssh_options()
+            || other.sets_condor_options()
+            || other.sets_kubernetes_options()
+            || slurm_config.tasks.is_some()
+            || slurm_config.nodes.is_some()
+            || slurm_config.partition.is_some()
+            || slurm_config.account.is_some()
+            || slurm_config.mail_user.is_some()
+            || slurm_config.mail_type.is_some()
+            || !slurm_config.additional_options.is_empty()
+        {
+            return None;
+        }
+        Some(TemplateExecutor {
+            retry: self.retry.merge(&other),
+            header: other.header.unwrap_or(self.header),
+            submit: other.submit.unwrap_or(self.submit),
+            job_id_regex: other.job_id_regex.unwrap_or(self.job_id_regex),
+            status: other.status.unwrap_or(self.status),
+            cancel: other.cancel.unwrap_or(self.cancel),
+            container: other.container.or(self.container),
+            cache: other.cache.unwrap_or(self.cache),
+            poll_rate: other.poll_rate.unwrap_or(self.poll_rate),
+            staging_mode: other.staging_mode.unwrap_or(self.staging_mode),
+            cpus: other.config.cpus.or(self.cpus),
+            memory: other.config.memory.or(self.memory),
+            gpus: other.config.gpus.or(self.gpus),
+            time: other.config.time.or(self.time),
+        })
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 #[doc(hidden)]
@@ -137,82 +619,142 @@ pub struct MaestroConfig {
     pub inputs: HashMap<String, Vec<String>>,
 }
 
-/// A global variable which holds the deserialized Maestro.toml configuration
-pub static MAESTRO_CONFIG: LazyLock<MaestroConfig> = LazyLock::new(|| {
-    let config_file = env::var("MAESTRO_CONFIG").unwrap_or("Maestro.toml".to_string());
-    let file_contents = fs::read_to_string(config_file).unwrap_or_else(|e| {
-        eprintln!("Failed to read config file: {e}");
-        exit(1)
-    });
-    let config: TomlConfig = toml::from_str(&file_contents).unwrap_or_else(|e| {
-        eprintln!("Failed to parse Maestro.toml: {e}");
-        exit(1)
-    });
+impl MaestroConfig {
+    /// Reads and resolves the configuration in the file at `path`
+    pub fn from_path(path: impl AsRef<Path>) -> NodeResult<Self> {
+        Self::read(path.as_ref()).map_err(NodeError::msg)
+    }
+
+    /// Parses and resolves a configuration in the format of Maestro.toml
+    pub fn from_toml(contents: &str) -> NodeResult<Self> {
+        Self::parse(contents).map_err(NodeError::msg)
+    }
+
+    fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {e}", path.display()))?;
+        Self::parse(&contents)
+    }
 
-    let canonicalized_executors = config
-        .executor
-        .iter()
-        .map(|(name, exec)| {
+    fn parse(contents: &str) -> Result<Self, String> {
+        let config: TomlConfig =
+            toml::from_str(contents).map_err(|e| format!("Failed to parse Maestro.toml: {e}"))?;
+
+        let mut executors = HashMap::with_capacity(config.executor.len());
+        for (name, exec) in &config.executor {
             let executor = match exec {
                 MaybeInheritingExecutor::Executor(exec) => exec.clone(),
                 MaybeInheritingExecutor::Inherit { .. } => {
-                    let mut overrides_vec = Vec::new();
-                    let mut seen_map = HashSet::new();
-                    seen_map.insert(name.as_str());
-
-                    fn recurse_executors<'a>(
-                        current: &'a MaybeInheritingExecutor,
-                        overrides_vec: &mut Vec<(&'a Option<PartialExecutor>, &'a str)>,
-                        seen_map: &mut HashSet<&'a str>,
-                        executors: &'a HashMap<String, MaybeInheritingExecutor>,
-                    ) -> &'a MaybeInheritingExecutor {
-                        match current {
-                            MaybeInheritingExecutor::Inherit { inherit, overrides } => {
-                                let inner_executor = match executors.get(inherit) {
-                                    Some(v) => v,
-                                    None => {
-                                        eprintln!("Unable to resolve inherited executor {inherit}");
-                                        exit(1)
-                                    }
-                                };
-                                overrides_vec.push((overrides, inherit));
-                                if !seen_map.insert(inherit) {
-                                    eprintln!("Circular dependence on executor {inherit}");
-                                    exit(1)
-                                }
-                                recurse_executors(inner_executor, overrides_vec, seen_map, executors)
-                            }
-                            MaybeInheritingExecutor::Executor(_) => current,
-                        }
-                    }
-
-                    let final_executor =
-                        recurse_executors(exec, &mut overrides_vec, &mut seen_map, &config.executor);
-                    match final_executor {
-                        MaybeInheritingExecutor::Executor(exe) => {
-                            let mut composite_executor = exe.clone();
-                            for (executor, other_name) in overrides_vec.into_iter().rev() {
-                                composite_executor = match composite_executor.merge(executor.clone()) {
-                                    Some(v) => v,
-                                    None => {
-                                        eprintln!("Attempted to inherit from an executor of a different type: {name} from {other_name}");
-                                        exit(1)
-                                    }
-                                }
-                            }
-                            composite_executor.clone()
-                        }
-                        _ => unreachable!(),
-                    }
+                    resolve_inherited(name, exec, &config.executor)?
+                }
+            };
+            let slurm_config = match &executor {
+                GenericExecutor::Slurm(slurm_exe) => Some(&slurm_exe.config),
+                GenericExecutor::SlurmAllocation(allocation_exe) => {
+                    Some(&allocation_exe.allocation)
                 }
+                _ => None,
             };
-            (name.clone(), executor)
+            if let Some(Err(e)) = slurm_config.map(SlurmConfig::validate) {
+                return Err(format!("Invalid Slurm options for executor {name}: {e}"));
+            }
+            executors.insert(name.clone(), executor);
+        }
+
+        Ok(MaestroConfig {
+            executors,
+            inputs: config.inputs,
+            args: config.args,
         })
-        .collect();
+    }
+}
+
+/// Resolves the executor `name` by laying its overrides over the executors it inherits from
+fn resolve_inherited(
+    name: &str,
+    exec: &MaybeInheritingExecutor,
+    executors: &HashMap<String, MaybeInheritingExecutor>,
+) -> Result<GenericExecutor, String> {
+    let mut overrides_vec = Vec::new();
+    let mut seen_map = HashSet::new();
+    seen_map.insert(name);
 
-    MaestroConfig {
-        executors: canonicalized_executors,
-        inputs: config.inputs,
-        args: config.args,
+    let mut current = exec;
+    let final_executor = loop {
+        match current {
+            MaybeInheritingExecutor::Inherit {
+                inherit,
+                raw_overrides,
+            } => {
+                current = executors
+                    .get(inherit)
+                    .ok_or_else(|| format!("Unable to resolve inherited executor {inherit}"))?;
+                overrides_vec.push((raw_overrides, inherit));
+                if !seen_map.insert(inherit) {
+                    return Err(format!("Circular dependence on executor {inherit}"));
+                }
+            }
+            MaybeInheritingExecutor::Executor(exe) => break exe,
+        }
+    };
+
+    let mut composite_executor = final_executor.clone();
+    for (raw_overrides, other_name) in overrides_vec.into_iter().rev() {
+        composite_executor = composite_executor
+            .merge(raw_overrides)?
+            .ok_or_else(|| {
+                format!(
+                    "Attempted to inherit from an executor of a different type: {name} from {other_name}"
+                )
+            })?;
     }
+    Ok(composite_executor)
+}
+
+/// A global variable which holds the deserialized Maestro.toml configuration
+pub static MAESTRO_CONFIG: LazyLock<MaestroConfig> = LazyLock::new(|| {
+    let config_file = env::var("MAESTRO_CONFIG").unwrap_or("Maestro.toml".to_string());
+    MaestroConfig::read(Path::new(&config_file)).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        exit(1)
+    })
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+[executor.base]
+type = "Slurm"
+cpus = 1
+"#;
+
+    #[test]
+    fn applies_inherited_overrides() {
+        let config =
+            format!("{BASE}\n[executor.big]\ninherit = \"base\"\ncpus = 8\npartition = \"long\"\n");
+        let config = MaestroConfig::from_toml(&config).unwrap();
+        let Some(GenericExecutor::Slurm(slurm_exe)) = config.executors.get("big") else {
+            panic!("big should be a Slurm executor");
+        };
+        assert_eq!(slurm_exe.config.cpus, Some(8));
+        assert_eq!(slurm_exe.config.partition.as_deref(), Some("long"));
+    }
+
+    #[test]
+    fn rejects_unknown_inherited_overrides() {
+        let config = format!("{BASE}\n[executor.big]\ninherit = \"base\"\ncpu = 8\n");
+        let Err(e) = MaestroConfig::parse(&config) else {
+            panic!("the misspelt option should be rejected");
+        };
+        assert!(e.contains("unknown field `cpu`"), "{e}");
+
+        // Overrides of the wrong type are rejected rather than dropped
+        let config = format!("{BASE}\n[executor.big]\ninherit = \"base\"\ncpus = \"eight\"\n");
+        let Err(e) = MaestroConfig::parse(&config) else {
+            panic!("the mistyped option should be rejected");
+        };
+        assert!(e.contains("Invalid inherited overrides"), "{e}");
+    }
+}
@@ -0,0 +1,612 @@
+use crate::{
+    Container, LP, Process,
+    executors::{
+        Executor, Reusable,
+        monitor::{JobMonitor, JobStatus},
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+        scheduler::{self, Scheduler},
+        slurm::{Memory, MemoryConfig, SlurmJobState, SlurmTime, resolve_job},
+    },
+    process::{StagingMode, resolve_input},
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::LazyLock,
+    time::Duration,
+};
+
+/// The session-wide monitor for submitted HTCondor jobs
+static CONDOR_MONITOR: LazyLock<JobMonitor> =
+    LazyLock::new(|| JobMonitor::new("HTCondor", query_condor_q));
+
+/// The status of jobs held in the queue
+const CONDOR_HELD: u32 = 5;
+/// Hold reason code of jobs whose output files could not be transferred back
+const HOLD_TRANSFER_OUTPUT_ERROR: u32 = 12;
+/// Hold reason codes of jobs that exceeded their requested memory or `allowed_execute_duration`
+const HOLD_MEMORY_EXCEEDED: u32 = 34;
+const HOLD_EXECUTE_DURATION_EXCEEDED: u32 = 47;
+
+/// An executor that schedules processes via HTCondor
+///
+/// By default, inputs are sent along with each job and declared outputs are returned
+/// by HTCondor file transfer, so no filesystem has to be shared with the execute nodes
+#[derive(Clone, Deserialize, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct CondorExecutor {
+    pub(crate) container: Option<Container>,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(default = "default_poll_rate")]
+    pub(crate) poll_rate: Duration,
+    #[serde(default)]
+    pub(crate) staging_mode: StagingMode,
+    #[serde(default = "default_file_transfer")]
+    pub(crate) file_transfer: bool,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
+    pub(crate) cpus: Option<u64>,
+    pub(crate) memory: Option<MemoryConfig>,
+    pub(crate) disk: Option<Memory>,
+    pub(crate) gpus: Option<u64>,
+    pub(crate) time: Option<SlurmTime>,
+    /// Extra submit commands, written as `<command> = <value>`
+    #[serde(default)]
+    pub(crate) additional_options: Vec<(String, String)>,
+}
+const fn default_poll_rate() -> Duration {
+    Duration::from_secs(5)
+}
+const fn default_file_transfer() -> bool {
+    true
+}
+
+impl Default for CondorExecutor {
+    fn default() -> Self {
+        Self {
+            container: None,
+            cache: false,
+            poll_rate: default_poll_rate(),
+            staging_mode: StagingMode::Symlink,
+            file_transfer: default_file_transfer(),
+            retry: RetryPolicy::default(),
+            cpus: None,
+            memory: None,
+            disk: None,
+            gpus: None,
+            time: None,
+            additional_options: Vec::new(),
+        }
+    }
+}
+
+impl CondorExecutor {
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    pub fn with_poll_rate(mut self, rate: Duration) -> Self {
+        self.poll_rate = rate;
+        self
+    }
+    pub fn with_staging_mode(mut self, staging_mode: StagingMode) -> Self {
+        self.staging_mode = staging_mode;
+        self
+    }
+    /// Whether inputs and outputs are moved by HTCondor file transfer;
+    /// when disabled, jobs run directly in their workdir on a shared filesystem
+    pub fn with_file_transfer(mut self, file_transfer: bool) -> Self {
+        self.file_transfer = file_transfer;
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
+    pub fn with_cpus(mut self, cpus: u64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+    pub fn with_memory(mut self, memory: MemoryConfig) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+    pub fn with_disk(mut self, disk: Memory) -> Self {
+        self.disk = Some(disk);
+        self
+    }
+    pub fn with_gpus(mut self, gpus: u64) -> Self {
+        self.gpus = Some(gpus);
+        self
+    }
+    pub fn with_time(mut self, time: SlurmTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+    pub fn with_arg<S: ToString>(mut self, command: S, value: S) -> Self {
+        self.additional_options
+            .push((command.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl Escalate for CondorExecutor {
+    fn escalate(&self, escalation: &Escalation) -> Self {
+        let mut escalated = self.clone();
+        escalation.apply(
+            &mut escalated.cpus,
+            &mut escalated.memory,
+            &mut escalated.time,
+        );
+        escalated
+    }
+}
+
+impl Executor for CondorExecutor {
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        run_reusable(self, process, |process| {
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir)
+            })
+        })
+    }
+}
+
+impl Reusable for CondorExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        (
+            "Condor",
+            &self.container,
+            self.cpus,
+            &self.memory,
+            &self.disk,
+            self.gpus,
+            &self.time,
+        )
+            .hash(state);
+    }
+}
+
+impl CondorExecutor {
+    fn exe_attempt(&self, process: &Process, workdir: &Path) -> Result<Vec<PathBuf>, AttemptError> {
+        let ((log_path, mut log_handle), (_, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
+
+        let staging_mode = match self.container {
+            None => &self.staging_mode,
+            Some(_) => &StagingMode::Copy,
+        };
+        let transferred_inputs = if self.file_transfer {
+            // Transferred inputs land in the scratch directory the job starts in
+            let inputs = self.transferred_inputs(process)?;
+            process.stage_inputs_from(&mut launcher_handle, workdir, staging_mode, |_, file| {
+                let file_name = file.file_name().unwrap_or(file.as_os_str());
+                Ok(Path::new("$PWD").join(file_name))
+            })?;
+            inputs
+        } else {
+            process.stage_inputs(&mut launcher_handle, workdir, staging_mode)?;
+            Vec::new()
+        };
+        Process::write_execution(launcher_handle, process, &self.container)?;
+
+        let description_path = workdir.join(".maestro.condor.sub");
+        fs::write(
+            &description_path,
+            self.submit_description(process, workdir, &transferred_inputs),
+        )
+        .map_err(|e| NodeError::msg(format!("Failed to write submit description: {e}")))?;
+
+        let mut condor_submit = Command::new("condor_submit");
+        condor_submit.arg(&description_path).current_dir(workdir);
+        scheduler::run_job(
+            self,
+            process,
+            workdir,
+            condor_submit,
+            &mut log_handle,
+            &log_path,
+        )
+    }
+
+    /// Resolves the inputs of `process` to the absolute paths HTCondor transfers
+    ///
+    /// Transferred files keep only their file name, so inputs sharing one are rejected
+    fn transferred_inputs(&self, process: &Process) -> NodeResult<Vec<PathBuf>> {
+        let mut file_names = HashSet::new();
+        let mut inputs = Vec::new();
+        for (_, file) in &process.inputs {
+            let file_name = file.file_name().ok_or(NodeError::msg(format!(
+                "Could not resolve file name of input {}",
+                file.display()
+            )))?;
+            if !file_names.insert(file_name) {
+                return Err(NodeError::msg(format!(
+                    "Multiple inputs of process {} are named {}, which HTCondor file transfer cannot tell apart",
+                    process.name,
+                    file_name.display()
+                )));
+            }
+            inputs.push(resolve_input(file)?);
+        }
+        Ok(inputs)
+    }
+
+    fn submit_description(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        transferred_inputs: &[PathBuf],
+    ) -> String {
+        let mut description = format!(
+            "universe = vanilla\nexecutable = .maestro.launcher\nbatch_name = {}\n\
+             output = .maestro.job.out\nerror = .maestro.job.err\nlog = .maestro.condor.log\n",
+            process.name
+        );
+        let mut push = |command: &str, value: &dyn std::fmt::Display| {
+            description.push_str(&format!("{command} = {value}\n"));
+        };
+        if let Some(cpus) = self.cpus {
+            push("request_cpus", &cpus);
+        }
+        if let Some(memory) = self.memory {
+            push("request_memory", &memory.total_mb(self.cpus.unwrap_or(1)));
+        }
+        if let Some(disk) = self.disk {
+            push("request_disk", &format!("{}M", disk.as_mb()));
+        }
+        if let Some(gpus) = self.gpus {
+            push("request_gpus", &gpus);
+        }
+        if let Some(time) = self.time {
+            push("allowed_execute_duration", &time.as_secs());
+        }
+
+        if self.file_transfer {
+            let inputs = [Path::new(".maestro.sh")]
+                .into_iter()
+                .chain(transferred_inputs.iter().map(PathBuf::as_path))
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>();
+            let outputs = [".maestro.out", ".maestro.err"]
+                .into_iter()
+                .map(str::to_string)
+                .chain(process.outputs.iter().map(|(_, p)| p.display().to_string()))
+                .collect::<Vec<_>>();
+            push("should_transfer_files", &"YES");
+            push("when_to_transfer_output", &"ON_EXIT");
+            push("preserve_relative_paths", &"true");
+            push("transfer_input_files", &inputs.join(", "));
+            push("transfer_output_files", &outputs.join(", "));
+        } else {
+            push("should_transfer_files", &"NO");
+            push("initialdir", &workdir.display());
+        }
+        for (command, value) in &self.additional_options {
+            push(command, value);
+        }
+        description.push_str("queue\n");
+        description
+    }
+}
+
+impl Scheduler for CondorExecutor {
+    fn monitor(&self) -> &'static JobMonitor {
+        &CONDOR_MONITOR
+    }
+    fn poll_rate(&self) -> Duration {
+        self.poll_rate
+    }
+    fn parse_job_id(&self, stdout: &str) -> Option<String> {
+        // 1 job(s) submitted to cluster 123.
+        let (_, cluster) = stdout.split_once("submitted to cluster ")?;
+        let cluster = cluster.split(|c: char| !c.is_ascii_digit()).next()?;
+        cluster.parse::<u64>().ok().map(|id| id.to_string())
+    }
+    fn cancel(&self, job_id: &str) {
+        let _ = Command::new("condor_rm").arg(job_id).status();
+    }
+    fn collect(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        job_id: &str,
+        log_handle: &mut File,
+        log_path: &Path,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        if let Ok(output) = fs::read_to_string(workdir.join(".maestro.job.out")) {
+            let _ = write!(log_handle, "{output}");
+        }
+        let job = condor_job(job_id).map_err(|e| {
+            NodeError::msg(format!("Failed to query HTCondor for job information: {e}"))
+        })?;
+        let Some(job) = job else {
+            let _ = writeln!(log_handle, "{LP} Job {job_id} is unknown to HTCondor");
+            return resolve_job(process, workdir, None, None, log_handle, log_path);
+        };
+        let _ = writeln!(log_handle, "{LP} Job information\n{}", job.record);
+
+        if job.status == CONDOR_HELD {
+            // Held jobs stay in the queue until released or removed
+            self.cancel(job_id);
+            let Some((job_state, job_status)) = job.held_resolution() else {
+                let _ = writeln!(
+                    log_handle,
+                    "{LP} Job was held: {}\nstderr: .maestro.job.err",
+                    job.hold_reason
+                );
+                return Err(AttemptError::Failed(NodeError::msg(format!(
+                    "Job was held: {}. Logs at {}",
+                    job.hold_reason,
+                    log_path.display()
+                ))));
+            };
+            if job.hold_reason_code == Some(HOLD_TRANSFER_OUTPUT_ERROR) {
+                let _ = writeln!(log_handle, "{LP} Job exited, but {}", job.hold_reason);
+            }
+            return resolve_job(
+                process, workdir, job_state, job_status, log_handle, log_path,
+            );
+        }
+        resolve_job(
+            process,
+            workdir,
+            None,
+            job.exit_status(),
+            log_handle,
+            log_path,
+        )
+    }
+}
+
+const JOB_ATTRIBUTES: [&str; 6] = [
+    "JobStatus",
+    "ExitCode",
+    "ExitBySignal",
+    "ExitSignal",
+    "HoldReasonCode",
+    "HoldReason",
+];
+
+/// The exit code and signal of a job
+type JobExit = (u32, u32);
+
+/// The final attributes of an HTCondor job used by maestro
+struct CondorJob {
+    status: u32,
+    exit_code: Option<u32>,
+    exit_signal: Option<u32>,
+    hold_reason_code: Option<u32>,
+    hold_reason: String,
+    /// The attributes as reported, for the log
+    record: String,
+}
+
+/// Looks up the final attributes of `job_id`, in the queue if it is held there or in the history
+fn condor_job(job_id: &str) -> io::Result<Option<CondorJob>> {
+    for command in ["condor_q", "condor_history"] {
+        let output = Command::new(command)
+            .arg(job_id)
+            .arg("-af:t")
+            .args(JOB_ATTRIBUTES)
+            .output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "{command} exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let Some(line) = stdout.lines().next() {
+            return Ok(Some(CondorJob::parse(line)));
+        }
+    }
+    Ok(None)
+}
+
+impl CondorJob {
+    /// Parses a line of `-af:t` output listing [`JOB_ATTRIBUTES`]
+    fn parse(line: &str) -> Self {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let number = |n: usize| fields.get(n).and_then(|field| field.parse::<u32>().ok());
+        let record = JOB_ATTRIBUTES
+            .iter()
+            .zip(&fields)
+            .map(|(attribute, value)| format!("{attribute}: {value}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        CondorJob {
+            status: number(0).unwrap_or_default(),
+            exit_code: number(1),
+            exit_signal: (fields.get(2) == Some(&"true"))
+                .then(|| number(3))
+                .flatten(),
+            hold_reason_code: number(4),
+            hold_reason: fields.get(5).unwrap_or(&"undefined").to_string(),
+            record,
+        }
+    }
+
+    /// The exit code and signal of the job, if it exited
+    fn exit_status(&self) -> Option<JobExit> {
+        match (self.exit_code, self.exit_signal) {
+            (_, Some(signal)) => Some((0, signal)),
+            (Some(code), None) => Some((code, 0)),
+            (None, None) => None,
+        }
+    }
+
+    /// The state and exit status a held job resolves to,
+    /// or `None` if it was held for reasons other than its own outcome
+    fn held_resolution(&self) -> Option<(Option<SlurmJobState>, Option<JobExit>)> {
+        match self.hold_reason_code {
+            Some(HOLD_MEMORY_EXCEEDED) => Some((Some(SlurmJobState::OutOfMemory), None)),
+            Some(HOLD_EXECUTE_DURATION_EXCEEDED) => Some((Some(SlurmJobState::Timeout), None)),
+            // A job that exits without writing a declared output fails its output transfer,
+            // which its exit status and the output check explain better
+            Some(HOLD_TRANSFER_OUTPUT_ERROR) => Some((None, Some(self.exit_status()?))),
+            _ => None,
+        }
+    }
+}
+
+/// Queries the states of `job_ids` in a single condor_q call; jobs absent from the output have left the queue
+fn query_condor_q(job_ids: &[String]) -> io::Result<HashMap<String, JobStatus>> {
+    let output = Command::new("condor_q")
+        .args(job_ids)
+        .args(["-af:t", "ClusterId", "JobStatus"])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "condor_q exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut states = HashMap::new();
+    for line in stdout.lines() {
+        let Some((id, status)) = line.split_once('\t') else {
+            continue;
+        };
+        let status = match status.trim() {
+            // Idle or suspended
+            "1" | "7" => JobStatus::Pending,
+            // Removed, completed or held
+            "3" | "4" | "5" => JobStatus::Finished,
+            _ => JobStatus::Running,
+        };
+        states.insert(id.to_string(), status);
+    }
+    Ok(states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::slurm::Memory;
+
+    fn process() -> Process {
+        Process::new(
+            "align reads".to_string(),
+            vec![("reads".into(), PathBuf::from("/data/reads.fq"))],
+            vec![],
+            vec![("bam".into(), PathBuf::from("out/aligned.bam"))],
+            "true".into(),
+        )
+    }
+
+    #[test]
+    fn describes_requests_and_file_transfer() {
+        let executor = CondorExecutor::default()
+            .with_cpus(4)
+            .with_memory(MemoryConfig::PerCpu(Memory::from_mb(1024)))
+            .with_disk(Memory::from_mb(2048))
+            .with_time(SlurmTime::new(0, 1, 30, 0).unwrap())
+            .with_arg("requirements", "OpSysMajorVer == 9");
+        let description = executor.submit_description(
+            &process(),
+            Path::new("/work/align"),
+            &[PathBuf::from("/data/reads.fq")],
+        );
+        for line in [
+            "batch_name = align reads",
+            "request_cpus = 4",
+            "request_memory = 4096",
+            "request_disk = 2048M",
+            "allowed_execute_duration = 5400",
+            "should_transfer_files = YES",
+            "transfer_input_files = .maestro.sh, /data/reads.fq",
+            "transfer_output_files = .maestro.out, .maestro.err, out/aligned.bam",
+            "requirements = OpSysMajorVer == 9",
+        ] {
+            assert!(
+                description.lines().any(|l| l == line),
+                "missing {line:?} in\n{description}"
+            );
+        }
+        assert!(description.ends_with("queue\n"));
+    }
+
+    #[test]
+    fn runs_in_workdir_without_file_transfer() {
+        let executor = CondorExecutor::default().with_file_transfer(false);
+        let description = executor.submit_description(&process(), Path::new("/work/align"), &[]);
+        assert!(description.contains("should_transfer_files = NO\n"));
+        assert!(description.contains("initialdir = /work/align\n"));
+        assert!(!description.contains("transfer_output_files"));
+        assert!(!description.contains("allowed_execute_duration"));
+    }
+
+    #[test]
+    fn parses_job_id_from_condor_submit() {
+        let executor = CondorExecutor::default();
+        let stdout = "Submitting job(s).\n1 job(s) submitted to cluster 4213.\n";
+        assert_eq!(executor.parse_job_id(stdout).as_deref(), Some("4213"));
+        assert_eq!(executor.parse_job_id("ERROR: Failed to connect"), None);
+    }
+
+    #[test]
+    fn resolves_held_jobs_by_hold_reason_code() {
+        let held = |code: u32, exit_code: &str| {
+            CondorJob::parse(&format!(
+                "5\t{exit_code}\tfalse\tundefined\t{code}\tSome hold reason"
+            ))
+        };
+        assert!(matches!(
+            held(HOLD_MEMORY_EXCEEDED, "undefined").held_resolution(),
+            Some((Some(SlurmJobState::OutOfMemory), None))
+        ));
+        assert!(matches!(
+            held(HOLD_EXECUTE_DURATION_EXCEEDED, "undefined").held_resolution(),
+            Some((Some(SlurmJobState::Timeout), None))
+        ));
+        assert!(matches!(
+            held(HOLD_TRANSFER_OUTPUT_ERROR, "3").held_resolution(),
+            Some((None, Some((3, 0))))
+        ));
+        // Without an exit code, the hold itself is the failure
+        assert!(
+            held(HOLD_TRANSFER_OUTPUT_ERROR, "undefined")
+                .held_resolution()
+                .is_none()
+        );
+        // allowed_job_duration, which maestro does not set
+        assert!(held(46, "undefined").held_resolution().is_none());
+        assert!(held(1, "undefined").held_resolution().is_none());
+    }
+
+    #[test]
+    fn parses_exit_status_of_finished_jobs() {
+        let job = CondorJob::parse("4\t0\tfalse\tundefined\tundefined\tundefined");
+        assert_eq!(job.status, 4);
+        assert_eq!(job.exit_status(), Some((0, 0)));
+        let job = CondorJob::parse("4\tundefined\ttrue\t9\tundefined\tundefined");
+        assert_eq!(job.exit_status(), Some((0, 9)));
+        assert!(job.record.contains("ExitSignal: 9"));
+    }
+}
@@ -0,0 +1,675 @@
+use crate::{
+    Process, WorkflowResult,
+    executors::{
+        self, Reusable, Reuse,
+        retry::{AttemptError, AttemptTracker, Escalate, RetryPolicy, Settled},
+    },
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// The state of a submitted job, as returned by [`JobExecutor::poll`]
+pub enum JobPoll {
+    /// The job waits to start, such as in the queue of its scheduler or before a retry
+    Pending,
+    Running,
+    /// The job ended, with the outcome of its last attempt
+    ///
+    /// The outcome is handed out once; later polls of the job return an error
+    Finished(WorkflowResult),
+}
+
+/// An executor that runs processes as jobs which are submitted, polled and cancelled separately
+///
+/// Unlike [`Executor::exe`](crate::executors::Executor::exe), which occupies a thread until
+/// its process finishes, submitted jobs are driven by polling them, so a single thread can
+/// oversee any number of them. Failed attempts are retried under the retry policy of the
+/// executor as polling goes along
+pub trait JobExecutor {
+    /// A handle to a submitted job
+    type Job: Send + 'static;
+
+    /// Submits `process`, returning as soon as it is handed to the executor
+    fn submit(&self, process: Process) -> NodeResult<Self::Job>;
+    /// Checks on a submitted job without waiting for it
+    fn poll(&self, job: &mut Self::Job) -> JobPoll;
+    /// Cancels a submitted job on a best-effort basis
+    fn cancel(&self, job: &mut Self::Job);
+    /// How often submitted jobs are worth polling
+    fn poll_rate(&self) -> Duration;
+
+    /// Submits `process` and polls it to completion, without blocking the async runtime
+    ///
+    /// Submission and polling run on the blocking thread pool of the runtime.
+    /// The job is cancelled if the returned future is dropped before it finishes
+    #[cfg(feature = "tokio")]
+    fn exe_async(
+        &self,
+        process: Process,
+    ) -> impl std::future::Future<Output = WorkflowResult> + Send + 'static
+    where
+        Self: Clone + Send + Sync + Sized + 'static,
+    {
+        let executor = self.clone();
+        async move {
+            // The guard is set up before submitting, so that a job submitted after the future
+            // was dropped is still cancelled
+            let mut guard = CancelOnDrop {
+                executor,
+                slot: std::sync::Arc::new(std::sync::Mutex::new(JobSlot {
+                    job: None,
+                    abandoned: false,
+                })),
+                finished: false,
+            };
+            let (submitter, slot) = (guard.executor.clone(), guard.slot.clone());
+            let submitted = tokio::task::spawn_blocking(move || {
+                let mut job = submitter.submit(process)?;
+                let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+                if slot.abandoned {
+                    drop(slot);
+                    submitter.cancel(&mut job);
+                } else {
+                    slot.job = Some(job);
+                }
+                Ok(())
+            })
+            .await
+            .map_err(join_error);
+            if let Err(e) = submitted.and_then(|submitted| submitted) {
+                guard.finished = true;
+                return Err(e);
+            }
+            loop {
+                let (executor, slot) = (guard.executor.clone(), guard.slot.clone());
+                let poll = tokio::task::spawn_blocking(move || {
+                    let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+                    match slot.job.as_mut() {
+                        Some(job) => executor.poll(job),
+                        None => JobPoll::Finished(Err(NodeError::msg("The job was cancelled"))),
+                    }
+                })
+                .await
+                .map_err(join_error)?;
+                if let JobPoll::Finished(result) = poll {
+                    guard.finished = true;
+                    return result;
+                }
+                tokio::time::sleep(guard.executor.poll_rate()).await;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn join_error(e: tokio::task::JoinError) -> NodeError {
+    NodeError::msg(format!("Job polling task failed: {e}"))
+}
+
+/// The job of a [`JobExecutor::exe_async`] future, once submitted
+#[cfg(feature = "tokio")]
+struct JobSlot<J> {
+    job: Option<J>,
+    /// Set once the future is dropped, so that a job submitted later is cancelled right away
+    abandoned: bool,
+}
+
+/// Cancels the held job when dropped, unless it finished
+///
+/// The cancellation runs on a blocking thread, as it may wait on the scheduler
+/// or on a poll of the job still in progress
+#[cfg(feature = "tokio")]
+struct CancelOnDrop<E: JobExecutor + Clone + Send + 'static> {
+    executor: E,
+    slot: std::sync::Arc<std::sync::Mutex<JobSlot<E::Job>>>,
+    finished: bool,
+}
+#[cfg(feature = "tokio")]
+impl<E: JobExecutor + Clone + Send + 'static> Drop for CancelOnDrop<E> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let (executor, slot) = (self.executor.clone(), self.slot.clone());
+        let cancel = move || {
+            let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+            slot.abandoned = true;
+            if let Some(mut job) = slot.job.take() {
+                drop(slot);
+                executor.cancel(&mut job);
+            }
+        };
+        // Outside a runtime, such as during its shutdown, a thread of its own cancels the job
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(cancel)),
+            Err(_) => drop(std::thread::spawn(cancel)),
+        }
+    }
+}
+
+/// The state of a single attempt, as reported by [`PolledExecutor::check_attempt`]
+pub(crate) enum AttemptPoll {
+    Pending,
+    Running,
+    Finished(Result<Vec<PathBuf>, AttemptError>),
+}
+
+/// An executor whose attempts can be started and checked on without blocking
+pub(crate) trait PolledExecutor: Escalate + Reusable {
+    /// A started attempt
+    type Attempt: Send;
+
+    fn retry_policy(&self) -> &RetryPolicy;
+    /// Prepares and starts an attempt at running `process` in `workdir`
+    fn start_attempt(
+        &self,
+        process: &Process,
+        workdir: &Path,
+    ) -> Result<Self::Attempt, AttemptError>;
+    fn check_attempt(
+        &self,
+        attempt: &mut Self::Attempt,
+        process: &Process,
+        workdir: &Path,
+    ) -> AttemptPoll;
+    fn cancel_attempt(&self, attempt: &mut Self::Attempt);
+}
+
+enum Phase<A> {
+    /// The next attempt starts once its backoff has passed
+    Waiting(Instant),
+    Active(A),
+    Finished(Option<WorkflowResult>),
+}
+
+/// A process run through a [`PolledExecutor`], across all of its attempts
+///
+/// The running attempt is cancelled if the job is dropped before it finishes
+pub(crate) struct PolledJob<E: PolledExecutor> {
+    process: Process,
+    workdir: PathBuf,
+    attempts: AttemptTracker<E>,
+    phase: Phase<E::Attempt>,
+    /// The key the outputs are cached under once the job succeeds, if caching is enabled
+    cache_key: Option<String>,
+}
+
+impl<E: PolledExecutor> PolledJob<E> {
+    /// Starts the first attempt at running `process`,
+    /// unless the outputs of an earlier run can be reused
+    pub(crate) fn submit(executor: &E, process: Process) -> NodeResult<Self> {
+        let (workdir, phase, cache_key) = match executors::reuse(executor, &process, false)? {
+            Reuse::Outputs(outputs) => {
+                let workdir = outputs.last().cloned().unwrap_or_default();
+                (workdir, Phase::Finished(Some(Ok(outputs))), None)
+            }
+            Reuse::Run(cache_key) => (
+                process.create_workdir()?,
+                Phase::Waiting(Instant::now()),
+                cache_key,
+            ),
+        };
+        let mut job = Self {
+            process,
+            workdir,
+            attempts: AttemptTracker::new(executor),
+            phase,
+            cache_key,
+        };
+        // Errors that end the job before it was ever submitted are returned right away
+        match job.poll(executor.retry_policy()) {
+            JobPoll::Finished(Err(e)) => Err(e),
+            JobPoll::Finished(Ok(outputs)) => {
+                job.phase = Phase::Finished(Some(Ok(outputs)));
+                Ok(job)
+            }
+            JobPoll::Pending | JobPoll::Running => Ok(job),
+        }
+    }
+
+    /// Advances the job, starting new attempts as earlier ones fail
+    pub(crate) fn poll(&mut self, policy: &RetryPolicy) -> JobPoll {
+        loop {
+            let result = match &mut self.phase {
+                Phase::Finished(result) => {
+                    return JobPoll::Finished(result.take().unwrap_or_else(|| {
+                        Err(NodeError::msg(format!(
+                            "The outcome of process {} was already collected",
+                            self.process.name
+                        )))
+                    }));
+                }
+                Phase::Waiting(until) => {
+                    if Instant::now() < *until {
+                        return JobPoll::Pending;
+                    }
+                    let current = self.attempts.current();
+                    match current.start_attempt(&self.process, &self.workdir) {
+                        Ok(attempt) => {
+                            self.phase = Phase::Active(attempt);
+                            continue;
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                Phase::Active(attempt) => {
+                    let current = self.attempts.current();
+                    match current.check_attempt(attempt, &self.process, &self.workdir) {
+                        AttemptPoll::Pending => return JobPoll::Pending,
+                        AttemptPoll::Running => return JobPoll::Running,
+                        AttemptPoll::Finished(result) => result,
+                    }
+                }
+            };
+            self.phase =
+                match policy.settle(&mut self.attempts, &self.process, &self.workdir, result) {
+                    Settled::Done(result) => {
+                        let cache_handle =
+                            self.cache_key.take().map(|key| (key, self.process.clone()));
+                        Phase::Finished(Some(executors::finish(result, cache_handle)))
+                    }
+                    Settled::Retry(backoff) => Phase::Waiting(Instant::now() + backoff),
+                };
+        }
+    }
+
+    /// Cancels the running attempt, ending the job
+    pub(crate) fn cancel(&mut self) {
+        if let Phase::Active(attempt) = &mut self.phase {
+            self.attempts.current().cancel_attempt(attempt);
+        }
+        if !matches!(self.phase, Phase::Finished(_)) {
+            self.phase = Phase::Finished(Some(Err(NodeError::msg(format!(
+                "Process {} was cancelled",
+                self.process.name
+            )))));
+        }
+    }
+
+    /// The number of the current attempt, starting at 1
+    pub(crate) fn attempt(&self) -> u32 {
+        self.attempts.attempt_number()
+    }
+    pub(crate) fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+    /// The running attempt, if any
+    pub(crate) fn active(&self) -> Option<&E::Attempt> {
+        match &self.phase {
+            Phase::Active(attempt) => Some(attempt),
+            _ => None,
+        }
+    }
+}
+
+impl<E: PolledExecutor> Drop for PolledJob<E> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Session,
+        config::MaestroConfig,
+        executors::retry::{AttemptOutcome, AttemptRecord, Escalation},
+    };
+    use std::{
+        collections::{HashMap, VecDeque},
+        env, fs,
+        hash::{Hash, Hasher},
+        process,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    /// How a scripted attempt ends
+    #[derive(Clone, Copy)]
+    enum Outcome {
+        Succeed,
+        Fail,
+        Fatal,
+        /// Fails before the attempt is started
+        Reject,
+    }
+
+    /// What happened to the attempts of a [`ScriptedExecutor`]
+    #[derive(Default)]
+    struct Log {
+        outcomes: VecDeque<Outcome>,
+        started: u32,
+        cancelled: u32,
+    }
+
+    /// An executor whose attempts run for `polls` polls and end as scripted
+    #[derive(Clone)]
+    struct ScriptedExecutor {
+        retry: RetryPolicy,
+        cache: bool,
+        polls: u32,
+        /// How long submitting a job takes
+        submit_delay: Duration,
+        log: Arc<Mutex<Log>>,
+    }
+
+    impl ScriptedExecutor {
+        fn new(outcomes: &[Outcome]) -> Self {
+            Self {
+                retry: RetryPolicy::default()
+                    .with_retries(1)
+                    .with_retry_backoff(Duration::ZERO),
+                cache: false,
+                polls: 1,
+                submit_delay: Duration::ZERO,
+                log: Arc::new(Mutex::new(Log {
+                    outcomes: outcomes.iter().copied().collect(),
+                    ..Default::default()
+                })),
+            }
+        }
+        fn started(&self) -> u32 {
+            self.log.lock().unwrap().started
+        }
+        fn cancelled(&self) -> u32 {
+            self.log.lock().unwrap().cancelled
+        }
+    }
+
+    impl Escalate for ScriptedExecutor {
+        fn escalate(&self, _escalation: &Escalation) -> Self {
+            self.clone()
+        }
+    }
+
+    impl Reusable for ScriptedExecutor {
+        fn cache(&self) -> bool {
+            self.cache
+        }
+        fn hash_execution<H: Hasher>(&self, state: &mut H) {
+            "Scripted".hash(state);
+        }
+    }
+
+    impl JobExecutor for ScriptedExecutor {
+        type Job = PolledJob<ScriptedExecutor>;
+
+        fn submit(&self, process: Process) -> NodeResult<Self::Job> {
+            thread::sleep(self.submit_delay);
+            PolledJob::submit(self, process)
+        }
+        fn poll(&self, job: &mut Self::Job) -> JobPoll {
+            job.poll(&self.retry)
+        }
+        fn cancel(&self, job: &mut Self::Job) {
+            job.cancel();
+        }
+        fn poll_rate(&self) -> Duration {
+            Duration::from_millis(10)
+        }
+    }
+
+    struct ScriptedAttempt {
+        polls_left: u32,
+        outcome: Outcome,
+    }
+
+    impl PolledExecutor for ScriptedExecutor {
+        type Attempt = ScriptedAttempt;
+
+        fn retry_policy(&self) -> &RetryPolicy {
+            &self.retry
+        }
+        fn start_attempt(
+            &self,
+            _process: &Process,
+            _workdir: &Path,
+        ) -> Result<ScriptedAttempt, AttemptError> {
+            let mut log = self.log.lock().unwrap();
+            let outcome = log.outcomes.pop_front().expect("an attempt too many");
+            if let Outcome::Reject = outcome {
+                return Err(AttemptError::Fatal(NodeError::msg("Rejected")));
+            }
+            log.started += 1;
+            Ok(ScriptedAttempt {
+                polls_left: self.polls,
+                outcome,
+            })
+        }
+        fn check_attempt(
+            &self,
+            attempt: &mut ScriptedAttempt,
+            process: &Process,
+            workdir: &Path,
+        ) -> AttemptPoll {
+            if attempt.polls_left > 0 {
+                attempt.polls_left -= 1;
+                return AttemptPoll::Running;
+            }
+            AttemptPoll::Finished(match attempt.outcome {
+                Outcome::Succeed => {
+                    for (_, output) in &process.outputs {
+                        fs::write(workdir.join(output), "done").unwrap();
+                    }
+                    Ok(process.output_paths(workdir.to_path_buf()))
+                }
+                Outcome::Fail => Err(AttemptError::Failed(NodeError::msg("Failed"))),
+                Outcome::Fatal | Outcome::Reject => {
+                    Err(AttemptError::Fatal(NodeError::msg("Fatal")))
+                }
+            })
+        }
+        fn cancel_attempt(&self, _attempt: &mut ScriptedAttempt) {
+            self.log.lock().unwrap().cancelled += 1;
+        }
+    }
+
+    /// A session in a fresh directory unique to `test`, resuming an earlier one if `resume`
+    fn new_session(test: &str, run: &str, resume: bool) -> Session {
+        let dir = env::temp_dir()
+            .join(format!("maestro-job-{}-{test}", process::id()))
+            .join(run);
+        if !resume {
+            let _ = fs::remove_dir_all(&dir);
+        }
+        let config = MaestroConfig {
+            executors: HashMap::new(),
+            args: HashMap::new(),
+            inputs: HashMap::new(),
+        };
+        match resume {
+            true => Session::resume(config, dir).unwrap(),
+            false => Session::new(config, dir).unwrap(),
+        }
+    }
+
+    fn process(session: &Session) -> Process {
+        Process::new(
+            "job".to_string(),
+            vec![],
+            vec![],
+            vec![("out".into(), PathBuf::from("out.txt"))],
+            "true".into(),
+        )
+        .with_session(session)
+    }
+
+    fn poll_to_end<E: PolledExecutor>(
+        job: &mut PolledJob<E>,
+        policy: &RetryPolicy,
+    ) -> WorkflowResult {
+        loop {
+            match job.poll(policy) {
+                JobPoll::Finished(result) => return result,
+                JobPoll::Pending | JobPoll::Running => {}
+            }
+        }
+    }
+
+    #[test]
+    fn polls_attempt_to_completion() {
+        let session = new_session("complete", "run", false);
+        let mut executor = ScriptedExecutor::new(&[Outcome::Succeed]);
+        executor.polls = 2;
+        let mut job = PolledJob::submit(&executor, process(&session)).unwrap();
+        assert_eq!(executor.started(), 1);
+        assert!(matches!(job.poll(&executor.retry), JobPoll::Running));
+        let Ok(outputs) = poll_to_end(&mut job, &executor.retry) else {
+            panic!("the job should succeed");
+        };
+        let workdir = session.workdir().join("job");
+        assert_eq!(outputs, [workdir.join("out.txt"), workdir.clone()]);
+        assert!(workdir.join(".maestro.done").exists());
+        // The outcome is handed out once
+        assert!(matches!(
+            job.poll(&executor.retry),
+            JobPoll::Finished(Err(_))
+        ));
+        drop(job);
+        assert_eq!(executor.cancelled(), 0);
+    }
+
+    #[test]
+    fn retries_failed_attempts() {
+        let session = new_session("retry", "run", false);
+        let executor = ScriptedExecutor::new(&[Outcome::Fail, Outcome::Succeed]);
+        let mut job = PolledJob::submit(&executor, process(&session)).unwrap();
+        assert!(poll_to_end(&mut job, &executor.retry).is_ok());
+        assert_eq!(executor.started(), 2);
+        assert_eq!(job.attempt(), 2);
+
+        let records = AttemptRecord::read(job.workdir()).unwrap();
+        let outcomes: Vec<_> = records.iter().map(|record| record.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [AttemptOutcome::Failed, AttemptOutcome::Succeeded]
+        );
+        assert!(job.workdir().join(".maestro.attempts").join("1").is_dir());
+    }
+
+    #[test]
+    fn gives_up_once_retries_run_out() {
+        let session = new_session("exhausted", "run", false);
+        let executor = ScriptedExecutor::new(&[Outcome::Fail, Outcome::Fail]);
+        let mut job = PolledJob::submit(&executor, process(&session)).unwrap();
+        assert!(poll_to_end(&mut job, &executor.retry).is_err());
+        assert_eq!(executor.started(), 2);
+        assert!(!job.workdir().join(".maestro.done").exists());
+
+        let session = new_session("fatal", "run", false);
+        let executor = ScriptedExecutor::new(&[Outcome::Fatal]);
+        let mut job = PolledJob::submit(&executor, process(&session)).unwrap();
+        assert!(poll_to_end(&mut job, &executor.retry).is_err());
+        assert_eq!(executor.started(), 1);
+    }
+
+    #[test]
+    fn returns_errors_before_submission_from_submit() {
+        let session = new_session("reject", "run", false);
+        let executor = ScriptedExecutor::new(&[Outcome::Reject]);
+        assert!(PolledJob::submit(&executor, process(&session)).is_err());
+    }
+
+    #[test]
+    fn cancels_unfinished_job_on_drop() {
+        let session = new_session("drop", "run", false);
+        let executor = ScriptedExecutor::new(&[Outcome::Succeed]);
+        let job = PolledJob::submit(&executor, process(&session)).unwrap();
+        drop(job);
+        assert_eq!(executor.cancelled(), 1);
+
+        let session = new_session("cancel", "run", false);
+        let executor = ScriptedExecutor::new(&[Outcome::Succeed]);
+        let mut job = PolledJob::submit(&executor, process(&session)).unwrap();
+        job.cancel();
+        assert!(matches!(
+            job.poll(&executor.retry),
+            JobPoll::Finished(Err(_))
+        ));
+        drop(job);
+        assert_eq!(executor.cancelled(), 1);
+    }
+
+    #[test]
+    fn reuses_completed_outputs_when_resuming() {
+        let first = new_session("resume", "run", false);
+        let executor = ScriptedExecutor::new(&[Outcome::Succeed]);
+        let mut job = PolledJob::submit(&executor, process(&first)).unwrap();
+        let outputs = poll_to_end(&mut job, &executor.retry).unwrap();
+
+        let resumed = new_session("resume", "run", true);
+        let executor = ScriptedExecutor::new(&[]);
+        let mut job = PolledJob::submit(&executor, process(&resumed)).unwrap();
+        assert_eq!(poll_to_end(&mut job, &executor.retry).unwrap(), outputs);
+        assert_eq!(executor.started(), 0);
+    }
+
+    #[test]
+    fn caches_outputs_of_successful_jobs() {
+        let first = new_session("cache", "run1", false);
+        let mut executor = ScriptedExecutor::new(&[Outcome::Succeed]);
+        executor.cache = true;
+        let mut job = PolledJob::submit(&executor, process(&first)).unwrap();
+        poll_to_end(&mut job, &executor.retry).unwrap();
+
+        let second = new_session("cache", "run2", false);
+        let mut executor = ScriptedExecutor::new(&[]);
+        executor.cache = true;
+        let mut job = PolledJob::submit(&executor, process(&second)).unwrap();
+        let outputs = poll_to_end(&mut job, &executor.retry).unwrap();
+        assert_eq!(executor.started(), 0);
+        assert_eq!(outputs[0], second.workdir().join("job").join("out.txt"));
+        assert_eq!(fs::read_to_string(&outputs[0]).unwrap(), "done");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn cancels_jobs_submitted_after_the_future_is_dropped() {
+        let session = new_session("async-drop", "run", false);
+        let mut executor = ScriptedExecutor::new(&[Outcome::Succeed]);
+        executor.polls = u32::MAX;
+        executor.submit_delay = Duration::from_millis(200);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let future = executor.exe_async(process(&session));
+        // Drops the future while the job is still being submitted
+        let timed_out = runtime
+            .block_on(async { tokio::time::timeout(Duration::from_millis(20), future).await });
+        assert!(timed_out.is_err());
+        assert_eq!(executor.started(), 0);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while executor.cancelled() == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(executor.started(), 1);
+        assert_eq!(executor.cancelled(), 1);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn cancels_running_jobs_when_the_future_is_dropped() {
+        let session = new_session("async-cancel", "run", false);
+        let mut executor = ScriptedExecutor::new(&[Outcome::Succeed]);
+        executor.polls = u32::MAX;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let future = executor.exe_async(process(&session));
+        let timed_out = runtime
+            .block_on(async { tokio::time::timeout(Duration::from_millis(100), future).await });
+        assert!(timed_out.is_err());
+        assert_eq!(executor.started(), 1);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while executor.cancelled() == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(executor.cancelled(), 1);
+    }
+}
@@ -0,0 +1,764 @@
+use crate::{
+    Container, LP, Process,
+    cache::copy_recursive,
+    executors::{
+        Executor, Reusable,
+        monitor::{JobMonitor, JobStatus},
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+        scheduler::{self, Scheduler},
+        slurm::{MemoryConfig, SlurmJobState, SlurmTime, resolve_job},
+    },
+    process::{StagingMode, resolve_input},
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// The name of the container running each process within its pod
+const CONTAINER_NAME: &str = "maestro";
+/// The volume holding the maestro workdir within each pod
+const VOLUME_NAME: &str = "maestro-workdir";
+/// The resource name GPUs are requested under
+const GPU_RESOURCE: &str = "nvidia.com/gpu";
+
+/// An executor that runs processes as Kubernetes Jobs, driven through kubectl
+///
+/// Each process runs in a pod of the executor's container image, with the maestro workdir
+/// mounted at the same path as on the submitting host from a PVC or hostPath volume
+#[derive(Clone, Deserialize, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct KubernetesExecutor {
+    /// The image of each pod, which must provide bash
+    pub(crate) container: Option<Container>,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(default = "default_poll_rate")]
+    pub(crate) poll_rate: Duration,
+    #[serde(default)]
+    pub(crate) staging_mode: StagingMode,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
+    #[serde(flatten)]
+    pub(crate) cluster: KubernetesCluster,
+    #[serde(default)]
+    pub(crate) volume: KubernetesVolume,
+    pub(crate) cpus: Option<u64>,
+    pub(crate) memory: Option<MemoryConfig>,
+    pub(crate) gpus: Option<u64>,
+    pub(crate) time: Option<SlurmTime>,
+}
+const fn default_poll_rate() -> Duration {
+    Duration::from_secs(5)
+}
+
+impl Default for KubernetesExecutor {
+    fn default() -> Self {
+        Self {
+            container: None,
+            cache: false,
+            poll_rate: default_poll_rate(),
+            staging_mode: StagingMode::Symlink,
+            retry: RetryPolicy::default(),
+            cluster: KubernetesCluster::default(),
+            volume: KubernetesVolume::default(),
+            cpus: None,
+            memory: None,
+            gpus: None,
+            time: None,
+        }
+    }
+}
+
+impl KubernetesExecutor {
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    pub fn with_poll_rate(mut self, rate: Duration) -> Self {
+        self.poll_rate = rate;
+        self
+    }
+    pub fn with_staging_mode(mut self, staging_mode: StagingMode) -> Self {
+        self.staging_mode = staging_mode;
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
+    pub fn with_namespace<S: ToString>(mut self, namespace: S) -> Self {
+        self.cluster.namespace = Some(namespace.to_string());
+        self
+    }
+    pub fn with_context<S: ToString>(mut self, context: S) -> Self {
+        self.cluster.context = Some(context.to_string());
+        self
+    }
+    pub fn with_kubeconfig<P: Into<PathBuf>>(mut self, kubeconfig: P) -> Self {
+        self.cluster.kubeconfig = Some(kubeconfig.into());
+        self
+    }
+    pub fn with_volume(mut self, volume: KubernetesVolume) -> Self {
+        self.volume = volume;
+        self
+    }
+    pub fn with_cpus(mut self, cpus: u64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+    pub fn with_memory(mut self, memory: MemoryConfig) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+    pub fn with_gpus(mut self, gpus: u64) -> Self {
+        self.gpus = Some(gpus);
+        self
+    }
+    pub fn with_time(mut self, time: SlurmTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+/// The cluster and namespace kubectl talks to, defaulting to the current kubeconfig context
+#[derive(Default, Clone, Deserialize, Hash, PartialEq, Eq)]
+pub struct KubernetesCluster {
+    pub kubeconfig: Option<PathBuf>,
+    pub context: Option<String>,
+    pub namespace: Option<String>,
+}
+
+impl KubernetesCluster {
+    /// A kubectl command targeting this cluster
+    fn kubectl(&self) -> Command {
+        let mut kubectl = Command::new("kubectl");
+        if let Some(kubeconfig) = &self.kubeconfig {
+            kubectl.arg("--kubeconfig").arg(kubeconfig);
+        }
+        if let Some(context) = &self.context {
+            kubectl.args(["--context", context]);
+        }
+        if let Some(namespace) = &self.namespace {
+            kubectl.args(["--namespace", namespace]);
+        }
+        kubectl
+    }
+}
+
+/// The volume through which pods share the maestro workdir with the submitting host
+#[derive(Clone, Deserialize, Hash)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum KubernetesVolume {
+    /// A directory on each node, by default at the same path as the maestro workdir
+    HostPath { path: Option<PathBuf> },
+    /// A PersistentVolumeClaim, which the submitting host must see at the maestro workdir
+    Pvc {
+        claim: String,
+        sub_path: Option<String>,
+    },
+}
+impl Default for KubernetesVolume {
+    fn default() -> Self {
+        Self::HostPath { path: None }
+    }
+}
+
+impl Escalate for KubernetesExecutor {
+    fn escalate(&self, escalation: &Escalation) -> Self {
+        let mut escalated = self.clone();
+        escalation.apply(
+            &mut escalated.cpus,
+            &mut escalated.memory,
+            &mut escalated.time,
+        );
+        escalated
+    }
+}
+
+impl Executor for KubernetesExecutor {
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        run_reusable(self, process, |process| {
+            let image = match &self.container {
+                Some(Container::Docker(image) | Container::Podman(image)) => image,
+                Some(Container::Apptainer(_)) | None => {
+                    return Err(NodeError::msg(format!(
+                        "Process {} needs a Docker or Podman image to run on Kubernetes",
+                        process.name
+                    )));
+                }
+            };
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir, image)
+            })
+        })
+    }
+}
+
+impl Reusable for KubernetesExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        (
+            "Kubernetes",
+            &self.container,
+            self.cpus,
+            &self.memory,
+            self.gpus,
+            &self.time,
+        )
+            .hash(state);
+    }
+}
+
+impl KubernetesExecutor {
+    fn exe_attempt(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        image: &str,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        let ((log_path, mut log_handle), (_, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
+
+        // Inputs outside the mounted workdir are copied into it before submission
+        let mount_path = mount_path(process)?;
+        let copied_inputs = workdir.join(".maestro.inputs");
+        process.stage_inputs_from(
+            &mut launcher_handle,
+            workdir,
+            &self.staging_mode,
+            |n, file| {
+                let input = resolve_input(file)?;
+                if input.starts_with(&mount_path) {
+                    return Ok(input);
+                }
+                let file_name = input.file_name().unwrap_or(input.as_os_str());
+                let destination = copied_inputs.join(n.to_string()).join(file_name);
+                copy_recursive(&input, &destination)?;
+                Ok(destination)
+            },
+        )?;
+        // The pod itself runs the image, so the script is executed directly
+        Process::write_execution(launcher_handle, process, &None)?;
+
+        let manifest_path = workdir.join(".maestro.job.json");
+        let manifest = self.job_manifest(process, workdir, image, &mount_path);
+        fs::write(&manifest_path, manifest.to_string())
+            .map_err(|e| NodeError::msg(format!("Failed to write job manifest: {e}")))?;
+
+        let mut kubectl = self.cluster.kubectl();
+        kubectl
+            .args(["create", "--output", "name", "--filename"])
+            .arg(&manifest_path);
+        scheduler::run_job(self, process, workdir, kubectl, &mut log_handle, &log_path)
+    }
+
+    fn job_manifest(&self, process: &Process, workdir: &Path, image: &str, mount: &Path) -> Value {
+        let mut requests = serde_json::Map::new();
+        if let Some(cpus) = self.cpus {
+            requests.insert("cpu".to_string(), json!(cpus.to_string()));
+        }
+        if let Some(memory) = self.memory {
+            let mb = memory.total_mb(self.cpus.unwrap_or(1));
+            requests.insert("memory".to_string(), json!(format!("{mb}Mi")));
+        }
+        if let Some(gpus) = self.gpus {
+            requests.insert(GPU_RESOURCE.to_string(), json!(gpus.to_string()));
+        }
+
+        let (mut volume, sub_path) = match &self.volume {
+            KubernetesVolume::HostPath { path } => (
+                json!({ "hostPath": {
+                    "path": path.as_deref().unwrap_or(mount).display().to_string(),
+                    "type": "Directory",
+                } }),
+                None,
+            ),
+            KubernetesVolume::Pvc { claim, sub_path } => (
+                json!({ "persistentVolumeClaim": { "claimName": claim } }),
+                sub_path.as_ref(),
+            ),
+        };
+        let mut volume_mount = json!({
+            "name": VOLUME_NAME,
+            "mountPath": mount.display().to_string(),
+        });
+        if let Some(sub_path) = sub_path {
+            volume_mount["subPath"] = json!(sub_path);
+        }
+        volume["name"] = json!(VOLUME_NAME);
+
+        let labels = json!({
+            "app.kubernetes.io/managed-by": "maestro",
+            "maestro/process": label_value(&process.name),
+        });
+        let mut spec = json!({
+            // Retries are handled by maestro, which sees every failed attempt
+            "backoffLimit": 0,
+            "template": {
+                "metadata": { "labels": labels },
+                "spec": {
+                    "restartPolicy": "Never",
+                    "containers": [{
+                        "name": CONTAINER_NAME,
+                        "image": image,
+                        "command": ["/bin/bash", ".maestro.launcher"],
+                        "workingDir": workdir.display().to_string(),
+                        "resources": { "requests": requests, "limits": requests },
+                        "volumeMounts": [volume_mount],
+                    }],
+                    "volumes": [volume],
+                },
+            },
+        });
+        if let Some(time) = self.time {
+            spec["activeDeadlineSeconds"] = json!(time.as_secs());
+        }
+        json!({
+            "apiVersion": "batch/v1",
+            "kind": "Job",
+            "metadata": {
+                "generateName": format!("maestro-{}-", label_value(&process.name)),
+                "labels": labels,
+            },
+            "spec": spec,
+        })
+    }
+
+    /// The monitor shared by all Kubernetes executors targeting the same cluster and namespace
+    fn cluster_monitor(&self) -> &'static JobMonitor {
+        static MONITORS: Mutex<Vec<(KubernetesCluster, &'static JobMonitor)>> =
+            Mutex::new(Vec::new());
+        let mut monitors = MONITORS.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, monitor)) = monitors
+            .iter()
+            .find(|(cluster, _)| *cluster == self.cluster)
+        {
+            return monitor;
+        }
+
+        let cluster = self.cluster.clone();
+        let monitor: &'static JobMonitor = Box::leak(Box::new(JobMonitor::new(
+            "Kubernetes",
+            move |job_ids: &[String]| query_jobs(&cluster, job_ids),
+        )));
+        monitors.push((self.cluster.clone(), monitor));
+        monitor
+    }
+}
+
+impl Scheduler for KubernetesExecutor {
+    fn monitor(&self) -> &'static JobMonitor {
+        self.cluster_monitor()
+    }
+    fn poll_rate(&self) -> Duration {
+        self.poll_rate
+    }
+    fn parse_job_id(&self, stdout: &str) -> Option<String> {
+        // job.batch/maestro-align-x7k2p
+        let name = stdout.trim().strip_prefix("job.batch/")?;
+        (!name.is_empty()).then(|| name.to_string())
+    }
+    fn cancel(&self, job_id: &str) {
+        // Deleting the job also deletes its pods
+        let _ = self
+            .cluster
+            .kubectl()
+            .args([
+                "delete",
+                "job",
+                job_id,
+                "--wait=false",
+                "--ignore-not-found",
+            ])
+            .output();
+    }
+    fn collect(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        job_id: &str,
+        log_handle: &mut File,
+        log_path: &Path,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        let job = kubernetes_job(&self.cluster, job_id).map_err(|e| {
+            NodeError::msg(format!(
+                "Failed to query Kubernetes for job information: {e}"
+            ))
+        });
+        let pod = job_pod(&self.cluster, job_id).map_err(|e| {
+            NodeError::msg(format!(
+                "Failed to query Kubernetes for pod information: {e}"
+            ))
+        });
+        if let Ok(Some(pod)) = &pod {
+            match pod_logs(&self.cluster, &pod.name) {
+                Ok(logs) => {
+                    let _ = write!(log_handle, "{logs}");
+                }
+                Err(e) => {
+                    let _ = writeln!(
+                        log_handle,
+                        "{LP} Failed to fetch logs of pod {}: {e}",
+                        pod.name
+                    );
+                }
+            }
+        }
+        // Finished jobs are kept by Kubernetes until deleted
+        self.cancel(job_id);
+
+        let Some(job) = job? else {
+            let _ = writeln!(log_handle, "{LP} Job {job_id} is unknown to Kubernetes");
+            return resolve_job(process, workdir, None, None, log_handle, log_path);
+        };
+        let pod = pod?;
+        let termination = pod.as_ref().and_then(|pod| pod.termination.as_ref());
+        let _ = writeln!(
+            log_handle,
+            "{LP} Job information\nCondition: {}\nPod: {}\nTermination: {}",
+            job.condition.as_deref().unwrap_or("None"),
+            pod.as_ref().map(|pod| pod.name.as_str()).unwrap_or("None"),
+            termination
+                .map(|t| format!("{} (exit code {})", t.reason, t.exit_code))
+                .unwrap_or("None".to_string()),
+        );
+
+        let job_state = if job.condition.as_deref() == Some("DeadlineExceeded") {
+            Some(SlurmJobState::Timeout)
+        } else if termination.is_some_and(|t| t.reason == "OOMKilled") {
+            Some(SlurmJobState::OutOfMemory)
+        } else {
+            None
+        };
+        let job_status = termination.map(|t| match t.signal {
+            Some(signal) => (0, signal),
+            None => (t.exit_code, 0),
+        });
+        resolve_job(
+            process, workdir, job_state, job_status, log_handle, log_path,
+        )
+    }
+}
+
+/// The path at which pods mount the maestro workdir, matching the submitting host
+fn mount_path(process: &Process) -> NodeResult<PathBuf> {
+    let session_dir = &process.session()?.path;
+    let root = session_dir.parent().unwrap_or(session_dir);
+    std::path::absolute(root)
+        .map_err(|e| NodeError::msg(format!("Failed to resolve the maestro workdir: {e}")))
+}
+
+/// Converts `name` into a valid label value and job name component
+fn label_value(name: &str) -> String {
+    let value: String = name
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9') => c,
+            _ => '-',
+        })
+        .collect();
+    // Leaves room for the prefix and random suffix of generated job names
+    value
+        .trim_matches('-')
+        .chars()
+        .take(48)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
+
+/// The final state of a Kubernetes job used by maestro
+struct KubernetesJob {
+    /// The reason of a Failed condition, or the type of the terminal condition
+    condition: Option<String>,
+}
+
+/// How the container of a job's pod terminated
+struct Termination {
+    reason: String,
+    exit_code: u32,
+    signal: Option<u32>,
+}
+
+struct KubernetesPod {
+    name: String,
+    termination: Option<Termination>,
+}
+
+/// Runs a kubectl query that prints JSON, returning None for missing objects
+fn kubectl_json(mut kubectl: Command) -> io::Result<Option<Value>> {
+    let output = kubectl
+        .args(["--output", "json", "--ignore-not-found"])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "kubectl exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    // Missing objects print nothing at all
+    if output.stdout.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    serde_json::from_slice(&output.stdout)
+        .map(Some)
+        .map_err(|e| io::Error::other(format!("Failed to parse kubectl output: {e}")))
+}
+
+/// The objects of a kubectl response, which is a List for several objects
+fn items(value: Option<Value>) -> Vec<Value> {
+    match value {
+        None => Vec::new(),
+        Some(Value::Object(mut object)) if object.get("kind") == Some(&json!("List")) => {
+            match object.remove("items") {
+                Some(Value::Array(items)) => items,
+                _ => Vec::new(),
+            }
+        }
+        Some(object) => vec![object],
+    }
+}
+
+/// The terminal condition of a job, if it has finished
+fn terminal_condition(job: &Value) -> Option<String> {
+    let conditions = job["status"]["conditions"].as_array()?;
+    conditions
+        .iter()
+        .filter(|condition| condition["status"] == "True")
+        .find_map(|condition| match condition["type"].as_str()? {
+            "Complete" => Some("Complete".to_string()),
+            "Failed" => Some(condition["reason"].as_str().unwrap_or("Failed").to_string()),
+            _ => None,
+        })
+}
+
+fn kubernetes_job(cluster: &KubernetesCluster, job_id: &str) -> io::Result<Option<KubernetesJob>> {
+    let mut kubectl = cluster.kubectl();
+    kubectl.args(["get", "job", job_id]);
+    Ok(kubectl_json(kubectl)?.map(|job| KubernetesJob {
+        condition: terminal_condition(&job),
+    }))
+}
+
+/// Looks up the most recently created pod of `job_id`
+fn job_pod(cluster: &KubernetesCluster, job_id: &str) -> io::Result<Option<KubernetesPod>> {
+    let mut kubectl = cluster.kubectl();
+    kubectl.args(["get", "pods", "--selector", &format!("job-name={job_id}")]);
+    let pods = items(kubectl_json(kubectl)?);
+    let Some(pod) = pods.iter().max_by_key(|pod| {
+        pod["metadata"]["creationTimestamp"]
+            .as_str()
+            .unwrap_or_default()
+    }) else {
+        return Ok(None);
+    };
+    let terminated = pod["status"]["containerStatuses"]
+        .as_array()
+        .and_then(|statuses| {
+            statuses
+                .iter()
+                .find(|status| status["name"] == CONTAINER_NAME)
+        })
+        .map(|status| &status["state"]["terminated"])
+        .filter(|terminated| terminated.is_object());
+    Ok(Some(KubernetesPod {
+        name: pod["metadata"]["name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        termination: terminated.map(|terminated| Termination {
+            reason: terminated["reason"]
+                .as_str()
+                .unwrap_or("Unknown")
+                .to_string(),
+            exit_code: terminated["exitCode"].as_u64().unwrap_or(1) as u32,
+            signal: terminated["signal"].as_u64().map(|signal| signal as u32),
+        }),
+    }))
+}
+
+fn pod_logs(cluster: &KubernetesCluster, pod: &str) -> io::Result<String> {
+    let output = cluster
+        .kubectl()
+        .args(["logs", pod, "--container", CONTAINER_NAME])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Queries the states of `job_ids` in a single kubectl call; jobs absent from the output were deleted
+fn query_jobs(
+    cluster: &KubernetesCluster,
+    job_ids: &[String],
+) -> io::Result<HashMap<String, JobStatus>> {
+    let mut kubectl = cluster.kubectl();
+    kubectl.args(["get", "jobs"]).args(job_ids);
+    Ok(items(kubectl_json(kubectl)?)
+        .into_iter()
+        .filter_map(|job| {
+            let name = job["metadata"]["name"].as_str()?.to_string();
+            let status = if terminal_condition(&job).is_some() {
+                JobStatus::Finished
+            } else if job["status"]["ready"].as_u64().unwrap_or_default() > 0 {
+                JobStatus::Running
+            } else {
+                JobStatus::Pending
+            };
+            Some((name, status))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::slurm::Memory;
+
+    fn process(name: &str) -> Process {
+        Process::new(name.to_string(), vec![], vec![], vec![], "true".into())
+    }
+
+    fn job_manifest(executor: &KubernetesExecutor) -> Value {
+        executor.job_manifest(
+            &process("Align Reads_2"),
+            Path::new("/work/session/Align Reads_2"),
+            "ubuntu:24.04",
+            Path::new("/work"),
+        )
+    }
+
+    #[test]
+    fn manifest_requests_and_limits_resources() {
+        let executor = KubernetesExecutor::default()
+            .with_cpus(4)
+            .with_memory(MemoryConfig::PerCpu(Memory::from_mb(1000)))
+            .with_gpus(2)
+            .with_time(SlurmTime::new(0, 1, 0, 30).unwrap());
+        let manifest = job_manifest(&executor);
+        let container = &manifest["spec"]["template"]["spec"]["containers"][0];
+        let resources = json!({ "cpu": "4", "memory": "4000Mi", "nvidia.com/gpu": "2" });
+        assert_eq!(container["resources"]["requests"], resources);
+        assert_eq!(container["resources"]["limits"], resources);
+        assert_eq!(container["image"], "ubuntu:24.04");
+        assert_eq!(container["workingDir"], "/work/session/Align Reads_2");
+        assert_eq!(manifest["spec"]["activeDeadlineSeconds"], 3630);
+        assert_eq!(manifest["spec"]["backoffLimit"], 0);
+    }
+
+    #[test]
+    fn manifest_without_resources_requests_nothing() {
+        let manifest = job_manifest(&KubernetesExecutor::default());
+        let container = &manifest["spec"]["template"]["spec"]["containers"][0];
+        assert_eq!(container["resources"]["requests"], json!({}));
+        assert_eq!(container["resources"]["limits"], json!({}));
+        assert_eq!(manifest["spec"]["activeDeadlineSeconds"], Value::Null);
+    }
+
+    #[test]
+    fn manifest_mounts_host_path_at_workdir() {
+        let manifest = job_manifest(&KubernetesExecutor::default());
+        let pod = &manifest["spec"]["template"]["spec"];
+        assert_eq!(
+            pod["volumes"],
+            json!([{
+                "name": VOLUME_NAME,
+                "hostPath": { "path": "/work", "type": "Directory" },
+            }])
+        );
+        assert_eq!(
+            pod["containers"][0]["volumeMounts"],
+            json!([{ "name": VOLUME_NAME, "mountPath": "/work" }])
+        );
+
+        let executor = KubernetesExecutor::default().with_volume(KubernetesVolume::HostPath {
+            path: Some(PathBuf::from("/mnt/shared/work")),
+        });
+        let manifest = job_manifest(&executor);
+        let pod = &manifest["spec"]["template"]["spec"];
+        assert_eq!(pod["volumes"][0]["hostPath"]["path"], "/mnt/shared/work");
+        assert_eq!(
+            pod["containers"][0]["volumeMounts"][0]["mountPath"],
+            "/work"
+        );
+    }
+
+    #[test]
+    fn manifest_mounts_pvc() {
+        let executor = KubernetesExecutor::default().with_volume(KubernetesVolume::Pvc {
+            claim: "maestro-data".to_string(),
+            sub_path: Some("runs".to_string()),
+        });
+        let manifest = job_manifest(&executor);
+        let pod = &manifest["spec"]["template"]["spec"];
+        assert_eq!(
+            pod["volumes"],
+            json!([{
+                "name": VOLUME_NAME,
+                "persistentVolumeClaim": { "claimName": "maestro-data" },
+            }])
+        );
+        assert_eq!(
+            pod["containers"][0]["volumeMounts"],
+            json!([{ "name": VOLUME_NAME, "mountPath": "/work", "subPath": "runs" }])
+        );
+    }
+
+    #[test]
+    fn manifest_labels_jobs_with_sanitized_name() {
+        let manifest = job_manifest(&KubernetesExecutor::default());
+        assert_eq!(
+            manifest["metadata"]["generateName"],
+            "maestro-align-reads-2-"
+        );
+        let labels = json!({
+            "app.kubernetes.io/managed-by": "maestro",
+            "maestro/process": "align-reads-2",
+        });
+        assert_eq!(manifest["metadata"]["labels"], labels);
+        assert_eq!(manifest["spec"]["template"]["metadata"]["labels"], labels);
+    }
+
+    #[test]
+    fn label_value_is_a_valid_label() {
+        assert_eq!(label_value("hello"), "hello");
+        assert_eq!(label_value("Align Reads_2"), "align-reads-2");
+        assert_eq!(label_value("__private.step__"), "private-step");
+        assert_eq!(label_value("ünïcode"), "n-code");
+        assert_eq!(label_value("!!!"), "");
+
+        let long = label_value(&format!("{}-{}", "a".repeat(47), "b".repeat(20)));
+        assert_eq!(long, "a".repeat(47));
+        let long = label_value(&"x".repeat(100));
+        assert_eq!(long.len(), 48);
+    }
+}
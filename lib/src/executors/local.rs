@@ -1,19 +1,48 @@
 use crate::{
     Container, LP, Process,
-    executors::Executor,
+    executors::{
+        Executor, Reusable,
+        job::{AttemptPoll, JobExecutor, JobPoll, PolledExecutor, PolledJob},
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+        slurm::{MemoryConfig, SlurmTime},
+    },
+    metrics::{ProcessMetrics, hostname, unix_secs},
     process::{CheckTime, StagingMode},
+    shutdown::InFlight,
 };
 use dagger_lib::result::{NodeError, NodeResult};
 use serde::Deserialize;
-use std::{io::Write as _, path::PathBuf, process::Command};
+use std::{
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    os::unix::process::{CommandExt as _, ExitStatusExt as _},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Condvar, LazyLock, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
 /// An executor that directly executes processes
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Deserialize, Default, Hash)]
 #[serde(deny_unknown_fields)]
 pub struct LocalExecutor {
     pub(crate) container: Option<Container>,
     #[serde(default)]
     pub(crate) staging_mode: StagingMode,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
+    pub(crate) cpus: Option<u64>,
+    pub(crate) memory: Option<MemoryConfig>,
+    pub(crate) time: Option<SlurmTime>,
+    pub(crate) max_parallel: Option<u64>,
+    /// The cpu slots shared by the processes of this executor and its clones
+    #[serde(skip)]
+    pub(crate) slots: SlotPoolRef,
 }
 
 impl LocalExecutor {
@@ -25,42 +54,392 @@ impl LocalExecutor {
         self.staging_mode = mode;
         self
     }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
+    pub fn with_cpus(mut self, cpus: u64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+    pub fn with_memory(mut self, memory: MemoryConfig) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+    pub fn with_time(mut self, time: SlurmTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+    /// Limits the number of cpu slots in use by the processes of this executor at once;
+    /// each process occupies as many slots as it requests cpus (one by default).
+    /// Processes start in the order they were queued
+    pub fn with_max_parallel(mut self, slots: u64) -> Self {
+        self.max_parallel = Some(slots);
+        self
+    }
+}
+
+impl Escalate for LocalExecutor {
+    fn escalate(&self, escalation: &Escalation) -> Self {
+        let mut escalated = self.clone();
+        escalation.apply(
+            &mut escalated.cpus,
+            &mut escalated.memory,
+            &mut escalated.time,
+        );
+        escalated
+    }
+}
+
+/// Whether processes can be confined to a transient cgroup v2 scope via systemd
+static SYSTEMD_SCOPES: LazyLock<bool> = LazyLock::new(|| {
+    Path::new("/sys/fs/cgroup/cgroup.controllers").exists()
+        && Command::new("systemd-run")
+            .args(["--user", "--scope", "--quiet", "true"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+});
+/// How long a timed out process may take to exit after SIGTERM before it is killed
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(10);
+const WAIT_POLL_RATE: Duration = Duration::from_millis(100);
+
+/// A handle to the cpu slot pool of a local executor
+///
+/// Every executor built or deserialized gets a pool of its own, which its clones share,
+/// so escalated retries stay within the pool of the executor they escalate
+#[derive(Clone, Default)]
+pub(crate) struct SlotPoolRef(Arc<SlotPool>);
+
+impl Hash for SlotPoolRef {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+#[derive(Default)]
+struct SlotPool {
+    state: Mutex<SlotState>,
+    released: Condvar,
+}
+#[derive(Default)]
+struct SlotState {
+    used: u64,
+    running: usize,
+    queued: usize,
+    /// The ticket handed to the next queued process, and the one allowed to start next
+    next_ticket: u64,
+    serving: u64,
+}
+
+impl SlotPool {
+    /// Blocks until `slots` slots are free within a budget of `capacity`
+    /// and every process queued before it has started
+    ///
+    /// Requests larger than the budget run once nothing else is running
+    fn acquire(self: &Arc<Self>, name: &str, slots: u64, capacity: u64) -> SlotGuard {
+        let slots = slots.clamp(1, capacity.max(1));
+        let fits = |state: &SlotState| state.used == 0 || state.used + slots <= capacity;
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.queued > 0 || !fits(&state) {
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.queued += 1;
+            println!(
+                "{LP} Queued local process {name} (running: {}, queued: {})",
+                state.running, state.queued
+            );
+            state = self
+                .released
+                .wait_while(state, |state| state.serving != ticket || !fits(state))
+                .unwrap_or_else(PoisonError::into_inner);
+            state.queued -= 1;
+            state.serving += 1;
+            // The next in line may fit alongside this process
+            self.released.notify_all();
+            println!(
+                "{LP} Starting local process {name} (running: {}, queued: {})",
+                state.running + 1,
+                state.queued
+            );
+        }
+        state.used += slots;
+        state.running += 1;
+        SlotGuard {
+            pool: self.clone(),
+            slots,
+        }
+    }
+
+    /// Takes `slots` slots if they are free within a budget of `capacity`
+    /// and no process is queued for them, without waiting
+    fn try_acquire(self: &Arc<Self>, slots: u64, capacity: u64) -> Option<SlotGuard> {
+        let slots = slots.clamp(1, capacity.max(1));
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.queued > 0 || (state.used != 0 && state.used + slots > capacity) {
+            return None;
+        }
+        state.used += slots;
+        state.running += 1;
+        Some(SlotGuard {
+            pool: self.clone(),
+            slots,
+        })
+    }
+}
+
+/// Returns its slots to the pool when dropped
+struct SlotGuard {
+    pool: Arc<SlotPool>,
+    slots: u64,
+}
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        let mut state = self
+            .pool
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        state.used -= self.slots;
+        state.running -= 1;
+        self.pool.released.notify_all();
+    }
 }
 
 impl Executor for LocalExecutor {
-    fn exe(&self, mut process: Process) -> NodeResult<Vec<PathBuf>> {
-        let (workdir, (log_path, mut log_handle), (launcher_path, mut launcher_handle)) =
-            process.prep_script_workdir()?;
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        run_reusable(self, process, |process| {
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir)
+            })
+        })
+    }
+}
+
+impl Reusable for LocalExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        (
+            "Local",
+            &self.container,
+            self.cpus,
+            &self.memory,
+            &self.time,
+        )
+            .hash(state);
+    }
+}
+
+impl LocalExecutor {
+    fn exe_attempt(&self, process: &Process, workdir: &Path) -> Result<Vec<PathBuf>, AttemptError> {
+        let mut attempt = self.prepare(process, workdir)?;
+        let slots = self.max_parallel.map(|capacity| {
+            self.slots
+                .0
+                .acquire(&process.name, self.cpus.unwrap_or(1), capacity)
+        });
+        let run = self.spawn(&mut attempt, workdir, slots)?;
+        let (reaped, timed_out) = wait_with_deadline(&run.child, self.time_limit())
+            .map_err(|e| NodeError::msg(format!("Failed to wait on launcher process: {e}")))?;
+        self.finish(&mut attempt, &run, process, workdir, reaped, timed_out)
+    }
+
+    fn time_limit(&self) -> Option<Duration> {
+        self.time.map(|time| Duration::from_secs(time.as_secs()))
+    }
+
+    /// Writes the launcher of `process` and prepares the command running it
+    fn prepare(&self, process: &Process, workdir: &Path) -> NodeResult<LocalAttempt> {
+        let ((log_path, mut log_handle), (launcher_path, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
         let staging_mode = match self.container {
             None => &self.staging_mode,
             Some(_) => &StagingMode::Copy,
         };
-        process.stage_inputs(&mut launcher_handle, &workdir, staging_mode)?;
+        process.stage_inputs(&mut launcher_handle, workdir, staging_mode)?;
         writeln!(
             launcher_handle,
             "echo -e \":: Launching local process\\nstdout: .maestro.out\\nstderr: .maestro.err\""
         )
         .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
-        Process::write_execution(launcher_handle, &process, &self.container)?;
 
-        let output = Command::new(launcher_path)
-            .stdout(log_handle.try_clone()?)
-            .stderr(log_handle.try_clone()?)
-            .current_dir(&workdir)
-            .output()
+        let memory_mb = self
+            .memory
+            .map(|memory| memory.total_mb(self.cpus.unwrap_or(1)));
+        let confinement = match self.container {
+            Some(Container::Docker(_) | Container::Podman(_)) => Confinement::Container,
+            _ if (memory_mb.is_some() || self.cpus.is_some()) && *SYSTEMD_SCOPES => {
+                Confinement::Scope
+            }
+            _ => Confinement::Rlimit,
+        };
+        if confinement == Confinement::Rlimit {
+            if let Some(cpus) = self.cpus {
+                warn_unenforced(
+                    &mut log_handle,
+                    &format!("The limit of {cpus} cpus cannot be enforced without systemd scopes"),
+                );
+            }
+            if let Some(mb) = memory_mb {
+                warn_unenforced(
+                    &mut log_handle,
+                    &format!(
+                        "Without systemd scopes, the memory limit of {mb}M only caps the address space \
+                         through ulimit -v, which does not bound resident memory and has no effect \
+                         on containers"
+                    ),
+                );
+                // Without cgroups, fall back to capping the address space
+                writeln!(launcher_handle, "ulimit -v {}", mb * 1024)
+                    .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+            }
+        }
+        Process::write_limited_execution(
+            launcher_handle,
+            process,
+            &self.container,
+            self.cpus,
+            memory_mb,
+        )?;
+
+        let command = if confinement == Confinement::Scope {
+            let mut command = Command::new("systemd-run");
+            command.args(["--user", "--scope", "--quiet", "--collect"]);
+            if let Some(mb) = memory_mb {
+                command.arg(format!("--property=MemoryMax={mb}M"));
+                command.arg("--property=MemorySwapMax=0");
+            }
+            if let Some(cpus) = self.cpus {
+                command.arg(format!("--property=CPUQuota={}%", cpus * 100));
+            }
+            command.arg(&launcher_path);
+            command
+        } else {
+            Command::new(&launcher_path)
+        };
+        Ok(LocalAttempt {
+            command,
+            log_handle,
+            log_path,
+            memory_mb,
+            confinement,
+            submitted: SystemTime::now(),
+            run: None,
+        })
+    }
+
+    /// Spawns the prepared launcher, holding on to `slots` until the process is reaped
+    fn spawn(
+        &self,
+        attempt: &mut LocalAttempt,
+        workdir: &Path,
+        slots: Option<SlotGuard>,
+    ) -> NodeResult<LocalRun> {
+        let (start_time, started) = (SystemTime::now(), Instant::now());
+        let child = attempt
+            .command
+            .stdout(attempt.log_handle.try_clone()?)
+            .stderr(attempt.log_handle.try_clone()?)
+            .current_dir(workdir)
+            .process_group(0)
+            .spawn()
             .map_err(|e| NodeError::msg(format!("Failed to spawn launcher process: {e}")))?;
+        let in_flight = InFlight::process_group(workdir, child.id());
+        Ok(LocalRun {
+            child,
+            start_time,
+            started,
+            terminated: None,
+            killed: false,
+            _slots: slots,
+            _in_flight: in_flight,
+        })
+    }
+
+    /// Records the metrics of a reaped process and resolves the outcome of its attempt
+    fn finish(
+        &self,
+        attempt: &mut LocalAttempt,
+        run: &LocalRun,
+        process: &Process,
+        workdir: &Path,
+        reaped: Reaped,
+        timed_out: bool,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        let LocalAttempt {
+            log_handle,
+            log_path,
+            memory_mb,
+            confinement,
+            submitted,
+            ..
+        } = attempt;
+        let status = reaped.status;
+        ProcessMetrics {
+            wall_time_secs: Some(run.started.elapsed().as_secs_f64()),
+            cpu_time_secs: Some(reaped.cpu_time_secs()),
+            peak_rss_bytes: Some(reaped.peak_rss_bytes()),
+            exit_code: status.code(),
+            signal: status.signal(),
+            node: hostname(),
+            requeues: None,
+            submit_time: unix_secs(*submitted),
+            start_time: unix_secs(run.start_time),
+            end_time: unix_secs(SystemTime::now()),
+        }
+        .write(workdir);
 
-        if !output.status.success() {
+        if timed_out {
+            let _ = writeln!(
+                log_handle,
+                "{LP} Process exceeded its time limit and was killed"
+            );
+            return Err(AttemptError::ResourcesExhausted(NodeError::msg(format!(
+                "Process exceeded its time limit of {}. Logs at {}",
+                self.time.unwrap_or_default(),
+                log_path.display()
+            ))));
+        }
+        if let Some(mb) = *memory_mb
+            && !status.success()
+            && ran_out_of_memory(status, *confinement)
+        {
+            let _ = writeln!(
+                log_handle,
+                "{LP} Process exceeded its memory limit of {mb}M"
+            );
+            return Err(AttemptError::ResourcesExhausted(NodeError::msg(format!(
+                "Process exceeded its memory limit of {mb}M. Logs at {}; stderr at {}",
+                log_path.display(),
+                workdir.join(".maestro.err").display()
+            ))));
+        }
+
+        if !status.success() {
             let _ = writeln!(log_handle, "{LP} Process failed!");
-            if let Some(exit_code) = output.status.code() {
+            if let Some(exit_code) = status.code() {
                 let _ = writeln!(log_handle, "Exit code: {exit_code}");
             }
             let _ = writeln!(log_handle, "stderr at .maestro.err");
-            return Err(NodeError::msg(format!(
+            return Err(AttemptError::Failed(NodeError::msg(format!(
                 "Shell process exited with non-zero exit code. Logs at {}; stderr at {}",
                 log_path.display(),
                 workdir.join(".maestro.err").display()
-            )));
+            ))));
         } else {
             let _ = writeln!(
                 log_handle,
@@ -68,13 +447,351 @@ impl Executor for LocalExecutor {
             );
         }
 
-        process.check_files(CheckTime::Output, Some(&workdir))?;
-        let mut outputs: Vec<_> = process
-            .outputs
-            .iter()
-            .map(|(_, p)| workdir.join(p))
-            .collect();
-        outputs.push(workdir);
-        Ok(outputs)
+        process
+            .check_files(CheckTime::Output, Some(workdir))
+            .map_err(AttemptError::Failed)?;
+        Ok(process.output_paths(workdir.to_path_buf()))
+    }
+}
+
+/// An attempt at running a local process, spawned once its slots are free
+pub(crate) struct LocalAttempt {
+    command: Command,
+    log_handle: File,
+    log_path: PathBuf,
+    memory_mb: Option<u64>,
+    confinement: Confinement,
+    submitted: SystemTime,
+    run: Option<LocalRun>,
+}
+
+/// A spawned local process
+struct LocalRun {
+    child: Child,
+    start_time: SystemTime,
+    started: Instant,
+    /// When the process group was sent SIGTERM for exceeding the time limit
+    terminated: Option<Instant>,
+    killed: bool,
+    _slots: Option<SlotGuard>,
+    _in_flight: InFlight,
+}
+
+impl LocalRun {
+    /// Terminates the process group once `limit` has elapsed,
+    /// and kills it if it outlives the grace period
+    fn enforce_deadline(&mut self, limit: Option<Duration>) {
+        let Some(limit) = limit else {
+            return;
+        };
+        match self.terminated {
+            None if self.started.elapsed() >= limit => {
+                signal_process_group(self.child.id(), "TERM");
+                self.terminated = Some(Instant::now());
+            }
+            Some(terminated) if !self.killed && terminated.elapsed() >= KILL_GRACE_PERIOD => {
+                signal_process_group(self.child.id(), "KILL");
+                self.killed = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A process submitted through [`LocalExecutor`] as a [`JobExecutor`]
+pub struct LocalJob(PolledJob<LocalExecutor>);
+
+impl LocalJob {
+    /// The process id of the current attempt, once it was spawned
+    pub fn pid(&self) -> Option<u32> {
+        let run = self.0.active()?.run.as_ref()?;
+        Some(run.child.id())
+    }
+    /// The number of the current attempt, starting at 1
+    pub fn attempt(&self) -> u32 {
+        self.0.attempt()
+    }
+    pub fn workdir(&self) -> &Path {
+        self.0.workdir()
+    }
+}
+
+impl JobExecutor for LocalExecutor {
+    type Job = LocalJob;
+
+    /// Starts `process`, or leaves it to start on a later poll once slots within
+    /// `max_parallel` are free
+    fn submit(&self, process: Process) -> NodeResult<LocalJob> {
+        PolledJob::submit(self, process).map(LocalJob)
+    }
+    fn poll(&self, job: &mut LocalJob) -> JobPoll {
+        job.0.poll(&self.retry)
+    }
+    fn cancel(&self, job: &mut LocalJob) {
+        job.0.cancel();
+    }
+    fn poll_rate(&self) -> Duration {
+        WAIT_POLL_RATE
+    }
+}
+
+impl PolledExecutor for LocalExecutor {
+    type Attempt = LocalAttempt;
+
+    fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+    fn start_attempt(
+        &self,
+        process: &Process,
+        workdir: &Path,
+    ) -> Result<LocalAttempt, AttemptError> {
+        let mut attempt = self.prepare(process, workdir)?;
+        if let Some(run) = self.try_spawn(&mut attempt, workdir)? {
+            attempt.run = Some(run);
+        }
+        Ok(attempt)
+    }
+    fn check_attempt(
+        &self,
+        attempt: &mut LocalAttempt,
+        process: &Process,
+        workdir: &Path,
+    ) -> AttemptPoll {
+        let mut run = match attempt.run.take() {
+            Some(run) => run,
+            None => match self.try_spawn(attempt, workdir) {
+                Ok(Some(run)) => run,
+                Ok(None) => return AttemptPoll::Pending,
+                Err(e) => return AttemptPoll::Finished(Err(e.into())),
+            },
+        };
+        match wait4(&run.child, false) {
+            Ok(Some(reaped)) => {
+                let timed_out = run.terminated.is_some();
+                AttemptPoll::Finished(
+                    self.finish(attempt, &run, process, workdir, reaped, timed_out),
+                )
+            }
+            Ok(None) => {
+                run.enforce_deadline(self.time_limit());
+                attempt.run = Some(run);
+                AttemptPoll::Running
+            }
+            Err(e) => AttemptPoll::Finished(Err(NodeError::msg(format!(
+                "Failed to wait on launcher process: {e}"
+            ))
+            .into())),
+        }
+    }
+    fn cancel_attempt(&self, attempt: &mut LocalAttempt) {
+        if let Some(run) = attempt.run.take() {
+            signal_process_group(run.child.id(), "KILL");
+            let _ = wait4_blocking(&run.child);
+            let _ = writeln!(attempt.log_handle, "{LP} Process was cancelled");
+        }
+    }
+}
+
+impl LocalExecutor {
+    /// Spawns the prepared launcher if slots are free, without waiting for them
+    fn try_spawn(
+        &self,
+        attempt: &mut LocalAttempt,
+        workdir: &Path,
+    ) -> NodeResult<Option<LocalRun>> {
+        let slots = match self.max_parallel {
+            Some(capacity) => match self.slots.0.try_acquire(self.cpus.unwrap_or(1), capacity) {
+                Some(slots) => Some(slots),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+        self.spawn(attempt, workdir, slots).map(Some)
+    }
+}
+
+/// The exit status and resource usage of a reaped child process
+struct Reaped {
+    status: ExitStatus,
+    usage: libc::rusage,
+}
+
+impl Reaped {
+    /// User and system time spent by the process and its reaped descendants
+    fn cpu_time_secs(&self) -> f64 {
+        let secs = |time: libc::timeval| time.tv_sec as f64 + time.tv_usec as f64 / 1e6;
+        secs(self.usage.ru_utime) + secs(self.usage.ru_stime)
+    }
+
+    fn peak_rss_bytes(&self) -> u64 {
+        // Reported in kilobytes, except on macOS where it is in bytes
+        let unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+        self.usage.ru_maxrss.max(0) as u64 * unit
+    }
+}
+
+/// Reaps `child` through wait4 to collect its resource usage
+///
+/// Returns `None` if `block` is false and the child has not exited yet.
+/// Once reaped, the child must not be waited on through [`Child`] again
+fn wait4(child: &Child, block: bool) -> io::Result<Option<Reaped>> {
+    let options = if block { 0 } else { libc::WNOHANG };
+    let mut status = 0;
+    // SAFETY: rusage is plain data, for which all zeroes is a valid value
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: status and usage are valid for writes for the duration of the call
+        let pid =
+            unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, options, &mut usage) };
+        match pid {
+            0 => return Ok(None),
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+            }
+            _ => {
+                return Ok(Some(Reaped {
+                    status: ExitStatus::from_raw(status),
+                    usage,
+                }));
+            }
+        }
+    }
+}
+
+/// Blocks until `child` exits, then reaps it
+fn wait4_blocking(child: &Child) -> io::Result<Reaped> {
+    wait4(child, true)?.ok_or_else(|| io::Error::other("wait4 returned before the child exited"))
+}
+
+/// Waits for `child` to exit, terminating its process group once `limit` has elapsed
+///
+/// Also returns whether the process was terminated for exceeding its time limit
+fn wait_with_deadline(child: &Child, limit: Option<Duration>) -> io::Result<(Reaped, bool)> {
+    let Some(limit) = limit else {
+        return Ok((wait4_blocking(child)?, false));
+    };
+    let deadline = Instant::now() + limit;
+    loop {
+        if let Some(reaped) = wait4(child, false)? {
+            return Ok((reaped, false));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::sleep(WAIT_POLL_RATE.min(deadline - now));
+    }
+
+    signal_process_group(child.id(), "TERM");
+    let grace_deadline = Instant::now() + KILL_GRACE_PERIOD;
+    loop {
+        if let Some(reaped) = wait4(child, false)? {
+            return Ok((reaped, true));
+        }
+        if Instant::now() >= grace_deadline {
+            signal_process_group(child.id(), "KILL");
+            return Ok((wait4_blocking(child)?, true));
+        }
+        thread::sleep(WAIT_POLL_RATE);
+    }
+}
+
+pub(crate) fn signal_process_group(pgid: u32, signal: &str) {
+    let _ = Command::new("kill")
+        .arg(format!("-{signal}"))
+        .arg("--")
+        .arg(format!("-{pgid}"))
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// How the resource limits of a local process are enforced
+#[derive(Clone, Copy, PartialEq)]
+enum Confinement {
+    /// A transient cgroup v2 scope run through systemd
+    Scope,
+    /// The docker or podman container the process runs in
+    Container,
+    /// Only `ulimit -v`, as cpus cannot be limited without cgroups
+    Rlimit,
+}
+
+/// Reports a limit that cannot be enforced in the log of the process, and once on stderr
+fn warn_unenforced(log_handle: &mut File, warning: &str) {
+    static WARNED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    let _ = writeln!(log_handle, "{LP} Warning: {warning}");
+    let mut warned = WARNED.lock().unwrap_or_else(PoisonError::into_inner);
+    if !warned.iter().any(|known| known == warning) {
+        eprintln!("{LP} Warning: {warning}");
+        warned.push(warning.to_string());
+    }
+}
+
+/// Determines from its exit status whether a failed process was most likely
+/// terminated for exceeding its memory limit
+///
+/// The cgroup and container OOM killers send SIGKILL, which the launcher reports as exit code
+/// 137 when it lands on one of its children. Under `ulimit -v` allocations fail instead,
+/// which most runtimes answer by aborting
+fn ran_out_of_memory(status: ExitStatus, confinement: Confinement) -> bool {
+    let terminated_by =
+        |signal: i32| status.signal() == Some(signal) || status.code() == Some(128 + signal);
+    match confinement {
+        Confinement::Scope | Confinement::Container => terminated_by(libc::SIGKILL),
+        Confinement::Rlimit => terminated_by(libc::SIGKILL) || terminated_by(libc::SIGABRT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gives_each_executor_its_own_pool() {
+        let first = LocalExecutor::default().with_max_parallel(2);
+        let second = LocalExecutor::default().with_max_parallel(2);
+        assert!(!Arc::ptr_eq(&first.slots.0, &second.slots.0));
+
+        let config: LocalExecutor = toml::from_str("max_parallel = 2").unwrap();
+        assert!(!Arc::ptr_eq(&first.slots.0, &config.slots.0));
+    }
+
+    #[test]
+    fn escalated_executors_keep_their_pool() {
+        let executor = LocalExecutor::default().with_max_parallel(4).with_cpus(1);
+        let escalated = executor.escalate(&Escalation {
+            memory: None,
+            time: None,
+            cpus: Some(1),
+        });
+        assert_eq!(escalated.cpus, Some(2));
+        assert!(Arc::ptr_eq(&executor.slots.0, &escalated.slots.0));
+
+        let _running = executor.slots.0.try_acquire(3, 4).unwrap();
+        assert!(escalated.slots.0.try_acquire(2, 4).is_none());
+    }
+
+    #[test]
+    fn queued_processes_start_before_later_ones() {
+        let pool = Arc::new(SlotPool::default());
+        let running = pool.try_acquire(1, 2).unwrap();
+
+        let waiter = thread::spawn({
+            let pool = pool.clone();
+            move || drop(pool.acquire("wide", 2, 2))
+        });
+        while pool.state.lock().unwrap().queued == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        // A slot is free, but the queued process comes first
+        assert!(pool.try_acquire(1, 2).is_none());
+
+        drop(running);
+        waiter.join().unwrap();
+        assert!(pool.try_acquire(2, 2).is_some());
     }
 }
@@ -0,0 +1,489 @@
+use crate::{
+    Container, LP, Process,
+    executors::{
+        Executor, Reusable,
+        monitor::{JobMonitor, JobStatus},
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+        scheduler::{self, Scheduler},
+        slurm::{MemoryConfig, SlurmJobState, SlurmTime, resolve_job},
+    },
+    process::StagingMode,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::LazyLock,
+    time::Duration,
+};
+
+/// The session-wide monitor for submitted LSF jobs
+static LSF_MONITOR: LazyLock<JobMonitor> = LazyLock::new(|| JobMonitor::new("LSF", query_bjobs));
+
+/// An executor that schedules processes via IBM Spectrum LSF
+#[derive(Clone, Deserialize, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct LsfExecutor {
+    pub(crate) container: Option<Container>,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(default = "default_poll_rate")]
+    pub(crate) poll_rate: Duration,
+    #[serde(default)]
+    pub(crate) staging_mode: StagingMode,
+    #[serde(default)]
+    pub(crate) modules: Vec<String>,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
+    #[serde(flatten)]
+    pub(crate) config: LsfConfig,
+}
+const fn default_poll_rate() -> Duration {
+    Duration::from_secs(5)
+}
+
+impl Default for LsfExecutor {
+    fn default() -> Self {
+        Self {
+            container: None,
+            cache: false,
+            poll_rate: default_poll_rate(),
+            staging_mode: StagingMode::Symlink,
+            modules: Vec::new(),
+            retry: RetryPolicy::default(),
+            config: LsfConfig::default(),
+        }
+    }
+}
+
+impl LsfExecutor {
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    pub fn with_poll_rate(mut self, rate: Duration) -> Self {
+        self.poll_rate = rate;
+        self
+    }
+    pub fn with_staging_mode(mut self, staging_mode: StagingMode) -> Self {
+        self.staging_mode = staging_mode;
+        self
+    }
+    pub fn with_module<S: ToString>(mut self, module: S) -> Self {
+        self.modules.push(module.to_string());
+        self
+    }
+    pub fn with_modules<S: ToString, M: IntoIterator<Item = S>>(mut self, modules: M) -> Self {
+        let transformed_modules = modules.into_iter().map(|module| module.to_string());
+        self.modules.extend(transformed_modules);
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
+    pub fn with_config(mut self, config: LsfConfig) -> Self {
+        self.config = config;
+        self
+    }
+    pub fn map_config<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(LsfConfig) -> LsfConfig,
+    {
+        self.config = (f)(self.config);
+        self
+    }
+}
+
+/// LSF configuration options
+#[derive(Default, Clone, Deserialize, Hash)]
+pub struct LsfConfig {
+    pub cpus: Option<u64>,
+    /// The memory reserved for the job, split evenly across its slots
+    pub memory: Option<MemoryConfig>,
+    pub gpus: Option<u64>,
+    pub queue: Option<String>,
+    pub time: Option<SlurmTime>,
+    pub project: Option<String>,
+    /// Extra directives, written as `#BSUB -<flag> <value>`
+    #[serde(default)]
+    pub additional_options: Vec<(String, String)>,
+}
+
+impl LsfConfig {
+    pub fn with_cpus(mut self, cpus: u64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+    pub fn with_memory(mut self, memory: MemoryConfig) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+    pub fn with_gpus(mut self, gpus: u64) -> Self {
+        self.gpus = Some(gpus);
+        self
+    }
+    pub fn with_queue<S: ToString>(mut self, queue: S) -> Self {
+        self.queue = Some(queue.to_string());
+        self
+    }
+    pub fn with_time(mut self, time: SlurmTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+    pub fn with_project<S: ToString>(mut self, project: S) -> Self {
+        self.project = Some(project.to_string());
+        self
+    }
+    pub fn with_arg<S: ToString>(mut self, arg: S, value: S) -> Self {
+        self.additional_options
+            .push((arg.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl Display for LsfConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let slots = self.cpus.unwrap_or(1);
+        if let Some(cpus) = self.cpus {
+            writeln!(f, "#BSUB -n {cpus}")?;
+            writeln!(f, "#BSUB -R \"span[hosts=1]\"")?;
+        }
+        if let Some(memory) = self.memory {
+            // rusage reserves memory per slot
+            let mb = memory.total_mb(slots).div_ceil(slots);
+            writeln!(f, "#BSUB -R \"rusage[mem={mb}MB]\"")?;
+        }
+        if let Some(gpus) = self.gpus {
+            writeln!(f, "#BSUB -gpu \"num={gpus}\"")?;
+        }
+        if let Some(time) = self.time {
+            let mins = time.as_secs().div_ceil(60);
+            writeln!(f, "#BSUB -W {}:{:02}", mins / 60, mins % 60)?;
+        }
+        if let Some(queue) = &self.queue {
+            writeln!(f, "#BSUB -q {queue}")?;
+        }
+        if let Some(project) = &self.project {
+            writeln!(f, "#BSUB -P {project}")?;
+        }
+        for (flag, arg) in &self.additional_options {
+            writeln!(f, "#BSUB -{flag} {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Escalate for LsfExecutor {
+    fn escalate(&self, escalation: &Escalation) -> Self {
+        let mut escalated = self.clone();
+        let config = &mut escalated.config;
+        escalation.apply(&mut config.cpus, &mut config.memory, &mut config.time);
+        escalated
+    }
+}
+
+impl Executor for LsfExecutor {
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        run_reusable(self, process, |process| {
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir)
+            })
+        })
+    }
+}
+
+impl Reusable for LsfExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        (
+            "Lsf",
+            &self.container,
+            self.config.cpus,
+            &self.config.memory,
+            self.config.gpus,
+            &self.config.time,
+        )
+            .hash(state);
+    }
+}
+
+impl LsfExecutor {
+    fn exe_attempt(&self, process: &Process, workdir: &Path) -> Result<Vec<PathBuf>, AttemptError> {
+        let ((log_path, mut log_handle), (launcher_path, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
+        write!(
+            launcher_handle,
+            "#BSUB -J {}\n#BSUB -o .maestro.log\n#BSUB -e .maestro.err\n{}",
+            lsf_job_name(&process.name),
+            self.config
+        )
+        .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+
+        let staging_mode = match self.container {
+            None => &self.staging_mode,
+            Some(_) => &StagingMode::Copy,
+        };
+        process.stage_inputs(&mut launcher_handle, workdir, staging_mode)?;
+        for module_name in &self.modules {
+            writeln!(launcher_handle, "module load {module_name}")
+                .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+        }
+        Process::write_execution(launcher_handle, process, &self.container)?;
+
+        // bsub only parses the #BSUB directives of scripts passed on stdin
+        let launcher = File::open(&launcher_path)
+            .map_err(|e| NodeError::msg(format!("Failed to open launcher file: {e}")))?;
+        let mut bsub = Command::new("bsub");
+        bsub.stdin(launcher).current_dir(workdir);
+        scheduler::run_job(self, process, workdir, bsub, &mut log_handle, &log_path)
+    }
+}
+
+impl Scheduler for LsfExecutor {
+    fn monitor(&self) -> &'static JobMonitor {
+        &LSF_MONITOR
+    }
+    fn poll_rate(&self) -> Duration {
+        self.poll_rate
+    }
+    fn parse_job_id(&self, stdout: &str) -> Option<String> {
+        // Job <123> is submitted to queue <normal>.
+        let (_, rest) = stdout.split_once("Job <")?;
+        let (job_id, _) = rest.split_once('>')?;
+        job_id.parse::<u64>().ok().map(|id| id.to_string())
+    }
+    fn cancel(&self, job_id: &str) {
+        let _ = Command::new("bkill").arg(job_id).status();
+    }
+    fn collect(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        job_id: &str,
+        log_handle: &mut File,
+        log_path: &Path,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        let job = bjobs(&[job_id.to_string()])
+            .map_err(|e| NodeError::msg(format!("Failed to query bjobs for job information: {e}")))?
+            .remove(job_id);
+        let Some(job) = job else {
+            let _ = writeln!(log_handle, "{LP} Job {job_id} is unknown to bjobs");
+            return resolve_job(process, workdir, None, None, log_handle, log_path);
+        };
+        let _ = writeln!(
+            log_handle,
+            "{LP} Job information\nSTAT: {}\nEXIT_CODE: {}\nEXIT_REASON: {}",
+            job.stat, job.exit_code, job.exit_reason
+        );
+
+        let job_state = if job.exit_reason.contains("TERM_MEMLIMIT") {
+            Some(SlurmJobState::OutOfMemory)
+        } else if job.exit_reason.contains("TERM_RUNLIMIT") {
+            Some(SlurmJobState::Timeout)
+        } else {
+            None
+        };
+        let job_status = match job.stat.as_str() {
+            "DONE" => Some((0, 0)),
+            // Jobs killed before exiting on their own have no exit code
+            "EXIT" => Some((job.exit_code.parse().unwrap_or(1), 0)),
+            _ => None,
+        };
+        resolve_job(
+            process, workdir, job_state, job_status, log_handle, log_path,
+        )
+    }
+}
+
+/// Sanitizes a process name into a valid LSF job name
+///
+/// Brackets would declare a job array, and whitespace would end the `#BSUB -J` directive
+fn lsf_job_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(4094)
+        .collect()
+}
+
+/// The fields of an LSF job record used by maestro
+#[derive(Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+struct LsfJob {
+    jobid: String,
+    #[serde(default)]
+    stat: String,
+    #[serde(default)]
+    exit_code: String,
+    #[serde(default)]
+    exit_reason: String,
+    /// Set instead of the other fields for jobs unknown to LSF
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BjobsOutput {
+    #[serde(rename = "RECORDS", default)]
+    records: Vec<LsfJob>,
+}
+
+/// Queries the records of `job_ids`, including recently finished jobs, in a single bjobs call
+fn bjobs(job_ids: &[String]) -> io::Result<HashMap<String, LsfJob>> {
+    let output = Command::new("bjobs")
+        .args(["-a", "-o", "jobid stat exit_code exit_reason", "-json"])
+        .args(job_ids)
+        .output()?;
+    // bjobs exits with an error if any job is unknown, while still reporting the others
+    parse_bjobs(&output.stdout).map_err(|e| {
+        io::Error::other(format!(
+            "bjobs exited with {}: {e}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    })
+}
+
+/// Parses the records of `bjobs -json`, skipping those of unknown jobs
+fn parse_bjobs(stdout: &[u8]) -> serde_json::Result<HashMap<String, LsfJob>> {
+    let parsed: BjobsOutput = serde_json::from_slice(stdout)?;
+    Ok(parsed
+        .records
+        .into_iter()
+        .filter(|job| job.error.is_none())
+        .map(|job| (job.jobid.clone(), job))
+        .collect())
+}
+
+/// Queries the states of `job_ids`; jobs absent from the output have left the queue
+fn query_bjobs(job_ids: &[String]) -> io::Result<HashMap<String, JobStatus>> {
+    Ok(bjobs(job_ids)?
+        .into_iter()
+        .map(|(id, job)| {
+            let status = match job.stat.as_str() {
+                "DONE" | "EXIT" => JobStatus::Finished,
+                "PEND" | "PSUSP" | "WAIT" => JobStatus::Pending,
+                _ => JobStatus::Running,
+            };
+            (id, status)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::slurm::Memory;
+
+    #[test]
+    fn parses_job_ids() {
+        let executor = LsfExecutor::default();
+        assert_eq!(
+            executor.parse_job_id("Job <4211> is submitted to queue <normal>.\n"),
+            Some("4211".to_string())
+        );
+        assert_eq!(
+            executor.parse_job_id("Job <4212> is submitted to default queue <normal>.\n"),
+            Some("4212".to_string())
+        );
+        assert_eq!(
+            executor.parse_job_id("Request aborted by esub. Job not submitted.\n"),
+            None
+        );
+        assert_eq!(executor.parse_job_id("Job <> is submitted"), None);
+    }
+
+    #[test]
+    fn writes_bsub_directives() {
+        let config = LsfConfig::default()
+            .with_cpus(4)
+            .with_memory(MemoryConfig::PerNode(Memory::from_mb(10000)))
+            .with_gpus(1)
+            .with_time(SlurmTime::new(0, 1, 30, 1).unwrap())
+            .with_queue("long")
+            .with_project("genomics")
+            .with_arg("G", "lab");
+        assert_eq!(
+            config.to_string(),
+            "#BSUB -n 4\n\
+             #BSUB -R \"span[hosts=1]\"\n\
+             #BSUB -R \"rusage[mem=2500MB]\"\n\
+             #BSUB -gpu \"num=1\"\n\
+             #BSUB -W 1:31\n\
+             #BSUB -q long\n\
+             #BSUB -P genomics\n\
+             #BSUB -G lab\n"
+        );
+        // Memory per CPU is already per slot, and a single slot needs no span
+        let config = LsfConfig::default().with_memory(MemoryConfig::PerCpu(Memory::from_mb(2000)));
+        assert_eq!(config.to_string(), "#BSUB -R \"rusage[mem=2000MB]\"\n");
+        assert_eq!(LsfConfig::default().to_string(), "");
+    }
+
+    #[test]
+    fn sanitizes_job_names() {
+        assert_eq!(lsf_job_name("align_reads-1.v2"), "align_reads-1.v2");
+        assert_eq!(lsf_job_name("align reads[1-10]"), "align_reads_1-10_");
+        assert_eq!(lsf_job_name(&"a".repeat(5000)).len(), 4094);
+    }
+
+    #[test]
+    fn parses_bjobs_json() {
+        let stdout = br#"{
+          "COMMAND":"bjobs",
+          "JOBS":3,
+          "RECORDS":[
+            {
+              "JOBID":"4211",
+              "STAT":"DONE",
+              "EXIT_CODE":"",
+              "EXIT_REASON":""
+            },
+            {
+              "JOBID":"4212",
+              "STAT":"EXIT",
+              "EXIT_CODE":"137",
+              "EXIT_REASON":"TERM_MEMLIMIT: job killed after reaching LSF memory usage limit"
+            },
+            {
+              "JOBID":"4213",
+              "ERROR":"Job <4213> is not found"
+            }
+          ]
+        }"#;
+        let jobs = parse_bjobs(stdout).unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs["4211"].stat, "DONE");
+        assert_eq!(jobs["4212"].stat, "EXIT");
+        assert_eq!(jobs["4212"].exit_code, "137");
+        assert!(jobs["4212"].exit_reason.starts_with("TERM_MEMLIMIT"));
+        assert!(!jobs.contains_key("4213"));
+        assert!(parse_bjobs(b"No unfinished job found").is_err());
+    }
+}
@@ -1,14 +1,46 @@
+use dagger_lib::result::{NodeError, NodeResult};
 use serde::Deserialize;
+use std::{hash::Hasher, path::PathBuf, thread};
 
 use crate::{
     Process, WorkflowResult,
-    executors::{local::LocalExecutor, slurm::SlurmExecutor},
+    executors::{
+        condor::CondorExecutor,
+        kubernetes::KubernetesExecutor,
+        local::LocalExecutor,
+        lsf::LsfExecutor,
+        pbs::PbsExecutor,
+        plugin::PluginExecutor,
+        slurm::{SlurmAllocationExecutor, SlurmExecutor},
+        ssh::SshExecutor,
+        template::TemplateExecutor,
+    },
 };
 
+/// HTCondor execution
+pub mod condor;
+/// Non-blocking execution through submitted job handles
+pub mod job;
+/// Execution as Kubernetes Jobs
+pub mod kubernetes;
 /// Local execution
 pub mod local;
+/// LSF execution
+pub mod lsf;
+mod monitor;
+/// PBS execution
+pub mod pbs;
+/// User-defined executor types
+pub mod plugin;
+/// Retry policies for failed processes
+pub mod retry;
+mod scheduler;
 /// Slurm execution
 pub mod slurm;
+/// Remote execution over SSH
+pub mod ssh;
+/// Execution on arbitrary batch schedulers driven by configurable commands
+pub mod template;
 
 /// Generic trait to implement executors against
 pub trait Executor {
@@ -16,11 +48,21 @@ pub trait Executor {
 }
 
 /// Generic executor enum for deserializing executor definitions from Maestro.toml
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Hash)]
 #[serde(tag = "type")]
 pub enum GenericExecutor {
     Local(LocalExecutor),
     Slurm(Box<SlurmExecutor>),
+    SlurmAllocation(Box<SlurmAllocationExecutor>),
+    Ssh(Box<SshExecutor>),
+    Pbs(Box<PbsExecutor>),
+    Lsf(Box<LsfExecutor>),
+    Condor(Box<CondorExecutor>),
+    Kubernetes(Box<KubernetesExecutor>),
+    Template(Box<TemplateExecutor>),
+    /// An executor of a type registered through [`register_executor!`](crate::register_executor)
+    #[serde(untagged)]
+    Plugin(Box<PluginExecutor>),
 }
 
 impl GenericExecutor {
@@ -28,6 +70,150 @@ impl GenericExecutor {
         match self {
             GenericExecutor::Local(executor) => executor.exe(process),
             GenericExecutor::Slurm(executor) => executor.exe(process),
+            GenericExecutor::SlurmAllocation(executor) => executor.exe(process),
+            GenericExecutor::Ssh(executor) => executor.exe(process),
+            GenericExecutor::Pbs(executor) => executor.exe(process),
+            GenericExecutor::Lsf(executor) => executor.exe(process),
+            GenericExecutor::Condor(executor) => executor.exe(process),
+            GenericExecutor::Kubernetes(executor) => executor.exe(process),
+            GenericExecutor::Template(executor) => executor.exe(process),
+            GenericExecutor::Plugin(executor) => executor.exe(process),
         }
     }
+
+    /// Runs a batch of processes, returning their results in order
+    ///
+    /// On Slurm, the processes that still need to run are submitted as a single job array,
+    /// with at most `max_concurrent` tasks running at once when set.
+    /// Other executors run them in parallel
+    pub fn exe_array(
+        &self,
+        processes: Vec<Process>,
+        max_concurrent: Option<u32>,
+    ) -> Vec<WorkflowResult> {
+        match self {
+            GenericExecutor::Slurm(executor) => executor.exe_array(processes, max_concurrent),
+            _ => thread::scope(|scope| {
+                let handles: Vec<_> = processes
+                    .into_iter()
+                    .map(|process| scope.spawn(|| self.exe(process)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|_| Err(NodeError::msg("Process panicked".to_string())))
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// An executor whose runs can be reused, from a resumed session or the cache
+pub(crate) trait Reusable {
+    /// Whether outputs are cached unless the process says otherwise
+    fn cache(&self) -> bool;
+    /// Feeds what decides the outcome of a run into the cache key:
+    /// the executor type, its container and the resources it requests
+    fn hash_execution<H: Hasher>(&self, state: &mut H);
+}
+
+/// Runs `process` through `run` unless its outputs can be reused,
+/// recording and caching the outputs of a successful run
+pub(crate) fn run_reusable<E: Reusable>(
+    executor: &E,
+    process: Process,
+    run: impl FnOnce(Process) -> WorkflowResult,
+) -> WorkflowResult {
+    match reuse(executor, &process, false)? {
+        Reuse::Outputs(outputs) => Ok(outputs),
+        Reuse::Run(cache_key) => {
+            let cache_handle = cache_key.map(|key| (key, process.clone()));
+            finish(run(process), cache_handle)
+        }
+    }
+}
+
+/// Runs the processes of a batch whose outputs cannot be reused through `run`,
+/// which returns their results in order, as does this
+pub(crate) fn run_reusable_batch<E: Reusable>(
+    executor: &E,
+    processes: Vec<Process>,
+    run: impl FnOnce(Vec<Process>) -> Vec<WorkflowResult>,
+) -> Vec<WorkflowResult> {
+    let mut results: Vec<Option<WorkflowResult>> = processes.iter().map(|_| None).collect();
+    let mut to_run = Vec::new();
+    let mut cache_handles = Vec::new();
+    for (index, process) in processes.into_iter().enumerate() {
+        match reuse(executor, &process, false) {
+            Ok(Reuse::Outputs(outputs)) => results[index] = Some(Ok(outputs)),
+            Ok(Reuse::Run(cache_key)) => {
+                cache_handles.push((index, cache_key.map(|key| (key, process.clone()))));
+                to_run.push(process);
+            }
+            Err(e) => results[index] = Some(Err(e)),
+        }
+    }
+
+    for ((index, cache_handle), outputs) in cache_handles.into_iter().zip(run(to_run)) {
+        results[index] = Some(finish(outputs, cache_handle));
+    }
+    results
+        .into_iter()
+        .map(|result| result.expect("Every process should resolve to a result"))
+        .collect()
+}
+
+/// Looks up outputs of `process` that can be reused without running it,
+/// either from the resumed session or from the cache
+///
+/// Unless the process is submitted `ahead`, this first waits for any upstream jobs
+/// producing its inputs; so does caching, as inputs have to exist to be hashed
+pub(crate) fn reuse<E: Reusable>(
+    executor: &E,
+    process: &Process,
+    ahead: bool,
+) -> NodeResult<Reuse> {
+    if let Some(outputs) = process.completed_outputs() {
+        return Ok(Reuse::Outputs(outputs));
+    }
+    let cache = process.cache.unwrap_or(executor.cache());
+    if !ahead || cache {
+        slurm::await_upstream(process)?;
+    }
+    if !cache {
+        return Ok(Reuse::Run(None));
+    }
+    let key = process.cache_key(executor)?;
+    if let Some(outputs) = process.restore_cached(&key)? {
+        if let Some(workdir) = outputs.last() {
+            Process::record_completion(workdir)?;
+        }
+        return Ok(Reuse::Outputs(outputs));
+    }
+    Ok(Reuse::Run(Some(key)))
+}
+
+/// Records a successful run, caching its outputs under the key held by `cache_handle`
+pub(crate) fn finish(
+    outputs: WorkflowResult,
+    cache_handle: Option<(String, Process)>,
+) -> WorkflowResult {
+    let outputs = outputs?;
+    if let Some(workdir) = outputs.last() {
+        Process::record_completion(workdir)?;
+        if let Some((key, process)) = cache_handle {
+            process.store_cached(&key, workdir);
+        }
+    }
+    Ok(outputs)
+}
+
+/// The result of looking up reusable outputs for a process
+pub(crate) enum Reuse {
+    Outputs(Vec<PathBuf>),
+    /// The process has to run; its outputs are cached under the key, if any
+    Run(Option<String>),
 }
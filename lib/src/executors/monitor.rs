@@ -0,0 +1,252 @@
+use crate::LP;
+use std::{
+    collections::HashMap,
+    io,
+    process::Command,
+    sync::{Condvar, LazyLock, Mutex, MutexGuard, Once, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The longest interval between queries while the scheduler is failing or slow to respond
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// The session-wide monitor for submitted Slurm jobs
+pub(crate) static SLURM_MONITOR: LazyLock<JobMonitor> =
+    LazyLock::new(|| JobMonitor::new("Slurm", query_squeue));
+
+/// Queries the states of a set of job ids in one go;
+/// ids absent from the returned map have left the queue
+pub(crate) type StateQuery =
+    Box<dyn Fn(&[String]) -> io::Result<HashMap<String, JobStatus>> + Send + Sync>;
+
+/// The scheduler state of a monitored job
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum JobStatus {
+    Pending,
+    Running,
+    /// The job has left the queue
+    Finished,
+    /// The job waits on a dependency that failed, so it can never start
+    DependencyNeverSatisfied,
+}
+impl JobStatus {
+    fn is_terminal(self) -> bool {
+        matches!(self, Self::Finished | Self::DependencyNeverSatisfied)
+    }
+}
+
+struct MonitoredJob {
+    status: JobStatus,
+    poll_rate: Duration,
+}
+
+/// Tracks all outstanding jobs of a scheduler with a single background thread
+///
+/// Each tick queries the scheduler once for every outstanding job,
+/// rather than once per job
+pub(crate) struct JobMonitor {
+    /// The scheduler name used in thread names and error messages
+    name: String,
+    query: StateQuery,
+    jobs: Mutex<HashMap<String, MonitoredJob>>,
+    changed: Condvar,
+    started: Once,
+}
+
+impl JobMonitor {
+    pub(crate) fn new<S, Q>(name: S, query: Q) -> Self
+    where
+        S: ToString,
+        Q: Fn(&[String]) -> io::Result<HashMap<String, JobStatus>> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            query: Box::new(query),
+            jobs: Mutex::new(HashMap::new()),
+            changed: Condvar::new(),
+            started: Once::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, MonitoredJob>> {
+        self.jobs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Starts tracking `job_id`, spawning the monitor thread on first use
+    fn track(
+        &'static self,
+        job_id: &str,
+        poll_rate: Duration,
+    ) -> MutexGuard<'static, HashMap<String, MonitoredJob>> {
+        self.started.call_once(|| {
+            thread::Builder::new()
+                .name(format!("maestro-{}-monitor", self.name.to_lowercase()))
+                .spawn(|| self.run())
+                .unwrap_or_else(|e| {
+                    panic!("Failed to spawn {} job monitor thread: {e}", self.name)
+                });
+        });
+
+        let mut jobs = self.lock();
+        jobs.insert(
+            job_id.to_string(),
+            MonitoredJob {
+                status: JobStatus::Pending,
+                poll_rate,
+            },
+        );
+        self.changed.notify_all();
+        jobs
+    }
+
+    /// Starts tracking `job_id` without waiting on it; [`JobMonitor::status`] reports on it
+    pub(crate) fn watch(&'static self, job_id: &str, poll_rate: Duration) {
+        drop(self.track(job_id, poll_rate));
+    }
+
+    /// The last known status of a watched job; jobs are no longer tracked once terminal
+    pub(crate) fn status(&self, job_id: &str) -> JobStatus {
+        let mut jobs = self.lock();
+        let status = jobs
+            .get(job_id)
+            .map_or(JobStatus::Finished, |job| job.status);
+        if status.is_terminal() {
+            jobs.remove(job_id);
+        }
+        status
+    }
+
+    /// Stops tracking a watched job, such as one that was cancelled
+    pub(crate) fn forget(&self, job_id: &str) {
+        self.lock().remove(job_id);
+    }
+
+    /// Blocks until `job_id` leaves the queue or can never start,
+    /// calling `on_start` whenever the job is seen running after having been pending
+    ///
+    /// Jobs requeued by the scheduler, such as after preemption, keep their id and
+    /// return to pending, so `on_start` is called again once they restart.
+    ///
+    /// The monitor polls at the fastest `poll_rate` among all outstanding jobs
+    pub(crate) fn wait<F: FnMut()>(
+        &'static self,
+        job_id: &str,
+        poll_rate: Duration,
+        mut on_start: F,
+    ) -> JobStatus {
+        let mut jobs = self.track(job_id, poll_rate);
+        let mut started = false;
+        loop {
+            let status = jobs
+                .get(job_id)
+                .map_or(JobStatus::Finished, |job| job.status);
+            match status {
+                JobStatus::Pending => started = false,
+                JobStatus::Running | JobStatus::Finished if !started => {
+                    started = true;
+                    on_start();
+                }
+                _ => {}
+            }
+            if status.is_terminal() {
+                jobs.remove(job_id);
+                return status;
+            }
+            jobs = self
+                .changed
+                .wait(jobs)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn run(&'static self) {
+        let mut backoff: Option<Duration> = None;
+        loop {
+            let (job_ids, poll_rate) = {
+                let mut jobs = self.lock();
+                while !jobs.values().any(|job| !job.status.is_terminal()) {
+                    jobs = self
+                        .changed
+                        .wait(jobs)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                let outstanding = jobs.iter().filter(|(_, job)| !job.status.is_terminal());
+                let poll_rate = outstanding
+                    .clone()
+                    .map(|(_, job)| job.poll_rate)
+                    .min()
+                    .unwrap_or(MAX_BACKOFF);
+                let job_ids: Vec<String> = outstanding.map(|(id, _)| id.clone()).collect();
+                (job_ids, poll_rate)
+            };
+
+            let query_start = Instant::now();
+            let next_backoff = backoff.map_or(poll_rate, |b| b * 2).min(MAX_BACKOFF);
+            match (self.query)(&job_ids) {
+                Ok(states) => {
+                    // Give a sluggish controller room to breathe
+                    backoff = (query_start.elapsed() > poll_rate).then_some(next_backoff);
+                    let mut jobs = self.lock();
+                    for id in &job_ids {
+                        if let Some(job) = jobs.get_mut(id) {
+                            job.status = states.get(id).copied().unwrap_or(JobStatus::Finished);
+                        }
+                    }
+                    self.changed.notify_all();
+                }
+                Err(e) => {
+                    eprintln!(
+                        "{LP} Failed to query {} job states: {e}; retrying in {}s",
+                        self.name,
+                        next_backoff.as_secs()
+                    );
+                    backoff = Some(next_backoff);
+                }
+            }
+            thread::sleep(backoff.unwrap_or(poll_rate));
+        }
+    }
+}
+
+/// Queries the states of `job_ids` in a single squeue call; jobs absent from the output have left the queue
+fn query_squeue(job_ids: &[String]) -> io::Result<HashMap<String, JobStatus>> {
+    let output = Command::new("squeue")
+        .args(["-h", "-o", "%i %T %r", "-j"])
+        .arg(job_ids.join(","))
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // Raised once none of the requested jobs are known to the controller anymore
+        if stderr.contains("Invalid job id") {
+            return Ok(HashMap::new());
+        }
+        return Err(io::Error::other(format!(
+            "squeue exited with {}: {}",
+            output.status,
+            stderr.trim()
+        )));
+    }
+
+    let mut states = HashMap::new();
+    for line in stdout.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(id), Some(state)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        // Array tasks are listed as <job id>_<task id>; the array runs while any task does
+        let id = id.split_once('_').map_or(id, |(job_id, _)| job_id);
+        let status = match (state, fields.next()) {
+            ("PENDING", Some("DependencyNeverSatisfied")) => JobStatus::DependencyNeverSatisfied,
+            // Requeued jobs stay in the queue under the same id until they run again
+            ("PENDING" | "REQUEUED" | "REQUEUE_HOLD" | "REQUEUE_FED", _) => JobStatus::Pending,
+            _ => JobStatus::Running,
+        };
+        let entry = states.entry(id.to_string()).or_insert(status);
+        if status == JobStatus::Running {
+            *entry = status;
+        }
+    }
+    Ok(states)
+}
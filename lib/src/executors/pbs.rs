@@ -0,0 +1,501 @@
+use crate::{
+    Container, LP, Process,
+    executors::{
+        Executor, Reusable,
+        monitor::{JobMonitor, JobStatus},
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+        scheduler::{self, Scheduler},
+        slurm::{MemoryConfig, SlurmJobState, SlurmTime, resolve_job},
+        ssh::quote,
+    },
+    process::StagingMode,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::LazyLock,
+    time::Duration,
+};
+
+/// The session-wide monitor for submitted PBS jobs
+static PBS_MONITOR: LazyLock<JobMonitor> = LazyLock::new(|| JobMonitor::new("PBS", query_qstat));
+
+/// An executor that schedules processes via PBS Pro
+#[derive(Clone, Deserialize, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct PbsExecutor {
+    pub(crate) container: Option<Container>,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(default = "default_poll_rate")]
+    pub(crate) poll_rate: Duration,
+    #[serde(default)]
+    pub(crate) staging_mode: StagingMode,
+    #[serde(default)]
+    pub(crate) modules: Vec<String>,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
+    #[serde(flatten)]
+    pub(crate) config: PbsConfig,
+}
+const fn default_poll_rate() -> Duration {
+    Duration::from_secs(5)
+}
+
+impl Default for PbsExecutor {
+    fn default() -> Self {
+        Self {
+            container: None,
+            cache: false,
+            poll_rate: default_poll_rate(),
+            staging_mode: StagingMode::Symlink,
+            modules: Vec::new(),
+            retry: RetryPolicy::default(),
+            config: PbsConfig::default(),
+        }
+    }
+}
+
+impl PbsExecutor {
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    pub fn with_poll_rate(mut self, rate: Duration) -> Self {
+        self.poll_rate = rate;
+        self
+    }
+    pub fn with_staging_mode(mut self, staging_mode: StagingMode) -> Self {
+        self.staging_mode = staging_mode;
+        self
+    }
+    pub fn with_module<S: ToString>(mut self, module: S) -> Self {
+        self.modules.push(module.to_string());
+        self
+    }
+    pub fn with_modules<S: ToString, M: IntoIterator<Item = S>>(mut self, modules: M) -> Self {
+        let transformed_modules = modules.into_iter().map(|module| module.to_string());
+        self.modules.extend(transformed_modules);
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
+    pub fn with_config(mut self, config: PbsConfig) -> Self {
+        self.config = config;
+        self
+    }
+    pub fn map_config<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(PbsConfig) -> PbsConfig,
+    {
+        self.config = (f)(self.config);
+        self
+    }
+}
+
+/// PBS configuration options
+///
+/// Resources are requested per chunk, with `nodes` chunks selected
+#[derive(Default, Clone, Deserialize, Hash)]
+pub struct PbsConfig {
+    pub cpus: Option<u64>,
+    pub memory: Option<MemoryConfig>,
+    pub gpus: Option<u64>,
+    pub nodes: Option<u64>,
+    pub queue: Option<String>,
+    pub time: Option<SlurmTime>,
+    pub account: Option<String>,
+    /// Extra directives, written as `#PBS -<flag> <value>`
+    #[serde(default)]
+    pub additional_options: Vec<(String, String)>,
+}
+
+impl PbsConfig {
+    pub fn with_cpus(mut self, cpus: u64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+    pub fn with_memory(mut self, memory: MemoryConfig) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+    pub fn with_gpus(mut self, gpus: u64) -> Self {
+        self.gpus = Some(gpus);
+        self
+    }
+    pub fn with_nodes(mut self, nodes: u64) -> Self {
+        self.nodes = Some(nodes);
+        self
+    }
+    pub fn with_queue<S: ToString>(mut self, queue: S) -> Self {
+        self.queue = Some(queue.to_string());
+        self
+    }
+    pub fn with_time(mut self, time: SlurmTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+    pub fn with_account<S: ToString>(mut self, account: S) -> Self {
+        self.account = Some(account.to_string());
+        self
+    }
+    pub fn with_arg<S: ToString>(mut self, arg: S, value: S) -> Self {
+        self.additional_options
+            .push((arg.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl Display for PbsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut select = format!("select={}", self.nodes.unwrap_or(1));
+        if let Some(cpus) = self.cpus {
+            select.push_str(&format!(":ncpus={cpus}"));
+        }
+        if let Some(memory) = self.memory {
+            let mb = memory.total_mb(self.cpus.unwrap_or(1));
+            select.push_str(&format!(":mem={mb}mb"));
+        }
+        if let Some(gpus) = self.gpus {
+            select.push_str(&format!(":ngpus={gpus}"));
+        }
+        writeln!(f, "#PBS -l {select}")?;
+        if let Some(time) = self.time {
+            writeln!(f, "#PBS -l walltime={}", time.as_hms())?;
+        }
+        if let Some(queue) = &self.queue {
+            writeln!(f, "#PBS -q {queue}")?;
+        }
+        if let Some(account) = &self.account {
+            writeln!(f, "#PBS -A {account}")?;
+        }
+        for (flag, arg) in &self.additional_options {
+            writeln!(f, "#PBS -{flag} {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Escalate for PbsExecutor {
+    fn escalate(&self, escalation: &Escalation) -> Self {
+        let mut escalated = self.clone();
+        let config = &mut escalated.config;
+        escalation.apply(&mut config.cpus, &mut config.memory, &mut config.time);
+        escalated
+    }
+}
+
+impl Executor for PbsExecutor {
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        run_reusable(self, process, |process| {
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir)
+            })
+        })
+    }
+}
+
+impl Reusable for PbsExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        (
+            "Pbs",
+            &self.container,
+            self.config.cpus,
+            &self.config.memory,
+            self.config.gpus,
+            self.config.nodes,
+            &self.config.time,
+        )
+            .hash(state);
+    }
+}
+
+impl PbsExecutor {
+    fn exe_attempt(&self, process: &Process, workdir: &Path) -> Result<Vec<PathBuf>, AttemptError> {
+        let ((log_path, mut log_handle), (launcher_path, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
+        // PBS starts jobs in the home directory and only copies their output back once they end.
+        // The exit status is left behind for servers that forget finished jobs
+        writeln!(
+            launcher_handle,
+            "#PBS -N {}\n#PBS -j oe\n#PBS -o .maestro.pbs\n{}cd {}\nexec >> .maestro.log 2>> .maestro.err\ntrap 'echo $? > {EXIT_FILE}' EXIT",
+            pbs_job_name(&process.name),
+            self.config,
+            quote(workdir)
+        )
+        .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+
+        let staging_mode = match self.container {
+            None => &self.staging_mode,
+            Some(_) => &StagingMode::Copy,
+        };
+        process.stage_inputs(&mut launcher_handle, workdir, staging_mode)?;
+        for module_name in &self.modules {
+            writeln!(launcher_handle, "module load {module_name}")
+                .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+        }
+        Process::write_execution(launcher_handle, process, &self.container)?;
+
+        let mut qsub = Command::new("qsub");
+        qsub.arg(launcher_path).current_dir(workdir);
+        scheduler::run_job(self, process, workdir, qsub, &mut log_handle, &log_path)
+    }
+}
+
+impl Scheduler for PbsExecutor {
+    fn monitor(&self) -> &'static JobMonitor {
+        &PBS_MONITOR
+    }
+    fn poll_rate(&self) -> Duration {
+        self.poll_rate
+    }
+    fn parse_job_id(&self, stdout: &str) -> Option<String> {
+        stdout.split_whitespace().last().map(str::to_string)
+    }
+    fn cancel(&self, job_id: &str) {
+        let _ = Command::new("qdel").arg(job_id).status();
+    }
+    fn collect(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        job_id: &str,
+        log_handle: &mut File,
+        log_path: &Path,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        let job = qstat(&[job_id.to_string()])
+            .map_err(|e| NodeError::msg(format!("Failed to query qstat for job information: {e}")))?
+            .remove(job_id);
+        let _ = match &job {
+            Some(job) => writeln!(
+                log_handle,
+                "{LP} Job information\njob_state: {}\nExit_status: {}",
+                job.job_state,
+                job.exit_status
+                    .map_or("unknown".to_string(), |code| code.to_string())
+            ),
+            None => writeln!(
+                log_handle,
+                "{LP} Job {job_id} is unknown to qstat; falling back to the exit status recorded by the job"
+            ),
+        };
+        let exit_status = match job {
+            Some(job) => job.exit_status,
+            // Without job history, finished jobs vanish from qstat along with their exit status
+            None => {
+                let recorded = fs::read_to_string(workdir.join(EXIT_FILE))
+                    .ok()
+                    .and_then(|code| code.trim().parse::<i64>().ok());
+                if recorded.is_none() {
+                    let _ = writeln!(
+                        log_handle,
+                        "{LP} Job {job_id} left no exit status, as when PBS kills it for exceeding its limits; \
+                        enable job history on the server (qmgr -c 'set server job_history_enable = True') to tell why"
+                    );
+                }
+                recorded
+            }
+        };
+        let (job_state, job_status) = match exit_outcome(exit_status) {
+            Ok(outcome) => outcome,
+            Err(code) => {
+                let _ = writeln!(
+                    log_handle,
+                    "{LP} PBS failed to run the job, with exit status {code}"
+                );
+                return Err(AttemptError::Failed(NodeError::msg(format!(
+                    "PBS failed to run the job. Logs at {}",
+                    log_path.display()
+                ))));
+            }
+        };
+        resolve_job(
+            process, workdir, job_state, job_status, log_handle, log_path,
+        )
+    }
+}
+
+/// The file jobs record their exit status in, relative to their workdir
+const EXIT_FILE: &str = ".maestro.exit";
+
+/// The state and exit code and signal of a job, as passed to [`resolve_job`]
+type JobOutcome = (Option<SlurmJobState>, Option<(u32, u32)>);
+
+/// Maps the `Exit_status` of a job to its outcome
+///
+/// Returns the status as an error if PBS itself failed to run the job
+fn exit_outcome(exit_status: Option<i64>) -> Result<JobOutcome, i64> {
+    Ok(match exit_status {
+        // Jobs over their vmem or mem limit, and over their cput or walltime limit
+        Some(-26 | -27) => (Some(SlurmJobState::OutOfMemory), None),
+        Some(-28 | -29) => (Some(SlurmJobState::Timeout), None),
+        Some(code @ ..0) => return Err(code),
+        // Jobs killed by a signal exit with 256 plus the signal number
+        Some(code @ 256..) => (None, Some((0, code as u32 - 256))),
+        Some(code) => (None, Some((code as u32, 0))),
+        None => (None, None),
+    })
+}
+
+/// Sanitizes a process name into a valid PBS job name
+fn pbs_job_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(236)
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name
+    } else {
+        format!("p{name}")
+    }
+}
+
+/// The fields of a PBS job record used by maestro
+#[derive(Deserialize)]
+struct PbsJob {
+    job_state: String,
+    #[serde(rename = "Exit_status")]
+    exit_status: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct QstatOutput {
+    #[serde(rename = "Jobs", default)]
+    jobs: HashMap<String, PbsJob>,
+}
+
+/// Queries the full records of `job_ids`, including finished jobs, in a single qstat call
+fn qstat(job_ids: &[String]) -> io::Result<HashMap<String, PbsJob>> {
+    let output = Command::new("qstat")
+        .args(["-f", "-F", "json", "-x"])
+        .args(job_ids)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // Raised once none of the requested jobs are known to the server anymore
+        if stderr.contains("Unknown Job Id") {
+            return Ok(HashMap::new());
+        }
+        return Err(io::Error::other(format!(
+            "qstat exited with {}: {}",
+            output.status,
+            stderr.trim()
+        )));
+    }
+    let parsed: QstatOutput = serde_json::from_str(&stdout).map_err(io::Error::other)?;
+    Ok(parsed.jobs)
+}
+
+/// Queries the states of `job_ids`; jobs absent from the output have left the server
+fn query_qstat(job_ids: &[String]) -> io::Result<HashMap<String, JobStatus>> {
+    Ok(qstat(job_ids)?
+        .into_iter()
+        .map(|(id, job)| {
+            let status = match job.job_state.as_str() {
+                "F" => JobStatus::Finished,
+                "Q" | "H" | "W" | "T" | "S" | "U" => JobStatus::Pending,
+                _ => JobStatus::Running,
+            };
+            (id, status)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_exit_status_to_outcome() {
+        assert!(matches!(exit_outcome(Some(0)), Ok((None, Some((0, 0))))));
+        assert!(matches!(exit_outcome(Some(3)), Ok((None, Some((3, 0))))));
+        assert!(matches!(
+            exit_outcome(Some(255)),
+            Ok((None, Some((255, 0))))
+        ));
+        // Killed by SIGKILL and SIGTERM
+        assert!(matches!(exit_outcome(Some(265)), Ok((None, Some((0, 9))))));
+        assert!(matches!(exit_outcome(Some(271)), Ok((None, Some((0, 15))))));
+        assert!(matches!(exit_outcome(None), Ok((None, None))));
+    }
+
+    #[test]
+    fn maps_exceeded_limits_to_resources_exhausted() {
+        for code in [-26, -27] {
+            assert!(matches!(
+                exit_outcome(Some(code)),
+                Ok((Some(SlurmJobState::OutOfMemory), None))
+            ));
+        }
+        for code in [-28, -29] {
+            assert!(matches!(
+                exit_outcome(Some(code)),
+                Ok((Some(SlurmJobState::Timeout), None))
+            ));
+        }
+        // The job could not be run, such as when its execution host rejected it
+        assert!(matches!(exit_outcome(Some(-1)), Err(-1)));
+        assert!(matches!(exit_outcome(Some(-25)), Err(-25)));
+    }
+
+    #[test]
+    fn sanitizes_job_names() {
+        assert_eq!(pbs_job_name("align_reads-1"), "align_reads-1");
+        assert_eq!(pbs_job_name("align reads/chr1.bam"), "align_reads_chr1_bam");
+        assert_eq!(pbs_job_name("1st"), "p1st");
+        assert_eq!(pbs_job_name("_hidden"), "p_hidden");
+        assert_eq!(pbs_job_name(&"a".repeat(300)).len(), 236);
+    }
+
+    #[test]
+    fn parses_qstat_json() {
+        let stdout = r#"{
+            "timestamp": 1700000000,
+            "pbs_version": "2022.1.0",
+            "pbs_server": "head",
+            "Jobs": {
+                "101.head": { "Job_Name": "align", "job_state": "F", "Exit_status": 271 },
+                "102.head": { "Job_Name": "sort", "job_state": "R" }
+            }
+        }"#;
+        let parsed: QstatOutput = serde_json::from_str(stdout).unwrap();
+        assert_eq!(parsed.jobs["101.head"].job_state, "F");
+        assert_eq!(parsed.jobs["101.head"].exit_status, Some(271));
+        assert_eq!(parsed.jobs["102.head"].exit_status, None);
+        let empty: QstatOutput = serde_json::from_str(r#"{"pbs_version": "2022.1.0"}"#).unwrap();
+        assert!(empty.jobs.is_empty());
+    }
+}
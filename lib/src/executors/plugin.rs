@@ -0,0 +1,137 @@
+use crate::{
+    Process, WorkflowResult,
+    executors::{Executor, Reusable, run_reusable},
+};
+use serde::{
+    Deserialize, Deserializer,
+    de::{DeserializeOwned, Error as _},
+};
+use std::{
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+/// An executor type defined outside of maestro, as registered by [`register_executor!`](crate::register_executor)
+#[doc(hidden)]
+pub struct RegisteredExecutor {
+    name: &'static str,
+    build: fn(toml::Table) -> Result<Arc<dyn Executor + Send + Sync>, String>,
+}
+inventory::collect!(RegisteredExecutor);
+
+impl RegisteredExecutor {
+    pub const fn new<E>(name: &'static str) -> Self
+    where
+        E: Executor + DeserializeOwned + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            build: build::<E>,
+        }
+    }
+}
+
+fn build<E>(table: toml::Table) -> Result<Arc<dyn Executor + Send + Sync>, String>
+where
+    E: Executor + DeserializeOwned + Send + Sync + 'static,
+{
+    E::deserialize(toml::Value::Table(table))
+        .map(|executor| Arc::new(executor) as Arc<dyn Executor + Send + Sync>)
+        .map_err(|e| e.to_string())
+}
+
+/// An executor of a type registered through [`register_executor!`](crate::register_executor)
+///
+/// It is deserialized from its settings in Maestro.toml, less the `type` key.
+/// A `cache` key, if set, enables caching as for the built-in executors,
+/// and is not passed on to the plugin
+#[derive(Clone)]
+pub struct PluginExecutor {
+    type_name: String,
+    table: toml::Table,
+    pub(crate) cache: bool,
+    executor: Arc<dyn Executor + Send + Sync>,
+}
+
+impl PluginExecutor {
+    fn build(type_name: String, table: toml::Table) -> Result<Self, String> {
+        let registered = inventory::iter::<RegisteredExecutor>
+            .into_iter()
+            .find(|registered| registered.name == type_name)
+            .ok_or_else(|| format!("unknown executor type `{type_name}`"))?;
+        let cache = match table.get("cache") {
+            None => false,
+            Some(toml::Value::Boolean(cache)) => *cache,
+            Some(_) => return Err("invalid type for `cache`, expected a boolean".to_string()),
+        };
+        // The plugin is built from its own settings, which `cache` is not one of
+        let mut settings = table.clone();
+        settings.remove("cache");
+        let executor = (registered.build)(settings)
+            .map_err(|e| format!("invalid {type_name} executor: {e}"))?;
+        Ok(Self {
+            type_name,
+            table,
+            cache,
+            executor,
+        })
+    }
+
+    /// Rebuilds the executor with the settings of an inheriting executor laid over its own
+    pub(crate) fn merge(&self, overrides: &toml::Table) -> Result<Self, String> {
+        let mut table = self.table.clone();
+        for (key, value) in overrides {
+            if key != "type" {
+                table.insert(key.clone(), value.clone());
+            }
+        }
+        Self::build(self.type_name.clone(), table)
+    }
+
+    /// The type the executor was registered under
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+}
+
+impl<'de> Deserialize<'de> for PluginExecutor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut table = toml::Table::deserialize(deserializer)?;
+        let type_name = match table.remove("type") {
+            Some(toml::Value::String(type_name)) => type_name,
+            _ => return Err(D::Error::missing_field("type")),
+        };
+        Self::build(type_name, table).map_err(D::Error::custom)
+    }
+}
+
+impl Hash for PluginExecutor {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_name.hash(state);
+        // Tables are sorted by key, so equal settings serialize alike
+        serde_json::to_string(&self.table)
+            .unwrap_or_default()
+            .hash(state);
+    }
+}
+
+impl Executor for PluginExecutor {
+    fn exe(&self, process: Process) -> WorkflowResult {
+        run_reusable(self, process, |process| self.executor.exe(process))
+    }
+}
+
+impl Reusable for PluginExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        // Settings of plugins are opaque, so all of them count but whether to cache
+        let mut table = self.table.clone();
+        table.remove("cache");
+        self.type_name.hash(state);
+        serde_json::to_string(&table)
+            .unwrap_or_default()
+            .hash(state);
+    }
+}
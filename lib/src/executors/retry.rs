@@ -0,0 +1,572 @@
+use crate::{
+    LP, Process, WorkflowResult,
+    executors::slurm::{MemoryConfig, SlurmTime},
+    shutdown,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// The file in each process workdir that its attempts are recorded in
+pub(crate) const ATTEMPTS_FILE: &str = ".maestro.attempts.json";
+
+/// Retry behaviour for failed processes
+#[derive(Clone, Deserialize, Hash)]
+pub struct RetryPolicy {
+    /// The number of times a failed process is rerun
+    #[serde(default)]
+    pub retries: u32,
+    /// The delay before the first retry; doubled on every subsequent retry
+    #[serde(default)]
+    pub retry_backoff: Duration,
+    /// Resources added on retries that follow running out of memory or time
+    pub escalate: Option<Escalation>,
+    /// The number of times a preempted job is resubmitted, on top of `retries`
+    #[serde(default = "default_preemption_retries")]
+    pub preemption_retries: u32,
+}
+const fn default_preemption_retries() -> u32 {
+    3
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            retry_backoff: Duration::ZERO,
+            escalate: None,
+            preemption_retries: default_preemption_retries(),
+        }
+    }
+}
+
+/// Resource escalation applied when a process runs out of memory or time
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Escalation {
+    /// A factor to multiply the memory request by
+    pub memory: Option<f64>,
+    /// A factor to multiply the time limit by
+    pub time: Option<f64>,
+    /// A number of cpus to add to the cpu request
+    pub cpus: Option<u64>,
+}
+impl Hash for Escalation {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.memory.map(f64::to_bits).hash(state);
+        self.time.map(f64::to_bits).hash(state);
+        self.cpus.hash(state);
+    }
+}
+
+impl Escalation {
+    /// Escalates a set of resource requests in place; absent requests are left unset,
+    /// except cpus, which start from a single cpu
+    pub(crate) fn apply(
+        &self,
+        cpus: &mut Option<u64>,
+        memory: &mut Option<MemoryConfig>,
+        time: &mut Option<SlurmTime>,
+    ) {
+        if let Some(factor) = self.memory {
+            *memory = memory.map(|memory| memory.scaled(factor));
+        }
+        if let Some(factor) = self.time {
+            *time = time
+                .map(|time| SlurmTime::from_secs((time.as_secs() as f64 * factor).ceil() as u64));
+        }
+        if let Some(extra_cpus) = self.cpus {
+            *cpus = Some(cpus.unwrap_or(1) + extra_cpus);
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+    pub fn with_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+    pub fn with_escalation(mut self, escalation: Escalation) -> Self {
+        self.escalate = Some(escalation);
+        self
+    }
+    /// Sets how often preempted jobs are resubmitted without counting as failed attempts
+    pub fn with_preemption_retries(mut self, preemption_retries: u32) -> Self {
+        self.preemption_retries = preemption_retries;
+        self
+    }
+
+    /// The delay before retrying after failed attempt `attempt_number`
+    pub(crate) fn backoff(&self, attempt_number: u32) -> Duration {
+        self.retry_backoff
+            .saturating_mul(1 << (attempt_number - 1).min(16))
+    }
+
+    /// Runs `attempt` in `workdir` until it succeeds or retries are exhausted
+    ///
+    /// The files of each unsuccessful attempt are kept in `.maestro.attempts/<n>/`,
+    /// and the outcome of every attempt is recorded in `.maestro.attempts.json`.
+    /// Preempted attempts are resubmitted right away and do not count against `retries`
+    pub(crate) fn run<E, F>(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        executor: &E,
+        mut attempt: F,
+    ) -> WorkflowResult
+    where
+        E: Escalate,
+        F: FnMut(&E) -> Result<Vec<PathBuf>, AttemptError>,
+    {
+        let mut tracker = AttemptTracker::new(executor);
+        loop {
+            let result = attempt(tracker.current());
+            match self.settle(&mut tracker, process, workdir, result) {
+                Settled::Done(result) => return result,
+                Settled::Retry(backoff) => thread::sleep(backoff),
+            }
+        }
+    }
+
+    /// Records the outcome of the current attempt of `tracker`,
+    /// deciding whether the process is done or another attempt follows
+    pub(crate) fn settle<E: Escalate>(
+        &self,
+        tracker: &mut AttemptTracker<E>,
+        process: &Process,
+        workdir: &Path,
+        result: Result<Vec<PathBuf>, AttemptError>,
+    ) -> Settled {
+        let attempt_number = tracker.attempt_number;
+        tracker
+            .records
+            .push(AttemptRecord::new(attempt_number, &result));
+        AttemptRecord::write(&tracker.records, workdir);
+
+        let (error, exhausted) = match result {
+            // Nothing is retried once the session is shutting down
+            Err(e) if shutdown::interrupted() => return Settled::Done(Err(e.into_inner())),
+            Ok(outputs) => return Settled::Done(Ok(outputs)),
+            Err(AttemptError::Fatal(e)) => return Settled::Done(Err(e)),
+            Err(AttemptError::Preempted(e)) if tracker.preemptions < self.preemption_retries => {
+                tracker.preemptions += 1;
+                let archive = match Process::archive_attempt(workdir, attempt_number) {
+                    Ok(archive) => archive,
+                    Err(e) => return Settled::Done(Err(e)),
+                };
+                println!(
+                    "{LP} Process {} was preempted on attempt {attempt_number}: {e:?}\n{LP} Logs kept at {}; resubmitting",
+                    process.name,
+                    archive.display(),
+                );
+                tracker.attempt_number += 1;
+                return Settled::Retry(Duration::ZERO);
+            }
+            Err(AttemptError::Failed(e) | AttemptError::Preempted(e)) => (e, false),
+            Err(AttemptError::ResourcesExhausted(e)) => (e, true),
+        };
+        tracker.failures += 1;
+        if tracker.failures > self.retries {
+            return Settled::Done(Err(error));
+        }
+
+        let archive = match Process::archive_attempt(workdir, attempt_number) {
+            Ok(archive) => archive,
+            Err(e) => return Settled::Done(Err(e)),
+        };
+        let backoff = self.backoff(tracker.failures);
+        println!(
+            "{LP} Process {} failed on attempt {attempt_number}: {error:?}\n{LP} Logs kept at {}; retrying in {}s",
+            process.name,
+            archive.display(),
+            backoff.as_secs_f32()
+        );
+        if exhausted && let Some(escalation) = &self.escalate {
+            tracker.current = tracker.current.escalate(escalation);
+        }
+        tracker.attempt_number += 1;
+        Settled::Retry(backoff)
+    }
+}
+
+/// The attempts made so far at running a process, and the executor for the next one
+pub(crate) struct AttemptTracker<E> {
+    current: E,
+    records: Vec<AttemptRecord>,
+    attempt_number: u32,
+    failures: u32,
+    preemptions: u32,
+}
+
+impl<E: Escalate> AttemptTracker<E> {
+    pub(crate) fn new(executor: &E) -> Self {
+        Self {
+            current: executor.clone(),
+            records: Vec::new(),
+            attempt_number: 1,
+            failures: 0,
+            preemptions: 0,
+        }
+    }
+
+    /// The executor to run the current attempt with, escalated after running out of resources
+    pub(crate) fn current(&self) -> &E {
+        &self.current
+    }
+    pub(crate) fn attempt_number(&self) -> u32 {
+        self.attempt_number
+    }
+}
+
+/// What follows an attempt, as decided by [`RetryPolicy::settle`]
+pub(crate) enum Settled {
+    Done(WorkflowResult),
+    /// Another attempt is due once the backoff has passed
+    Retry(Duration),
+}
+
+/// How an attempt at running a process ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptOutcome {
+    Succeeded,
+    Failed,
+    ResourcesExhausted,
+    /// The job was preempted by the scheduler and resubmitted, if resubmissions remained
+    Preempted,
+}
+
+/// The record of a single attempt at running a process
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AttemptRecord {
+    /// The attempt number, which is also the name of its archive in `.maestro.attempts/`
+    pub attempt: u32,
+    pub outcome: AttemptOutcome,
+    pub error: Option<String>,
+}
+
+impl AttemptRecord {
+    pub(crate) fn new(attempt: u32, result: &Result<Vec<PathBuf>, AttemptError>) -> Self {
+        let outcome = match result {
+            Ok(_) => AttemptOutcome::Succeeded,
+            Err(AttemptError::Fatal(_) | AttemptError::Failed(_)) => AttemptOutcome::Failed,
+            Err(AttemptError::ResourcesExhausted(_)) => AttemptOutcome::ResourcesExhausted,
+            Err(AttemptError::Preempted(_)) => AttemptOutcome::Preempted,
+        };
+        Self {
+            attempt,
+            outcome,
+            error: result.as_ref().err().map(|e| format!("{:?}", e.inner())),
+        }
+    }
+
+    /// Reads the attempts recorded in a process workdir, oldest first
+    pub fn read(workdir: &Path) -> NodeResult<Vec<AttemptRecord>> {
+        let contents = match fs::read_to_string(workdir.join(ATTEMPTS_FILE)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(NodeError::msg(format!("Failed to read attempts: {e}"))),
+        };
+        serde_json::from_str(&contents)
+            .map_err(|e| NodeError::msg(format!("Failed to parse attempts: {e}")))
+    }
+
+    /// Persists the records into `workdir`; failures are ignored, as records are informational
+    pub(crate) fn write(records: &[AttemptRecord], workdir: &Path) {
+        if let Ok(json) = serde_json::to_string_pretty(records) {
+            let _ = fs::write(workdir.join(ATTEMPTS_FILE), json + "\n");
+        }
+    }
+}
+
+/// Executors whose resource requests can be escalated between attempts
+pub(crate) trait Escalate: Clone {
+    fn escalate(&self, escalation: &Escalation) -> Self;
+}
+
+/// The outcome of a single failed attempt
+pub(crate) enum AttemptError {
+    /// The process could not be set up; retrying would not help
+    Fatal(NodeError),
+    /// The process failed and may succeed if rerun
+    Failed(NodeError),
+    /// The process ran out of memory or time
+    ResourcesExhausted(NodeError),
+    /// The job was preempted by the scheduler to make room for others
+    Preempted(NodeError),
+}
+impl AttemptError {
+    pub(crate) fn inner(&self) -> &NodeError {
+        match self {
+            AttemptError::Fatal(e)
+            | AttemptError::Failed(e)
+            | AttemptError::ResourcesExhausted(e)
+            | AttemptError::Preempted(e) => e,
+        }
+    }
+    pub(crate) fn into_inner(self) -> NodeError {
+        match self {
+            AttemptError::Fatal(e)
+            | AttemptError::Failed(e)
+            | AttemptError::ResourcesExhausted(e)
+            | AttemptError::Preempted(e) => e,
+        }
+    }
+}
+impl From<NodeError> for AttemptError {
+    fn from(value: NodeError) -> Self {
+        AttemptError::Fatal(value)
+    }
+}
+impl From<io::Error> for AttemptError {
+    fn from(value: io::Error) -> Self {
+        AttemptError::Fatal(value.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::slurm::Memory;
+    use std::{env, process};
+
+    /// The resource requests of an executor, escalated as the built-in executors do
+    #[derive(Clone, Default)]
+    struct Requests {
+        cpus: Option<u64>,
+        memory: Option<MemoryConfig>,
+        time: Option<SlurmTime>,
+    }
+
+    impl Escalate for Requests {
+        fn escalate(&self, escalation: &Escalation) -> Self {
+            let mut escalated = self.clone();
+            escalation.apply(
+                &mut escalated.cpus,
+                &mut escalated.memory,
+                &mut escalated.time,
+            );
+            escalated
+        }
+    }
+
+    /// A fresh process workdir unique to `test`
+    fn new_workdir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("maestro-retry-{}-{test}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn process() -> Process {
+        Process::new("job".to_string(), vec![], vec![], vec![], "true".into())
+    }
+
+    fn failed() -> Result<Vec<PathBuf>, AttemptError> {
+        Err(AttemptError::Failed(NodeError::msg("Failed")))
+    }
+
+    #[test]
+    fn escalates_resource_requests() {
+        let escalation = Escalation {
+            memory: Some(1.5),
+            time: Some(2.0),
+            cpus: Some(2),
+        };
+        let requests = Requests {
+            cpus: Some(4),
+            memory: Some(MemoryConfig::PerCpu(Memory::from_mb(1001))),
+            time: SlurmTime::new(0, 1, 30, 1),
+        }
+        .escalate(&escalation);
+        assert_eq!(requests.cpus, Some(6));
+        let Some(memory @ MemoryConfig::PerCpu(_)) = requests.memory else {
+            panic!("memory should stay per cpu");
+        };
+        // Scaled memory and time are rounded up
+        assert_eq!(memory.total_mb(1), 1502);
+        assert_eq!(requests.time.map(|time| time.as_secs()), Some(10802));
+
+        // Unset memory and time stay unset, while cpus start from a single cpu
+        let requests = Requests::default().escalate(&escalation);
+        assert_eq!(requests.cpus, Some(3));
+        assert!(requests.memory.is_none());
+        assert!(requests.time.is_none());
+
+        // Resources without a factor are left as they are
+        let requests = Requests {
+            cpus: Some(4),
+            memory: Some(MemoryConfig::PerNode(Memory::from_mb(8000))),
+            time: Some(SlurmTime::from_hours(1)),
+        }
+        .escalate(&Escalation {
+            memory: Some(2.0),
+            ..Default::default()
+        });
+        assert_eq!(requests.cpus, Some(4));
+        assert_eq!(
+            requests.memory.map(|memory| memory.total_mb(4)),
+            Some(16000)
+        );
+        assert_eq!(requests.time.map(|time| time.as_secs()), Some(3600));
+    }
+
+    #[test]
+    fn doubles_backoff_on_every_retry() {
+        let policy = RetryPolicy::default().with_retry_backoff(Duration::from_secs(10));
+        assert_eq!(policy.backoff(1), Duration::from_secs(10));
+        assert_eq!(policy.backoff(2), Duration::from_secs(20));
+        assert_eq!(policy.backoff(3), Duration::from_secs(40));
+        // The factor is capped rather than overflowing
+        assert_eq!(policy.backoff(64), Duration::from_secs(10 << 16));
+        assert_eq!(RetryPolicy::default().backoff(5), Duration::ZERO);
+    }
+
+    #[test]
+    fn retries_failed_attempts_until_retries_run_out() {
+        let workdir = new_workdir("exhausted");
+        let policy = RetryPolicy::default().with_retries(2);
+        let mut attempts = 0;
+        let result = policy.run(&process(), &workdir, &Requests::default(), |_| {
+            attempts += 1;
+            failed()
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+
+        let workdir = new_workdir("recovered");
+        let outputs = vec![workdir.join("out.txt"), workdir.clone()];
+        let mut attempts = 0;
+        let result = policy.run(&process(), &workdir, &Requests::default(), |_| {
+            attempts += 1;
+            match attempts {
+                1 => failed(),
+                _ => Ok(outputs.clone()),
+            }
+        });
+        assert_eq!(result.unwrap(), outputs);
+        assert_eq!(attempts, 2);
+        assert!(workdir.join(".maestro.attempts").join("1").is_dir());
+    }
+
+    #[test]
+    fn does_not_retry_fatal_errors() {
+        let workdir = new_workdir("fatal");
+        let policy = RetryPolicy::default().with_retries(3);
+        let mut attempts = 0;
+        let result = policy.run(&process(), &workdir, &Requests::default(), |_| {
+            attempts += 1;
+            Err(AttemptError::Fatal(NodeError::msg("Fatal")))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        assert!(!workdir.join(".maestro.attempts").exists());
+    }
+
+    #[test]
+    fn settles_attempts_with_growing_backoff() {
+        let workdir = new_workdir("settle");
+        let policy = RetryPolicy::default()
+            .with_retries(2)
+            .with_retry_backoff(Duration::from_secs(5));
+        let mut tracker = AttemptTracker::new(&Requests::default());
+        let backoffs: Vec<_> = (0..2)
+            .map(
+                |_| match policy.settle(&mut tracker, &process(), &workdir, failed()) {
+                    Settled::Retry(backoff) => backoff,
+                    Settled::Done(_) => panic!("the attempt should be retried"),
+                },
+            )
+            .collect();
+        assert_eq!(backoffs, [Duration::from_secs(5), Duration::from_secs(10)]);
+        assert_eq!(tracker.attempt_number(), 3);
+        assert!(matches!(
+            policy.settle(&mut tracker, &process(), &workdir, failed()),
+            Settled::Done(Err(_))
+        ));
+    }
+
+    #[test]
+    fn escalates_only_after_running_out_of_resources() {
+        let workdir = new_workdir("escalate");
+        let policy = RetryPolicy::default()
+            .with_retries(2)
+            .with_escalation(Escalation {
+                cpus: Some(1),
+                ..Default::default()
+            });
+        let mut cpus = Vec::new();
+        let result = policy.run(&process(), &workdir, &Requests::default(), |requests| {
+            cpus.push(requests.cpus);
+            match cpus.len() {
+                1 => Err(AttemptError::ResourcesExhausted(NodeError::msg("Timeout"))),
+                2 => failed(),
+                _ => Ok(Vec::new()),
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(cpus, [None, Some(2), Some(2)]);
+    }
+
+    #[test]
+    fn resubmits_preempted_attempts_without_counting_them() {
+        let workdir = new_workdir("preempted");
+        let policy = RetryPolicy::default().with_preemption_retries(2);
+        let mut attempts = 0;
+        let result = policy.run(&process(), &workdir, &Requests::default(), |_| {
+            attempts += 1;
+            Err(AttemptError::Preempted(NodeError::msg("Preempted")))
+        });
+        // Once resubmissions run out, a preemption counts as a failure, with no retries left
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn round_trips_attempt_records() {
+        let workdir = new_workdir("records");
+        assert!(AttemptRecord::read(&workdir).unwrap().is_empty());
+
+        let records = [
+            AttemptRecord::new(
+                1,
+                &Err(AttemptError::ResourcesExhausted(NodeError::msg("Timeout"))),
+            ),
+            AttemptRecord::new(
+                2,
+                &Err(AttemptError::Preempted(NodeError::msg("Preempted"))),
+            ),
+            AttemptRecord::new(3, &Ok(Vec::new())),
+        ];
+        AttemptRecord::write(&records, &workdir);
+        let read = AttemptRecord::read(&workdir).unwrap();
+        let outcomes: Vec<_> = read
+            .iter()
+            .map(|record| (record.attempt, record.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                (1, AttemptOutcome::ResourcesExhausted),
+                (2, AttemptOutcome::Preempted),
+                (3, AttemptOutcome::Succeeded),
+            ]
+        );
+        assert!(read[0].error.as_deref().unwrap().contains("Timeout"));
+        assert!(read[2].error.is_none());
+
+        fs::write(workdir.join(ATTEMPTS_FILE), "not json").unwrap();
+        assert!(AttemptRecord::read(&workdir).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{
+    LP, Process,
+    executors::{
+        job::AttemptPoll,
+        monitor::{JobMonitor, JobStatus},
+        retry::AttemptError,
+    },
+    shutdown::InFlight,
+};
+use dagger_lib::result::NodeError;
+use std::{
+    fs::File,
+    io::Write as _,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+/// A batch scheduler driven by submitting jobs, polling their states and cancelling them
+pub(crate) trait Scheduler {
+    /// The monitor tracking the submitted jobs of this scheduler
+    fn monitor(&self) -> &'static JobMonitor;
+    fn poll_rate(&self) -> Duration;
+    /// Extracts the job id from the output of a successful submission
+    fn parse_job_id(&self, stdout: &str) -> Option<String>;
+    /// Cancels a submitted job on a best-effort basis
+    fn cancel(&self, job_id: &str);
+    /// Resolves the outcome of a job that has left the queue
+    fn collect(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        job_id: &str,
+        log_handle: &mut File,
+        log_path: &Path,
+    ) -> Result<Vec<PathBuf>, AttemptError>;
+}
+
+/// Submits a job, waits for it to leave the queue and collects its outcome,
+/// cancelling the job if the wait is cut short
+pub(crate) fn run_job<S: Scheduler + Clone + Send + 'static>(
+    scheduler: &S,
+    process: &Process,
+    workdir: &Path,
+    submit_command: Command,
+    log_handle: &mut File,
+    log_path: &Path,
+) -> Result<Vec<PathBuf>, AttemptError> {
+    let job_id = submit(scheduler, submit_command, log_handle, log_path)?;
+    let mut starts = 0;
+    await_job(scheduler, &job_id, &[workdir], || {
+        log_start(log_handle, &mut starts)
+    });
+    scheduler.collect(process, workdir, &job_id, log_handle, log_path)
+}
+
+/// A submitted job, watched by the monitor of its scheduler until it is collected
+pub(crate) struct SubmittedJob {
+    pub(crate) job_id: String,
+    log_handle: File,
+    log_path: PathBuf,
+    starts: u32,
+    running: bool,
+    _in_flight: InFlight,
+}
+
+/// Submits a job and has the monitor of `scheduler` watch it, without waiting on it
+pub(crate) fn submit_job<S: Scheduler + Clone + Send + 'static>(
+    scheduler: &S,
+    workdir: &Path,
+    submit_command: Command,
+    mut log_handle: File,
+    log_path: PathBuf,
+) -> Result<SubmittedJob, AttemptError> {
+    let job_id = submit(scheduler, submit_command, &mut log_handle, &log_path)?;
+    scheduler.monitor().watch(&job_id, scheduler.poll_rate());
+    Ok(SubmittedJob {
+        _in_flight: in_flight(scheduler, &job_id, &[workdir]),
+        job_id,
+        log_handle,
+        log_path,
+        starts: 0,
+        running: false,
+    })
+}
+
+/// Checks on a submitted job, collecting its outcome once it has left the queue
+pub(crate) fn poll_job<S: Scheduler + ?Sized>(
+    scheduler: &S,
+    job: &mut SubmittedJob,
+    process: &Process,
+    workdir: &Path,
+) -> AttemptPoll {
+    match scheduler.monitor().status(&job.job_id) {
+        JobStatus::Pending => {
+            job.running = false;
+            AttemptPoll::Pending
+        }
+        JobStatus::Running => {
+            if !job.running {
+                job.running = true;
+                log_start(&mut job.log_handle, &mut job.starts);
+            }
+            AttemptPoll::Running
+        }
+        JobStatus::Finished | JobStatus::DependencyNeverSatisfied => {
+            if !job.running {
+                log_start(&mut job.log_handle, &mut job.starts);
+            }
+            AttemptPoll::Finished(scheduler.collect(
+                process,
+                workdir,
+                &job.job_id,
+                &mut job.log_handle,
+                &job.log_path,
+            ))
+        }
+    }
+}
+
+/// Cancels a submitted job and stops watching it
+pub(crate) fn cancel_job<S: Scheduler + ?Sized>(scheduler: &S, job: &SubmittedJob) {
+    scheduler.cancel(&job.job_id);
+    scheduler.monitor().forget(&job.job_id);
+}
+
+/// Logs the start of a job, or its restart after having been requeued
+pub(crate) fn log_start(log_handle: &mut File, starts: &mut u32) {
+    *starts += 1;
+    let _ = match *starts {
+        1 => writeln!(log_handle, ":: Job execution started"),
+        run => writeln!(
+            log_handle,
+            "{LP} Job was requeued; execution restarted (run {run})"
+        ),
+    };
+}
+
+/// Blocks until `job_id` leaves the queue, calling `on_start` whenever it is seen starting
+///
+/// The job is cancelled if the wait unwinds or the session shuts down,
+/// in which case `workdirs` are marked as cancelled
+pub(crate) fn await_job<S: Scheduler + Clone + Send + 'static, F: FnMut()>(
+    scheduler: &S,
+    job_id: &str,
+    workdirs: &[&Path],
+    on_start: F,
+) -> JobStatus {
+    let _in_flight = in_flight(scheduler, job_id, workdirs);
+    let mut job_guard = JobGuard {
+        scheduler,
+        job_id: Some(job_id),
+    };
+    let status = scheduler
+        .monitor()
+        .wait(job_id, scheduler.poll_rate(), on_start);
+    job_guard.job_id = None;
+    status
+}
+
+/// Registers a submitted job for cancellation should the session shut down
+fn in_flight<S: Scheduler + Clone + Send + 'static>(
+    scheduler: &S,
+    job_id: &str,
+    workdirs: &[&Path],
+) -> InFlight {
+    let (scheduler, job_id) = (scheduler.clone(), job_id.to_string());
+    let workdirs = workdirs
+        .iter()
+        .map(|workdir| workdir.to_path_buf())
+        .collect();
+    InFlight::job(workdirs, move || scheduler.cancel(&job_id))
+}
+
+/// Cancels the held job when dropped, unless it was released
+struct JobGuard<'a, S: Scheduler + ?Sized> {
+    scheduler: &'a S,
+    job_id: Option<&'a str>,
+}
+impl<'a, S: Scheduler + ?Sized> Drop for JobGuard<'a, S> {
+    fn drop(&mut self) {
+        if let Some(id) = self.job_id {
+            self.scheduler.cancel(id);
+        }
+    }
+}
+
+/// Runs a prepared submit command, returning the id of the submitted job
+pub(crate) fn submit<S: Scheduler + ?Sized>(
+    scheduler: &S,
+    mut submit_command: Command,
+    log_handle: &mut File,
+    log_path: &Path,
+) -> Result<String, AttemptError> {
+    let program = submit_command.get_program().to_string_lossy().into_owned();
+    let output = submit_command.output().map_err(|e| {
+        NodeError::msg(format!("Failed to spawn {program} for job submission: {e}"))
+    })?;
+
+    if output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let job_id = scheduler
+            .parse_job_id(&stdout)
+            .ok_or(NodeError::msg(format!(
+                "Failed to parse {program} output into a job code: {stdout}"
+            )));
+        let _ = match &job_id {
+            Ok(id) => writeln!(log_handle, "{LP} Job submitted successfully! Id: {id}"),
+            Err(_) => writeln!(
+                log_handle,
+                "{LP} Failed to parse {program} output into a job id\nstdout: {}",
+                stdout
+            ),
+        };
+        Ok(job_id?)
+    } else {
+        let error_code = output
+            .status
+            .code()
+            .map(|code| format!("Error code: {}\n", code))
+            .unwrap_or(String::new());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let _ = writeln!(
+            log_handle,
+            "{LP} Job failed to submit via {program}!\n{error_code}stderr: {stderr}",
+        );
+        Err(AttemptError::Failed(NodeError::msg(format!(
+            "Job did not submit successfully. Logs at {}",
+            log_path.display()
+        ))))
+    }
+}
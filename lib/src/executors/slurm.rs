@@ -1,23 +1,63 @@
 use crate::{
     Container, LP, Process,
-    executors::Executor,
+    executors::{
+        self, Executor, Reusable, Reuse,
+        job::{AttemptPoll, JobExecutor, JobPoll, PolledExecutor, PolledJob},
+        monitor::{JobMonitor, SLURM_MONITOR},
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+        scheduler::{self, Scheduler, SubmittedJob},
+    },
     process::{CheckTime, StagingMode},
 };
 use dagger_lib::result::{NodeError, NodeResult};
 use serde::Deserialize;
-use std::{fmt::Display, io::Write as _, path::PathBuf, process::Command, thread, time::Duration};
+use std::{
+    fmt::Display,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::Write as _,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+/// Job states and exit codes as reported by sacct
+mod accounting;
+/// Submission of jobs ahead of their inputs
+mod ahead;
+/// Processes run as job steps within a single allocation
+mod allocation;
+/// Job arrays for batches of processes
+mod array;
+/// Submission, polling and cancellation over slurmrestd
+mod rest;
+
+pub use accounting::{SlurmJobError, SlurmJobState};
+pub(crate) use ahead::{await_submitted, await_upstream, is_submitted_output};
+pub use allocation::SlurmAllocationExecutor;
+pub(crate) use allocation::release_allocations;
+pub use rest::{SlurmRest, SlurmToken};
 
 /// An executor that schedules processes via Slurm
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Hash)]
 #[serde(deny_unknown_fields)]
 pub struct SlurmExecutor {
     pub(crate) container: Option<Container>,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(default)]
+    pub(crate) submit_ahead: bool,
     #[serde(default = "default_poll_rate")]
     pub(crate) poll_rate: Duration,
     #[serde(default)]
     pub(crate) staging_mode: StagingMode,
     #[serde(default)]
     pub(crate) modules: Vec<String>,
+    /// Talks to slurmrestd instead of the Slurm CLI when set
+    pub(crate) rest: Option<SlurmRest>,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
     #[serde(flatten)]
     pub(crate) config: SlurmConfig,
 }
@@ -29,9 +69,13 @@ impl Default for SlurmExecutor {
     fn default() -> Self {
         Self {
             container: None,
+            cache: false,
+            submit_ahead: false,
             poll_rate: default_poll_rate(),
             staging_mode: StagingMode::Symlink,
             modules: Vec::new(),
+            rest: None,
+            retry: RetryPolicy::default(),
             config: SlurmConfig::default(),
         }
     }
@@ -42,6 +86,16 @@ impl SlurmExecutor {
         self.container = Some(container);
         self
     }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    /// Submits jobs without waiting for the jobs producing their inputs,
+    /// which instead become `afterok` dependencies
+    pub fn with_submit_ahead(mut self, submit_ahead: bool) -> Self {
+        self.submit_ahead = submit_ahead;
+        self
+    }
     pub fn with_poll_rate(mut self, rate: Duration) -> Self {
         self.poll_rate = rate;
         self
@@ -59,6 +113,22 @@ impl SlurmExecutor {
         self.modules.extend(transformed_modules);
         self
     }
+    /// Submits, polls and cancels jobs over slurmrestd rather than the Slurm CLI
+    pub fn with_rest(mut self, rest: SlurmRest) -> Self {
+        self.rest = Some(rest);
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
     pub fn with_config(mut self, config: SlurmConfig) -> Self {
         self.config = config;
         self
@@ -73,7 +143,7 @@ impl SlurmExecutor {
 }
 
 /// Slurm configuration options
-#[derive(Default, Clone, Deserialize)]
+#[derive(Default, Clone, Deserialize, Hash)]
 pub struct SlurmConfig {
     pub cpus: Option<u64>,
     pub memory: Option<MemoryConfig>,
@@ -85,12 +155,30 @@ pub struct SlurmConfig {
     pub account: Option<String>,
     pub mail_user: Option<String>,
     pub mail_type: Option<MailTypeList>,
+    /// Node features, such as `skylake&ib`
+    pub constraint: Option<String>,
+    pub qos: Option<String>,
+    /// Whether nodes are shared with no other jobs
+    pub exclusive: Option<bool>,
+    /// Generic resources, such as `gpu:a100:2`
+    pub gres: Option<String>,
+    pub reservation: Option<String>,
+    /// Nodes never to run on
+    pub exclude: Option<Vec<String>>,
+    /// The earliest time to start at, such as `now+1hour` or `2025-01-01T08:00:00`
+    pub begin: Option<String>,
+    /// Jobs to wait on, such as `afterok:123:456`
+    pub dependency: Option<String>,
+    pub nice: Option<i32>,
+    /// Whether the job may be requeued, such as after being preempted
+    pub requeue: Option<bool>,
+    /// Options without a typed field, passed on as `--flag=value`
     #[serde(default)]
     pub additional_options: Vec<(String, String)>,
 }
 
 /// Slurm time limit
-#[derive(Clone, Copy, Default, Deserialize)]
+#[derive(Clone, Copy, Default, Deserialize, Hash)]
 #[serde(default, deny_unknown_fields)]
 pub struct SlurmTime {
     days: u16,
@@ -136,6 +224,31 @@ impl SlurmTime {
         }
     }
 }
+impl SlurmTime {
+    pub(crate) fn as_secs(&self) -> u64 {
+        ((self.days as u64 * 24 + self.hours as u64) * 60 + self.mins as u64) * 60
+            + self.secs as u64
+    }
+    /// Formats the time limit as `HH:MM:SS`, with hours beyond a day
+    pub(crate) fn as_hms(&self) -> String {
+        let secs = self.as_secs();
+        format!(
+            "{:02}:{:02}:{:02}",
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        )
+    }
+    pub(crate) fn from_secs(secs: u64) -> Self {
+        let days = secs / 86_400;
+        Self {
+            days: days.min(u16::MAX as u64) as u16,
+            hours: (secs % 86_400 / 3_600) as u16,
+            mins: (secs % 3_600 / 60) as u8,
+            secs: (secs % 60) as u8,
+        }
+    }
+}
 impl Display for SlurmTime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -147,7 +260,7 @@ impl Display for SlurmTime {
 }
 
 /// Defines a mail notification type for Slurm
-#[derive(Clone, Copy, Deserialize)]
+#[derive(Clone, Copy, Deserialize, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum MailType {
     None,
@@ -188,7 +301,7 @@ impl Display for MailType {
     }
 }
 /// A list of Slurm mail notification types
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Hash)]
 pub struct MailTypeList(pub Vec<MailType>);
 impl Display for MailTypeList {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -204,12 +317,28 @@ impl Display for MailTypeList {
 }
 
 /// Defines a memory configuration for Slurm
-#[derive(Clone, Copy, Deserialize)]
+#[derive(Clone, Copy, Deserialize, Hash)]
 #[serde(tag = "type", content = "amount", rename_all = "snake_case")]
 pub enum MemoryConfig {
     PerNode(Memory),
     PerCpu(Memory),
 }
+impl MemoryConfig {
+    /// The total memory in MB available to a process using `cpus` cpus
+    pub(crate) fn total_mb(&self, cpus: u64) -> u64 {
+        match self {
+            MemoryConfig::PerNode(Memory(v)) => *v,
+            MemoryConfig::PerCpu(Memory(v)) => v * cpus,
+        }
+    }
+    pub(crate) fn scaled(self, factor: f64) -> Self {
+        let scale = |Memory(v): Memory| Memory((v as f64 * factor).ceil() as u64);
+        match self {
+            MemoryConfig::PerNode(memory) => MemoryConfig::PerNode(scale(memory)),
+            MemoryConfig::PerCpu(memory) => MemoryConfig::PerCpu(scale(memory)),
+        }
+    }
+}
 impl Display for MemoryConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -220,7 +349,7 @@ impl Display for MemoryConfig {
 }
 
 /// Slurm memory resource request
-#[derive(Clone, Copy, Deserialize)]
+#[derive(Clone, Copy, Deserialize, Hash)]
 pub struct Memory(u64);
 impl Memory {
     pub fn from_mb(memory: u64) -> Self {
@@ -229,6 +358,9 @@ impl Memory {
     pub fn from_gb(memory: u64) -> Self {
         Self(memory * 1024)
     }
+    pub(crate) fn as_mb(&self) -> u64 {
+        self.0
+    }
 }
 
 macro_rules! impl_setter {
@@ -257,6 +389,15 @@ impl SlurmConfig {
     impl_string_setter!(partition, with_partition);
     impl_string_setter!(account, with_account);
     impl_string_setter!(mail_user, with_mail_user);
+    impl_string_setter!(constraint, with_constraint);
+    impl_string_setter!(qos, with_qos);
+    impl_setter!(exclusive, with_exclusive, bool);
+    impl_string_setter!(gres, with_gres);
+    impl_string_setter!(reservation, with_reservation);
+    impl_string_setter!(begin, with_begin);
+    impl_string_setter!(dependency, with_dependency);
+    impl_setter!(nice, with_nice, i32);
+    impl_setter!(requeue, with_requeue, bool);
 
     pub fn with_time(mut self, time: SlurmTime) -> Self {
         self.time = Some(time);
@@ -266,6 +407,10 @@ impl SlurmConfig {
         self.mail_type = Some(MailTypeList(mail_types.as_ref().to_vec()));
         self
     }
+    pub fn with_exclude<S: ToString, N: IntoIterator<Item = S>>(mut self, nodes: N) -> Self {
+        self.exclude = Some(nodes.into_iter().map(|node| node.to_string()).collect());
+        self
+    }
     pub fn with_arg<S: ToString>(mut self, arg: S, value: S) -> Self {
         self.additional_options
             .push((arg.to_string(), value.to_string()));
@@ -286,203 +431,450 @@ impl SlurmConfig {
     }
 }
 
-impl Display for SlurmConfig {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        fn write_arg<A: Display>(
-            flag: &'static str,
-            maybe_arg: &Option<A>,
-            buffer: &mut std::fmt::Formatter<'_>,
-        ) -> std::fmt::Result {
+impl SlurmConfig {
+    /// The options as `--flag=value` arguments, shared by sbatch directives and salloc
+    pub(crate) fn flags(&self) -> Vec<String> {
+        fn push_arg<A: Display>(flag: &str, maybe_arg: &Option<A>, flags: &mut Vec<String>) {
             if let Some(arg) = maybe_arg {
-                writeln!(buffer, "#SBATCH --{flag}={arg}")?;
+                flags.push(format!("--{flag}={arg}"));
             }
-            Ok(())
         }
-        write_arg("cpus-per-task", &self.cpus, f)?;
-        write_arg("gpus", &self.gpus, f)?;
+        let mut flags = Vec::new();
+        push_arg("cpus-per-task", &self.cpus, &mut flags);
+        push_arg("gpus", &self.gpus, &mut flags);
 
         let memory_flag = match self.memory {
             Some(MemoryConfig::PerCpu(_)) => "mem-per-cpu",
             Some(MemoryConfig::PerNode(_)) => "mem",
             None => "",
         };
-        write_arg(memory_flag, &self.memory, f)?;
-        write_arg("ntasks", &self.tasks, f)?;
-        write_arg("nodes", &self.nodes, f)?;
-        write_arg("partition", &self.partition, f)?;
-        write_arg("time", &self.time, f)?;
-        write_arg("account", &self.account, f)?;
-        write_arg("mail-user", &self.mail_user, f)?;
-        write_arg("mail-type", &self.mail_type, f)?;
+        push_arg(memory_flag, &self.memory, &mut flags);
+        push_arg("ntasks", &self.tasks, &mut flags);
+        push_arg("nodes", &self.nodes, &mut flags);
+        push_arg("partition", &self.partition, &mut flags);
+        push_arg("time", &self.time, &mut flags);
+        push_arg("account", &self.account, &mut flags);
+        push_arg("mail-user", &self.mail_user, &mut flags);
+        push_arg("mail-type", &self.mail_type, &mut flags);
+        push_arg("constraint", &self.constraint, &mut flags);
+        push_arg("qos", &self.qos, &mut flags);
+        if self.exclusive == Some(true) {
+            flags.push("--exclusive".to_string());
+        }
+        push_arg("gres", &self.gres, &mut flags);
+        push_arg("reservation", &self.reservation, &mut flags);
+        push_arg(
+            "exclude",
+            &self.exclude.as_ref().map(|nodes| nodes.join(",")),
+            &mut flags,
+        );
+        push_arg("begin", &self.begin, &mut flags);
+        push_arg("dependency", &self.dependency, &mut flags);
+        push_arg("nice", &self.nice, &mut flags);
+        match self.requeue {
+            Some(true) => flags.push("--requeue".to_string()),
+            Some(false) => flags.push("--no-requeue".to_string()),
+            None => {}
+        }
 
         for (flag, arg) in &self.additional_options {
-            writeln!(f, "#SBATCH --{flag}={arg}")?;
+            flags.push(format!("--{flag}={arg}"));
         }
+        flags
+    }
+}
+
+/// The flags of the options with typed fields, along with those fields
+const TYPED_OPTIONS: [(&str, &str); 22] = [
+    ("cpus-per-task", "cpus"),
+    ("mem", "memory"),
+    ("mem-per-cpu", "memory"),
+    ("gpus", "gpus"),
+    ("ntasks", "tasks"),
+    ("nodes", "nodes"),
+    ("partition", "partition"),
+    ("time", "time"),
+    ("account", "account"),
+    ("mail-user", "mail_user"),
+    ("mail-type", "mail_type"),
+    ("constraint", "constraint"),
+    ("qos", "qos"),
+    ("exclusive", "exclusive"),
+    ("gres", "gres"),
+    ("reservation", "reservation"),
+    ("exclude", "exclude"),
+    ("begin", "begin"),
+    ("dependency", "dependency"),
+    ("nice", "nice"),
+    ("requeue", "requeue"),
+    ("no-requeue", "requeue"),
+];
 
+/// The dependency types understood by sbatch, besides `singleton`
+const DEPENDENCY_TYPES: [&str; 6] = [
+    "after",
+    "afterany",
+    "afterburstbuffer",
+    "aftercorr",
+    "afternotok",
+    "afterok",
+];
+
+impl SlurmConfig {
+    /// Checks the options for mistakes that sbatch would otherwise only report on submission
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for (flag, _) in &self.additional_options {
+            let flag = flag.trim_start_matches('-');
+            if let Some((_, field)) = TYPED_OPTIONS.iter().find(|(typed, _)| *typed == flag) {
+                return Err(format!(
+                    "`{flag}` should be set through the `{field}` field rather than additional_options"
+                ));
+            }
+        }
+        if let Some(dependency) = &self.dependency {
+            for condition in dependency.split([',', '?']) {
+                let valid = match condition.split_once(':') {
+                    Some((kind, jobs)) => {
+                        DEPENDENCY_TYPES.contains(&kind)
+                            && jobs.split(':').all(|job| !job.is_empty())
+                    }
+                    None => condition == "singleton",
+                };
+                if !valid {
+                    return Err(format!(
+                        "Invalid dependency `{condition}`; expected `<type>:<job_id>[:<job_id>...]` or `singleton`"
+                    ));
+                }
+            }
+        }
+        if let Some(gres) = &self.gres
+            && gres
+                .split(',')
+                .any(|resource| resource.split(':').any(str::is_empty))
+        {
+            return Err(format!(
+                "Invalid gres `{gres}`; expected `<name>[:<type>][:<count>]`"
+            ));
+        }
+        if let Some(exclude) = &self.exclude
+            && exclude.iter().any(|node| node.trim().is_empty())
+        {
+            return Err("Excluded nodes must not be empty".to_string());
+        }
         Ok(())
     }
 }
 
+impl Display for SlurmConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for flag in self.flags() {
+            writeln!(f, "#SBATCH {flag}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Escalate for SlurmExecutor {
+    fn escalate(&self, escalation: &Escalation) -> Self {
+        let mut escalated = self.clone();
+        let config = &mut escalated.config;
+        escalation.apply(&mut config.cpus, &mut config.memory, &mut config.time);
+        escalated
+    }
+}
+
 impl Executor for SlurmExecutor {
-    fn exe(&self, mut process: Process) -> NodeResult<Vec<PathBuf>> {
-        let (workdir, (log_path, mut log_handle), (launcher_path, mut launcher_handle)) =
-            process.prep_script_workdir()?;
-        writeln!(launcher_handle, "{}", self.config)
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        if self.submit_ahead {
+            return match executors::reuse(self, &process, true)? {
+                Reuse::Outputs(outputs) => Ok(outputs),
+                Reuse::Run(cache_key) => {
+                    let cache_handle = cache_key.map(|key| (key, process.clone()));
+                    self.exe_ahead(process, cache_handle)
+                }
+            };
+        }
+        run_reusable(self, process, |process| {
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir)
+            })
+        })
+    }
+}
+
+impl Reusable for SlurmExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        let config = &self.config;
+        (
+            "Slurm",
+            &self.container,
+            config.cpus,
+            &config.memory,
+            config.gpus,
+            config.tasks,
+            config.nodes,
+            &config.time,
+        )
+            .hash(state);
+    }
+}
+
+impl SlurmExecutor {
+    fn exe_attempt(&self, process: &Process, workdir: &Path) -> Result<Vec<PathBuf>, AttemptError> {
+        let (log_path, mut log_handle, submit_command) = self.submit_command(process, workdir)?;
+        scheduler::run_job(
+            self,
+            process,
+            workdir,
+            submit_command,
+            &mut log_handle,
+            &log_path,
+        )
+    }
+
+    /// Writes the launcher of `process` and prepares the command submitting it,
+    /// returning the log path and handle alongside the command
+    fn submit_command(
+        &self,
+        process: &Process,
+        workdir: &Path,
+    ) -> NodeResult<(PathBuf, File, Command)> {
+        let (log_path, log_handle, launcher_path) = self.write_launcher(process, workdir)?;
+        let submit_command = match &self.rest {
+            Some(rest) => rest.submit_command(&self.config, process, workdir, &launcher_path)?,
+            None => {
+                let mut sbatch = sbatch_command(workdir);
+                sbatch.arg(launcher_path);
+                sbatch
+            }
+        };
+        Ok((log_path, log_handle, submit_command))
+    }
+
+    /// Writes the launcher of `process` into `workdir`,
+    /// returning the log path and handle alongside the launcher path
+    fn write_launcher(
+        &self,
+        process: &Process,
+        workdir: &Path,
+    ) -> NodeResult<(PathBuf, File, PathBuf)> {
+        let ((log_path, log_handle), (launcher_path, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
+        writeln!(launcher_handle, "{}\n{REQUEUE_RESET}", self.config)
             .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
 
         let staging_mode = match self.container {
             None => &self.staging_mode,
             Some(_) => &StagingMode::Copy,
         };
-        process.stage_inputs(&mut launcher_handle, &workdir, staging_mode)?;
+        process.stage_inputs(&mut launcher_handle, workdir, staging_mode)?;
         for module_name in &self.modules {
             writeln!(launcher_handle, "module load {module_name}")
                 .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
         }
-        Process::write_execution(launcher_handle, &process, &self.container)?;
-
-        let output = Command::new("sbatch")
-            .args([
-                "-o",
-                ".maestro.log",
-                "-e",
-                ".maestro.err",
-                "--open-mode=append",
-            ])
-            .arg(launcher_path)
-            .current_dir(&workdir)
-            .output()
-            .map_err(|e| {
-                NodeError::msg(format!("Failed to spawn sbatch for job submission: {e}"))
-            })?;
-
-        struct SlurmJobGuard<'a> {
-            job_id: Option<&'a str>,
+        Process::write_execution(launcher_handle, process, &self.container)?;
+        Ok((log_path, log_handle, launcher_path))
+    }
+}
+
+/// A process submitted through [`SlurmExecutor`] as a [`JobExecutor`]
+pub struct SlurmJob(PolledJob<SlurmExecutor>);
+
+impl SlurmJob {
+    /// The Slurm id of the current attempt, unless it is waiting to be resubmitted
+    pub fn job_id(&self) -> Option<&str> {
+        self.0.active().map(|job| job.job_id.as_str())
+    }
+    /// The number of the current attempt, starting at 1
+    pub fn attempt(&self) -> u32 {
+        self.0.attempt()
+    }
+    pub fn workdir(&self) -> &Path {
+        self.0.workdir()
+    }
+}
+
+impl JobExecutor for SlurmExecutor {
+    type Job = SlurmJob;
+
+    /// Submits `process` as a job of its own, regardless of `submit_ahead`
+    fn submit(&self, process: Process) -> NodeResult<SlurmJob> {
+        PolledJob::submit(self, process).map(SlurmJob)
+    }
+    fn poll(&self, job: &mut SlurmJob) -> JobPoll {
+        job.0.poll(&self.retry)
+    }
+    fn cancel(&self, job: &mut SlurmJob) {
+        job.0.cancel();
+    }
+    fn poll_rate(&self) -> Duration {
+        self.poll_rate
+    }
+}
+
+impl PolledExecutor for SlurmExecutor {
+    type Attempt = SubmittedJob;
+
+    fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+    fn start_attempt(
+        &self,
+        process: &Process,
+        workdir: &Path,
+    ) -> Result<SubmittedJob, AttemptError> {
+        let (log_path, log_handle, submit_command) = self.submit_command(process, workdir)?;
+        scheduler::submit_job(self, workdir, submit_command, log_handle, log_path)
+    }
+    fn check_attempt(
+        &self,
+        attempt: &mut SubmittedJob,
+        process: &Process,
+        workdir: &Path,
+    ) -> AttemptPoll {
+        scheduler::poll_job(self, attempt, process, workdir)
+    }
+    fn cancel_attempt(&self, attempt: &mut SubmittedJob) {
+        scheduler::cancel_job(self, attempt);
+    }
+}
+
+/// Clears the staged inputs of an earlier run, as requeued jobs rerun their launcher from the top
+const REQUEUE_RESET: &str = r#"if [ "${SLURM_RESTART_COUNT:-0}" -gt 0 ]; then
+    echo ":: Job requeued; restart ${SLURM_RESTART_COUNT}"
+    rm -rf maestro_inputs
+fi"#;
+
+impl Scheduler for SlurmExecutor {
+    fn monitor(&self) -> &'static JobMonitor {
+        match &self.rest {
+            Some(rest) => rest.monitor(),
+            None => &SLURM_MONITOR,
+        }
+    }
+    fn poll_rate(&self) -> Duration {
+        self.poll_rate
+    }
+    fn parse_job_id(&self, stdout: &str) -> Option<String> {
+        if self.rest.is_some() {
+            return rest::parse_job_id(stdout);
         }
-        impl<'a> Drop for SlurmJobGuard<'a> {
-            fn drop(&mut self) {
-                if let Some(id) = &self.job_id {
-                    let _ = Command::new("scancel").arg(id).status();
-                }
+        stdout
+            .split_whitespace()
+            .last()
+            .and_then(|id| id.parse::<u32>().ok())
+            .map(|id| id.to_string())
+    }
+    fn cancel(&self, job_id: &str) {
+        match &self.rest {
+            Some(rest) => rest.cancel(job_id),
+            None => {
+                let _ = Command::new("scancel").arg(job_id).status();
             }
         }
+    }
+    fn collect(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        job_id: &str,
+        log_handle: &mut File,
+        log_path: &Path,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        match &self.rest {
+            Some(rest) => rest.collect_job(process, workdir, job_id, log_handle, log_path),
+            None => collect_job(process, workdir, job_id, log_handle, log_path),
+        }
+    }
+}
 
-        let job_id = if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let job_id = stdout
-                .split_whitespace()
-                .last()
-                .and_then(|id| id.parse::<u32>().ok())
-                .ok_or(NodeError::msg(format!(
-                    "Failed to parse sbatch output into a job code: {stdout}"
-                )));
-            let _ = match job_id {
-                Ok(id) => writeln!(log_handle, "{LP} Job submitted successfully! Id: {id}"),
-                Err(_) => writeln!(
-                    log_handle,
-                    "{LP} Failed to parse sbatch output into a job id\nstdout: {}",
-                    stdout
-                ),
-            };
-            job_id?.to_string()
-        } else {
-            let error_code = output
-                .status
-                .code()
-                .map(|code| format!("Error code: {}\n", code))
-                .unwrap_or(String::new());
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let _ = writeln!(
-                log_handle,
-                "{LP} Job failed to submit via sbatch!\n{error_code}stderr: {stderr}",
-            );
-            return Err(NodeError::msg(format!(
-                "Job did not submit successfully. Logs at {}",
-                log_path.display()
-            )));
-        };
-        let mut job_guard = SlurmJobGuard {
-            job_id: Some(&job_id),
-        };
-
-        let mut process_started = false;
-        let mut process_started_msg = || {
-            let _ = writeln!(log_handle, ":: Job execution started");
-        };
+/// Prepares an sbatch command submitting from `workdir`; the launcher is passed last
+fn sbatch_command(workdir: &Path) -> Command {
+    let mut sbatch = Command::new("sbatch");
+    sbatch
+        .args([
+            "-o",
+            ".maestro.log",
+            "-e",
+            ".maestro.err",
+            "--open-mode=append",
+        ])
+        .current_dir(workdir);
+    sbatch
+}
 
-        loop {
-            let squeue_out = Command::new("squeue")
-                .args(["-j", job_id.as_str(), "-h", "-o", "%T"])
-                .output()
-                .map_err(|e| {
-                    NodeError::msg(format!("Failed to spawn squeue to monitor job status: {e}"))
-                })?;
-
-            if squeue_out.stdout.is_empty() {
-                // Process finished
-                break;
-            } else if !process_started {
-                let stdout = String::from_utf8_lossy(&squeue_out.stdout);
-                // Process started
-                if stdout.trim() != "PENDING" {
-                    process_started = true;
-                    process_started_msg();
-                }
-            }
-            thread::sleep(self.poll_rate);
+/// Queries sacct for the final state of a job that left the queue and resolves its outcome
+fn collect_job(
+    process: &Process,
+    workdir: &Path,
+    job_id: &str,
+    log_handle: &mut File,
+    log_path: &Path,
+) -> Result<Vec<PathBuf>, AttemptError> {
+    let (job_state, job_status) = match accounting::sacct_job(job_id, log_handle)? {
+        Some(record) => {
+            record.metrics.write(workdir);
+            (Some(record.state), record.exit)
         }
+        None => (None, None),
+    };
+    resolve_job(
+        process, workdir, job_state, job_status, log_handle, log_path,
+    )
+}
 
-        job_guard.job_id = None;
-        // Process start was never read
-        if !process_started {
-            process_started_msg();
+/// Turns the final state and exit code of a job into the outcome of its attempt
+///
+/// Jobs without a finished state, such as those sacct has not caught up with,
+/// are judged by their exit code alone
+pub(crate) fn resolve_job(
+    process: &Process,
+    workdir: &Path,
+    job_state: Option<SlurmJobState>,
+    job_status: Option<(u32, u32)>,
+    log_handle: &mut File,
+    log_path: &Path,
+) -> Result<Vec<PathBuf>, AttemptError> {
+    let state = match (job_state, job_status) {
+        (Some(state), _) if state.is_finished() && state != SlurmJobState::Completed => state,
+        (_, Some((0, 0))) => SlurmJobState::Completed,
+        (_, Some(_)) => SlurmJobState::Failed,
+        (Some(SlurmJobState::Completed), None) => SlurmJobState::Completed,
+        (_, None) => {
+            let _ = writeln!(log_handle, ":: Failed to parse job status");
+            return Err(AttemptError::Failed(NodeError::msg(format!(
+                "Failed to parse job status. Logs at {}",
+                log_path.display()
+            ))));
         }
-        let job_info = Command::new("sacct")
-            .args(["-j", job_id.as_str(), "-o", "JobID,JobName,ExitCode,Elapsed,Start,End,TotalCPU,AveCPU,MaxRSS,AveRSS,MaxVMSize,AveVMSize"])
-            .output().map_err(|e| NodeError::msg(format!("Failed to spawn sacct to resolve job information: {e}")))?;
-        let stdout = String::from_utf8_lossy(&job_info.stdout);
-        let _ = writeln!(log_handle, "{LP} Job information\n{}", stdout);
-        let job_status = stdout
-            .lines()
-            .nth(2)
-            .and_then(|line| line.split_whitespace().nth(2))
-            .and_then(|codes| codes.split_once(':'))
-            .and_then(|(p1, p2)| {
-                let code_1: u32 = p1.parse().ok()?;
-                let code_2: u32 = p2.parse().ok()?;
-                Some((code_1, code_2))
-            });
-        match job_status {
-            Some((c1, c2)) => {
-                if c1 == 0 && c2 == 0 {
-                    let _ = writeln!(log_handle, "{LP} Job completed successfully!");
-                } else {
-                    let _ = writeln!(
-                        log_handle,
-                        "{LP} Job completed with non-zero exit code {c1}:{c2}\nstderr: .maestro.err"
-                    );
-                    return Err(NodeError::msg(format!(
-                        "Job completed with non-zero exit code. Logs at {}",
-                        log_path.display()
-                    )));
-                }
-            }
-            None => {
-                let _ = writeln!(log_handle, ":: Failed to parse job status");
-                return Err(NodeError::msg(format!(
-                    "Failed to parse job status. Logs at {}",
-                    log_path.display()
-                )));
-            }
+    };
+    if state != SlurmJobState::Completed {
+        let (exit_code, signal) = job_status.unwrap_or_default();
+        let error = SlurmJobError {
+            state,
+            exit_code,
+            signal,
         };
-
-        process.check_files(CheckTime::Output, Some(&workdir))?;
-        let mut outputs: Vec<_> = process
-            .outputs
-            .iter()
-            .map(|(_, p)| workdir.join(p))
-            .collect();
-        outputs.push(workdir);
-        Ok(outputs)
+        let _ = writeln!(log_handle, "{LP} {error}\nstderr: .maestro.err");
+        let message = NodeError::msg(format!("{error}. Logs at {}", log_path.display()));
+        return Err(match error.state {
+            SlurmJobState::OutOfMemory | SlurmJobState::Timeout => {
+                AttemptError::ResourcesExhausted(message)
+            }
+            // Jobs stopped on purpose are not rerun
+            SlurmJobState::Cancelled | SlurmJobState::Deadline => AttemptError::Fatal(message),
+            SlurmJobState::Preempted => AttemptError::Preempted(message),
+            _ => AttemptError::Failed(message),
+        });
     }
+    let _ = writeln!(log_handle, "{LP} Job completed successfully!");
+
+    process
+        .check_files(CheckTime::Output, Some(workdir))
+        .map_err(AttemptError::Failed)?;
+    Ok(process.output_paths(workdir.to_path_buf()))
 }
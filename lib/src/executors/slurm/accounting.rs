@@ -0,0 +1,470 @@
+use crate::{LP, metrics::ProcessMetrics};
+use dagger_lib::result::{NodeError, NodeResult};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    fs::File,
+    io::{self, Write as _},
+    process::Command,
+};
+
+/// The fields queried from sacct; those up to MaxRSS are parsed, the rest are only logged
+const SACCT_FIELDS: &str = "JobID,State,ExitCode,NodeList,Submit,Start,End,ElapsedRaw,TotalCPU,MaxRSS,JobName,MaxVMSize,AveCPU,AveRSS,AveVMSize";
+
+/// The state of a Slurm job or job step, as reported by sacct, squeue and slurmrestd
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SlurmJobState {
+    Pending,
+    Running,
+    Suspended,
+    Completing,
+    Requeued,
+    Resizing,
+    Completed,
+    Failed,
+    Timeout,
+    OutOfMemory,
+    Cancelled,
+    NodeFail,
+    Preempted,
+    BootFail,
+    Deadline,
+    Revoked,
+    /// A state unknown to maestro
+    Other(String),
+}
+
+impl SlurmJobState {
+    /// Parses a state name, ignoring trailing details such as in `CANCELLED by 1000`
+    /// and the `+` marking truncated names
+    pub fn parse(state: &str) -> Self {
+        let state = state.split_whitespace().next().unwrap_or_default();
+        match state.trim_end_matches('+') {
+            "PENDING" => Self::Pending,
+            "RUNNING" => Self::Running,
+            "SUSPENDED" => Self::Suspended,
+            "COMPLETING" => Self::Completing,
+            "REQUEUED" => Self::Requeued,
+            "RESIZING" => Self::Resizing,
+            "COMPLETED" => Self::Completed,
+            "FAILED" => Self::Failed,
+            "TIMEOUT" => Self::Timeout,
+            "OUT_OF_MEMORY" => Self::OutOfMemory,
+            "CANCELLED" => Self::Cancelled,
+            "NODE_FAIL" => Self::NodeFail,
+            "PREEMPTED" => Self::Preempted,
+            "BOOT_FAIL" => Self::BootFail,
+            "DEADLINE" => Self::Deadline,
+            "REVOKED" => Self::Revoked,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    /// Whether the job has ended and will not run again by itself
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            Self::Completed
+                | Self::Failed
+                | Self::Timeout
+                | Self::OutOfMemory
+                | Self::Cancelled
+                | Self::NodeFail
+                | Self::Preempted
+                | Self::BootFail
+                | Self::Deadline
+                | Self::Revoked
+        )
+    }
+}
+
+impl Display for SlurmJobState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            Self::Pending => "PENDING",
+            Self::Running => "RUNNING",
+            Self::Suspended => "SUSPENDED",
+            Self::Completing => "COMPLETING",
+            Self::Requeued => "REQUEUED",
+            Self::Resizing => "RESIZING",
+            Self::Completed => "COMPLETED",
+            Self::Failed => "FAILED",
+            Self::Timeout => "TIMEOUT",
+            Self::OutOfMemory => "OUT_OF_MEMORY",
+            Self::Cancelled => "CANCELLED",
+            Self::NodeFail => "NODE_FAIL",
+            Self::Preempted => "PREEMPTED",
+            Self::BootFail => "BOOT_FAIL",
+            Self::Deadline => "DEADLINE",
+            Self::Revoked => "REVOKED",
+            Self::Other(state) => state,
+        };
+        write!(f, "{state}")
+    }
+}
+
+/// A job that finished without completing, with the exit code and signal of its batch step
+#[derive(Clone, Debug)]
+pub struct SlurmJobError {
+    pub state: SlurmJobState,
+    pub exit_code: u32,
+    pub signal: u32,
+}
+
+impl Display for SlurmJobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match &self.state {
+            SlurmJobState::Failed => "Job failed",
+            SlurmJobState::Timeout => "Job exceeded its time limit",
+            SlurmJobState::OutOfMemory => "Job exceeded its memory limit",
+            SlurmJobState::Cancelled => "Job was cancelled",
+            SlurmJobState::NodeFail => "Job was terminated by a node failure",
+            SlurmJobState::Preempted => "Job was preempted",
+            SlurmJobState::BootFail => "Job failed to launch as its nodes failed to boot",
+            SlurmJobState::Deadline => "Job reached its deadline before completing",
+            SlurmJobState::Revoked => "Job was revoked by a sibling cluster",
+            _ => "Job did not complete",
+        };
+        write!(
+            f,
+            "{reason} ({}, exit code {}:{})",
+            self.state, self.exit_code, self.signal
+        )
+    }
+}
+
+impl Error for SlurmJobError {}
+
+/// The accounting of a job, with its own state and the exit code of its batch step
+pub(crate) struct SacctRecord {
+    pub(crate) state: SlurmJobState,
+    /// The exit code and signal, taken from the batch step if there is one
+    pub(crate) exit: Option<(u32, u32)>,
+    pub(crate) metrics: ProcessMetrics,
+    /// The sacct rows of the job and its steps
+    rows: Vec<String>,
+}
+
+impl SacctRecord {
+    /// Writes the sacct rows of the job to its log
+    pub(crate) fn log(&self, log_handle: &mut File) {
+        let _ = writeln!(
+            log_handle,
+            "{LP} Job information\n{}\n{}",
+            SACCT_FIELDS.replace(',', "|"),
+            self.rows.join("\n")
+        );
+        if let Some(requeues @ 1..) = self.metrics.requeues {
+            let _ = writeln!(log_handle, "{LP} Job requeues: {requeues}");
+        }
+    }
+}
+
+/// Queries sacct for the records of `job_ids`, a comma-separated list, keyed by job id
+///
+/// Steps other than the batch step keep their own records, under `<job>.<step>`.
+/// Runs of jobs that were requeued are all listed, so that the requeues can be counted
+pub(crate) fn sacct(job_ids: &str) -> io::Result<HashMap<String, SacctRecord>> {
+    let output = Command::new("sacct")
+        // Submit, Start and End are otherwise printed as ISO 8601 local times
+        .env("SLURM_TIME_FORMAT", "%s")
+        .args([
+            "--parsable2",
+            "--noheader",
+            "--duplicates",
+            "-j",
+            job_ids,
+            "-o",
+            SACCT_FIELDS,
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "sacct exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_records(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Queries sacct for the record of a single job or job step, logging it
+pub(crate) fn sacct_job(job_id: &str, log_handle: &mut File) -> NodeResult<Option<SacctRecord>> {
+    let record = sacct(job_id)
+        .map_err(|e| NodeError::msg(format!("Failed to query sacct for job information: {e}")))?
+        .remove(job_id);
+    match &record {
+        Some(record) => record.log(log_handle),
+        None => {
+            let _ = writeln!(log_handle, "{LP} Job {job_id} is unknown to sacct");
+        }
+    }
+    Ok(record)
+}
+
+/// Parses `--parsable2` sacct output, folding batch steps into the records of their jobs
+///
+/// Earlier runs of a requeued job are listed before its latest one, which replaces them
+fn parse_records(stdout: &str) -> HashMap<String, SacctRecord> {
+    let mut records: HashMap<String, SacctRecord> = HashMap::new();
+    for line in stdout.lines() {
+        let fields: Vec<&str> = line.split('|').collect();
+        let [
+            id,
+            state,
+            exit_code,
+            node,
+            submit,
+            start,
+            end,
+            elapsed,
+            cpu_time,
+            max_rss,
+            ..,
+        ] = fields[..]
+        else {
+            continue;
+        };
+        let exit = parse_exit_code(exit_code);
+        let peak_rss = parse_size(max_rss);
+        // Steps are listed right after their job
+        if let Some((job_id, step)) = id.split_once('.')
+            && let Some(record) = records.get_mut(job_id)
+        {
+            record.rows.push(line.to_string());
+            let metrics = &mut record.metrics;
+            metrics.peak_rss_bytes = metrics.peak_rss_bytes.max(peak_rss);
+            match step {
+                "batch" => {
+                    record.exit = exit.or(record.exit);
+                    continue;
+                }
+                "extern" => continue,
+                _ => {}
+            }
+        }
+        let mut rows = Vec::new();
+        let mut requeues = 0;
+        if let Some(earlier_run) = records.remove(id) {
+            rows = earlier_run.rows;
+            requeues = earlier_run.metrics.requeues.unwrap_or_default() + 1;
+        }
+        rows.push(line.to_string());
+        let metrics = ProcessMetrics {
+            wall_time_secs: elapsed.parse().ok(),
+            cpu_time_secs: parse_duration(cpu_time),
+            peak_rss_bytes: peak_rss,
+            node: Some(node.to_string()).filter(|node| !node.is_empty() && node != "None assigned"),
+            submit_time: submit.parse().ok(),
+            start_time: start.parse().ok(),
+            end_time: end.parse().ok(),
+            requeues: Some(requeues),
+            ..Default::default()
+        };
+        records.insert(
+            id.to_string(),
+            SacctRecord {
+                state: SlurmJobState::parse(state),
+                exit,
+                metrics,
+                rows,
+            },
+        );
+    }
+    for record in records.values_mut() {
+        if let Some(exit) = record.exit {
+            record.metrics.set_exit_status(exit);
+        }
+    }
+    records
+}
+
+/// Parses a duration of the form `[DD-][HH:]MM:SS[.mmm]` into seconds
+fn parse_duration(duration: &str) -> Option<f64> {
+    let (days, time) = match duration.split_once('-') {
+        Some((days, time)) => (days.parse::<f64>().ok()?, time),
+        None => (0.0, duration),
+    };
+    let mut secs = 0.0;
+    for part in time.split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(days * 86_400.0 + secs)
+}
+
+/// Parses a memory size such as `1234K` into bytes
+fn parse_size(size: &str) -> Option<u64> {
+    let (number, exponent) = match size.char_indices().last()? {
+        (i, 'K') => (&size[..i], 1),
+        (i, 'M') => (&size[..i], 2),
+        (i, 'G') => (&size[..i], 3),
+        (i, 'T') => (&size[..i], 4),
+        _ => (size, 0),
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * 1024f64.powi(exponent)) as u64)
+}
+
+/// Parses an sacct exit code of the form `<code>:<signal>`
+fn parse_exit_code(codes: &str) -> Option<(u32, u32)> {
+    let (p1, p2) = codes.split_once(':')?;
+    let code_1: u32 = p1.parse().ok()?;
+    let code_2: u32 = p2.parse().ok()?;
+    Some((code_1, code_2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_job_states() {
+        assert_eq!(SlurmJobState::parse("COMPLETED"), SlurmJobState::Completed);
+        assert_eq!(
+            SlurmJobState::parse("OUT_OF_MEMORY"),
+            SlurmJobState::OutOfMemory
+        );
+        assert_eq!(
+            SlurmJobState::parse("CANCELLED by 123"),
+            SlurmJobState::Cancelled
+        );
+        // States truncated by a field width are marked with a `+`
+        assert_eq!(SlurmJobState::parse("CANCELLED+"), SlurmJobState::Cancelled);
+        assert_eq!(
+            SlurmJobState::parse("OUT_OF_ME+"),
+            SlurmJobState::Other("OUT_OF_ME".to_string())
+        );
+        assert_eq!(
+            SlurmJobState::parse("SPECIAL_EXIT"),
+            SlurmJobState::Other("SPECIAL_EXIT".to_string())
+        );
+        assert_eq!(
+            SlurmJobState::parse(""),
+            SlurmJobState::Other(String::new())
+        );
+        assert!(SlurmJobState::parse("PREEMPTED").is_finished());
+        assert!(!SlurmJobState::parse("REQUEUED").is_finished());
+    }
+
+    #[test]
+    fn folds_batch_and_extern_steps_into_their_job() {
+        let stdout = "\
+123|OUT_OF_MEMORY|0:125|node01|1700000000|1700000002|1700000065|63|01:02.500||align|||||
+123.batch|OUT_OF_MEMORY|0:9|node01|1700000002|1700000002|1700000065|63|01:02.000|2048000K|batch|4096000K|01:02.000|2000000K|4000000K
+123.extern|COMPLETED|0:0|node01|1700000002|1700000002|1700000065|63|00:00:00|1024K|extern|0|00:00:00|0|0
+123.0|COMPLETED|0:0|node01|1700000003|1700000003|1700000060|57|00:30.250|3G|srun|3G|00:30.250|3G|3G
+";
+        let records = parse_records(stdout);
+        assert_eq!(records.len(), 2);
+
+        let job = &records["123"];
+        assert_eq!(job.state, SlurmJobState::OutOfMemory);
+        // The batch step holds the exit status of the script
+        assert_eq!(job.exit, Some((0, 9)));
+        assert_eq!(job.metrics.exit_code, None);
+        assert_eq!(job.metrics.signal, Some(9));
+        assert_eq!(job.metrics.wall_time_secs, Some(63.0));
+        assert_eq!(job.metrics.cpu_time_secs, Some(62.5));
+        assert_eq!(job.metrics.peak_rss_bytes, Some(3 * 1024 * 1024 * 1024));
+        assert_eq!(job.metrics.node.as_deref(), Some("node01"));
+        assert_eq!(job.metrics.submit_time, Some(1700000000));
+        assert_eq!(job.metrics.start_time, Some(1700000002));
+        assert_eq!(job.metrics.end_time, Some(1700000065));
+        assert_eq!(job.metrics.requeues, Some(0));
+        assert_eq!(job.rows.len(), 4);
+
+        let step = &records["123.0"];
+        assert_eq!(step.state, SlurmJobState::Completed);
+        assert_eq!(step.exit, Some((0, 0)));
+        assert_eq!(step.metrics.wall_time_secs, Some(57.0));
+    }
+
+    #[test]
+    fn counts_requeued_runs_listed_by_duplicates() {
+        let stdout = "\
+200|PREEMPTED|0:15|node07|1700000000|1700000010|1700000100|90|00:10.000||sim|||||
+200.batch|CANCELLED|0:15|node07|1700000010|1700000010|1700000100|90|00:10.000|1M|batch|||||
+200|PREEMPTED|0:15|node03|1700000000|1700000200|1700000300|100|00:10.000||sim|||||
+200.batch|CANCELLED|0:15|node03|1700000200|1700000200|1700000300|100|00:10.000|1M|batch|||||
+200|COMPLETED|0:0|node05|1700000000|1700000400|1700000460|60|00:59.000||sim|||||
+200.batch|COMPLETED|0:0|node05|1700000400|1700000400|1700000460|60|00:59.000|5M|batch|||||
+";
+        let records = parse_records(stdout);
+        let job = &records["200"];
+        assert_eq!(job.state, SlurmJobState::Completed);
+        assert_eq!(job.exit, Some((0, 0)));
+        assert_eq!(job.metrics.exit_code, Some(0));
+        assert_eq!(job.metrics.requeues, Some(2));
+        assert_eq!(job.metrics.node.as_deref(), Some("node05"));
+        assert_eq!(job.metrics.wall_time_secs, Some(60.0));
+        assert_eq!(job.metrics.peak_rss_bytes, Some(5 * 1024 * 1024));
+        assert_eq!(job.rows.len(), 6);
+    }
+
+    #[test]
+    fn keys_array_tasks_by_task_id() {
+        let stdout = "\
+300_1|COMPLETED|0:0|node01|1700000000|1700000001|1700000011|10|00:05.000||array|||||
+300_1.batch|COMPLETED|0:0|node01|1700000001|1700000001|1700000011|10|00:05.000|100K|batch|||||
+300_2|FAILED|3:0|node02|1700000000|1700000001|1700000004|3|00:01.000||array|||||
+300_2.batch|FAILED|3:0|node02|1700000001|1700000001|1700000004|3|00:01.000|100K|batch|||||
+300_3|CANCELLED by 1000|0:0|None assigned|1700000000|Unknown|1700000002|0|00:00:00||array|||||
+";
+        let records = parse_records(stdout);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records["300_1"].state, SlurmJobState::Completed);
+        assert_eq!(records["300_2"].state, SlurmJobState::Failed);
+        assert_eq!(records["300_2"].exit, Some((3, 0)));
+        assert_eq!(records["300_2"].metrics.exit_code, Some(3));
+
+        let never_started = &records["300_3"];
+        assert_eq!(never_started.state, SlurmJobState::Cancelled);
+        assert_eq!(never_started.metrics.node, None);
+        assert_eq!(never_started.metrics.start_time, None);
+        assert_eq!(never_started.metrics.peak_rss_bytes, None);
+    }
+
+    #[test]
+    fn skips_malformed_rows() {
+        let records = parse_records(
+            "\n400|COMPLETED\n400|COMPLETED|0:0|node01|1|2|3|1|00:01.000||job|||||\n",
+        );
+        assert_eq!(records.len(), 1);
+        assert_eq!(records["400"].state, SlurmJobState::Completed);
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("00:00"), Some(0.0));
+        assert_eq!(parse_duration("01:02.500"), Some(62.5));
+        assert_eq!(parse_duration("02:03:04"), Some(7384.0));
+        assert_eq!(parse_duration("1-00:00:01"), Some(86_401.0));
+        assert_eq!(parse_duration("3-12:30:00.250"), Some(304_200.25));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("INVALID"), None);
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("0"), Some(0));
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("2048K"), Some(2048 * 1024));
+        assert_eq!(parse_size("1.5M"), Some(1536 * 1024));
+        assert_eq!(parse_size("2G"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1T"), Some(1024u64.pow(4)));
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("K"), None);
+    }
+
+    #[test]
+    fn parses_exit_codes() {
+        assert_eq!(parse_exit_code("0:0"), Some((0, 0)));
+        assert_eq!(parse_exit_code("1:0"), Some((1, 0)));
+        assert_eq!(parse_exit_code("0:9"), Some((0, 9)));
+        assert_eq!(parse_exit_code("0:125"), Some((0, 125)));
+        assert_eq!(parse_exit_code(""), None);
+        assert_eq!(parse_exit_code("1"), None);
+        assert_eq!(parse_exit_code("-1:0"), None);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::{
+    LP, Process, WorkflowResult,
+    executors::{
+        self,
+        monitor::{JobStatus, SLURM_MONITOR},
+        retry::AttemptError,
+        scheduler::{log_start, submit},
+        slurm::{SlurmExecutor, accounting::sacct, collect_job, sbatch_command},
+    },
+    shutdown::InFlight,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use std::{
+    fs::File,
+    io::Write as _,
+    path::{self, Path, PathBuf},
+    process::Command,
+    sync::{Arc, Condvar, LazyLock, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Duration,
+};
+
+/// Every job submitted ahead during the session
+static SUBMITTED: LazyLock<Mutex<Vec<Arc<SubmittedJob>>>> = LazyLock::new(Default::default);
+
+/// A job submitted without waiting for it to run, tracked until it leaves the queue
+struct SubmittedJob {
+    process_name: String,
+    job_id: String,
+    /// The declared outputs of the process, followed by its workdir
+    outputs: Vec<PathBuf>,
+    /// Set once the job has finished, holding the failure if it did not succeed
+    outcome: Mutex<Option<Result<(), String>>>,
+    resolved: Condvar,
+}
+
+impl SubmittedJob {
+    fn lock(&self) -> MutexGuard<'_, Option<Result<(), String>>> {
+        self.outcome.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    fn outcome(&self) -> Option<Result<(), String>> {
+        self.lock().clone()
+    }
+    fn wait(&self) -> Result<(), String> {
+        let mut outcome = self.lock();
+        loop {
+            if let Some(result) = outcome.as_ref() {
+                return result.clone();
+            }
+            outcome = self
+                .resolved
+                .wait(outcome)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+    fn resolve(&self, result: Result<(), String>) {
+        *self.lock() = Some(result);
+        self.resolved.notify_all();
+    }
+    fn produces(&self, path: &Path) -> bool {
+        self.outputs.iter().any(|output| path.starts_with(output))
+    }
+    /// Whether the workdir of the job, the last of its outputs, lies within `dir`
+    fn runs_in(&self, dir: &Path) -> bool {
+        self.outputs
+            .last()
+            .is_some_and(|workdir| workdir.starts_with(dir))
+    }
+}
+
+fn submitted() -> MutexGuard<'static, Vec<Arc<SubmittedJob>>> {
+    SUBMITTED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The submitted jobs producing inputs of `process`
+fn upstream_jobs(process: &Process) -> Vec<Arc<SubmittedJob>> {
+    let submitted = submitted();
+    let mut upstream: Vec<Arc<SubmittedJob>> = Vec::new();
+    for (_, input) in &process.inputs {
+        let input = absolute(input);
+        for job in submitted.iter().filter(|job| job.produces(&input)) {
+            if !upstream.iter().any(|known| Arc::ptr_eq(known, job)) {
+                upstream.push(Arc::clone(job));
+            }
+        }
+    }
+    upstream
+}
+
+fn upstream_failed(process: &Process, job: &SubmittedJob, error: &str) -> NodeError {
+    NodeError::msg(format!(
+        "Process {} depends on process {} (job {}), which failed: {error}",
+        process.name, job.process_name, job.job_id
+    ))
+}
+
+/// Whether `path` lies within the outputs of a job submitted ahead,
+/// in which case it may not exist yet
+pub(crate) fn is_submitted_output(path: &Path) -> bool {
+    let path = absolute(path);
+    submitted().iter().any(|job| job.produces(&path))
+}
+
+/// Waits for the submitted jobs producing inputs of `process`, failing if any of them failed
+pub(crate) fn await_upstream(process: &Process) -> NodeResult<()> {
+    for job in upstream_jobs(process) {
+        if let Err(e) = job.wait() {
+            return Err(upstream_failed(process, &job, &e));
+        }
+    }
+    Ok(())
+}
+
+/// Waits for the jobs submitted ahead within the session workdir `session`,
+/// or for every job submitted ahead if `None`, reporting how many failed
+pub(crate) fn await_submitted(session: Option<&Path>) {
+    let session = session.map(absolute);
+    let submitted: Vec<_> = submitted()
+        .iter()
+        .filter(|job| session.as_ref().is_none_or(|dir| job.runs_in(dir)))
+        .cloned()
+        .collect();
+    if submitted.is_empty() {
+        return;
+    }
+    let outstanding = submitted
+        .iter()
+        .filter(|job| job.outcome().is_none())
+        .count();
+    if outstanding > 0 {
+        println!("{LP} Waiting for {outstanding} submitted jobs to finish");
+    }
+    let failed = submitted.iter().filter(|job| job.wait().is_err()).count();
+    if failed > 0 {
+        eprintln!(
+            "{LP} {failed} of {} submitted processes failed",
+            submitted.len()
+        );
+    }
+}
+
+impl SlurmExecutor {
+    /// Submits `process` without waiting for it to run, returning the paths its outputs will have
+    ///
+    /// Inputs produced by jobs that are still pending become `afterok` dependencies.
+    /// Completion is tracked in the background; once the job succeeds, the process is recorded
+    /// as complete and its outputs are cached under the key held by `cache_handle`.
+    /// Retries do not apply, as dependent jobs are bound to the first submission
+    pub(crate) fn exe_ahead(
+        &self,
+        process: Process,
+        cache_handle: Option<(String, Process)>,
+    ) -> WorkflowResult {
+        if self.rest.is_some() {
+            return Err(NodeError::msg(format!(
+                "Process {} cannot be submitted ahead over slurmrestd",
+                process.name
+            )));
+        }
+        let upstream = upstream_jobs(&process);
+        let finished = finished_in_accounting(&upstream);
+        let mut dependencies = Vec::new();
+        for job in &upstream {
+            // A job that already ended is resolved by its tracker shortly; depending on it instead
+            // could never be satisfied once Slurm purges it from the queue
+            let outcome = match job.outcome() {
+                None if finished.contains(&job.job_id) => Some(job.wait()),
+                outcome => outcome,
+            };
+            match outcome {
+                None => dependencies.push(job.job_id.as_str()),
+                Some(Ok(())) => {}
+                Some(Err(e)) => return Err(upstream_failed(&process, job, &e)),
+            }
+        }
+
+        let workdir = process.create_workdir()?;
+        let (log_path, mut log_handle, launcher_path) = self.write_launcher(&process, &workdir)?;
+        let mut sbatch = sbatch_command(&workdir);
+        if !dependencies.is_empty() {
+            let _ = writeln!(
+                log_handle,
+                "{LP} Waiting on upstream jobs {}",
+                dependencies.join(", ")
+            );
+            // Overrides the dependency directive, so any configured dependency is kept alongside
+            let mut dependency = format!("afterok:{}", dependencies.join(":"));
+            if let Some(configured) = &self.config.dependency {
+                dependency = format!("{dependency},{configured}");
+            }
+            sbatch.arg(format!("--dependency={dependency}"));
+        }
+        sbatch.arg(launcher_path);
+        let job_id =
+            submit(self, sbatch, &mut log_handle, &log_path).map_err(AttemptError::into_inner)?;
+
+        let outputs = process.output_paths(workdir.clone());
+        let job = Arc::new(SubmittedJob {
+            process_name: process.name.clone(),
+            job_id,
+            outputs: outputs.iter().map(|output| absolute(output)).collect(),
+            outcome: Mutex::new(None),
+            resolved: Condvar::new(),
+        });
+        submitted().push(Arc::clone(&job));
+
+        let poll_rate = self.poll_rate;
+        let tracked_job = Arc::clone(&job);
+        let tracker = move || {
+            let result = track(
+                &process,
+                &workdir,
+                &tracked_job,
+                &upstream,
+                poll_rate,
+                log_handle,
+                &log_path,
+            )
+            .and_then(|outputs| executors::finish(Ok(outputs), cache_handle))
+            .map(|_| ())
+            .map_err(|e| format!("{e:?}"));
+            if let Err(e) = &result {
+                eprintln!("{LP} Process {} failed: {e}", process.name);
+            }
+            tracked_job.resolve(result);
+        };
+        if let Err(e) = thread::Builder::new()
+            .name(format!("maestro-job-{}", job.job_id))
+            .spawn(tracker)
+        {
+            let error = format!("Failed to spawn job tracker: {e}");
+            job.resolve(Err(error.clone()));
+            return Err(NodeError::msg(error));
+        }
+        Ok(outputs)
+    }
+}
+
+/// The ids of the unresolved `jobs` that sacct reports as finished
+///
+/// If sacct is unavailable, no job is reported, leaving them all as dependencies
+fn finished_in_accounting(jobs: &[Arc<SubmittedJob>]) -> Vec<String> {
+    let unresolved: Vec<&str> = jobs
+        .iter()
+        .filter(|job| job.outcome().is_none())
+        .map(|job| job.job_id.as_str())
+        .collect();
+    if unresolved.is_empty() {
+        return Vec::new();
+    }
+    let Ok(records) = sacct(&unresolved.join(",")) else {
+        return Vec::new();
+    };
+    unresolved
+        .into_iter()
+        .filter(|job_id| {
+            records
+                .get(*job_id)
+                .is_some_and(|record| record.state.is_finished())
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Waits for a submitted job to leave the queue and resolves its outcome
+fn track(
+    process: &Process,
+    workdir: &Path,
+    job: &SubmittedJob,
+    upstream: &[Arc<SubmittedJob>],
+    poll_rate: Duration,
+    mut log_handle: File,
+    log_path: &Path,
+) -> WorkflowResult {
+    let cancelled_job_id = job.job_id.clone();
+    let _in_flight = InFlight::job(vec![workdir.to_path_buf()], move || {
+        let _ = Command::new("scancel").arg(&cancelled_job_id).status();
+    });
+    let mut starts = 0;
+    let status = SLURM_MONITOR.wait(&job.job_id, poll_rate, || {
+        log_start(&mut log_handle, &mut starts)
+    });
+    let result = if status == JobStatus::DependencyNeverSatisfied {
+        let _ = Command::new("scancel").arg(&job.job_id).status();
+        let _ = writeln!(
+            log_handle,
+            "{LP} Job cancelled, as an upstream job it depends on failed"
+        );
+        Err(NodeError::msg(format!(
+            "Job {} can never start, as an upstream job failed",
+            job.job_id
+        )))
+    } else {
+        collect_job(process, workdir, &job.job_id, &mut log_handle, log_path)
+            .map_err(AttemptError::into_inner)
+    };
+
+    // Clusters with kill_invalid_depend set cancel such jobs instead; either way,
+    // report the upstream failure as the cause
+    result.map_err(|e| {
+        upstream
+            .iter()
+            .find_map(|upstream_job| {
+                let error = upstream_job.wait().err()?;
+                Some(upstream_failed(process, upstream_job, &error))
+            })
+            .unwrap_or(e)
+    })
+}
@@ -0,0 +1,493 @@
+use crate::{
+    Container, LP, Process,
+    executors::{
+        Executor, Reusable,
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+        slurm::{MemoryConfig, SlurmConfig, SlurmTime, accounting::sacct_job, resolve_job},
+    },
+    process::StagingMode,
+    shutdown::{self, InFlight},
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::Deserialize;
+use std::{
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Write as _,
+    os::unix::process::CommandExt as _,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Condvar, Mutex, PoisonError},
+};
+
+/// The file each step records its `<job>.<step>` id into
+const STEP_ID_FILE: &str = ".maestro.step";
+
+/// An executor that runs processes as job steps packed into a single Slurm allocation
+///
+/// When maestro itself runs inside a Slurm job, such as under salloc or sbatch,
+/// that allocation is reused. Otherwise one is acquired through `salloc --no-shell`
+/// on first use and released when the session ends.
+/// Steps wait within srun until the allocation has room for them
+#[derive(Clone, Deserialize, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct SlurmAllocationExecutor {
+    pub(crate) container: Option<Container>,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(default)]
+    pub(crate) staging_mode: StagingMode,
+    #[serde(default)]
+    pub(crate) modules: Vec<String>,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
+    /// The allocation to acquire when not already running inside one
+    #[serde(default)]
+    pub(crate) allocation: SlurmConfig,
+    pub(crate) cpus: Option<u64>,
+    pub(crate) memory: Option<MemoryConfig>,
+    pub(crate) gpus: Option<u64>,
+    pub(crate) time: Option<SlurmTime>,
+}
+
+impl Default for SlurmAllocationExecutor {
+    fn default() -> Self {
+        Self {
+            container: None,
+            cache: false,
+            staging_mode: StagingMode::Symlink,
+            modules: Vec::new(),
+            retry: RetryPolicy::default(),
+            allocation: SlurmConfig::default(),
+            cpus: None,
+            memory: None,
+            gpus: None,
+            time: None,
+        }
+    }
+}
+
+impl SlurmAllocationExecutor {
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    pub fn with_staging_mode(mut self, staging_mode: StagingMode) -> Self {
+        self.staging_mode = staging_mode;
+        self
+    }
+    pub fn with_module<S: ToString>(mut self, module: S) -> Self {
+        self.modules.push(module.to_string());
+        self
+    }
+    pub fn with_modules<S: ToString, M: IntoIterator<Item = S>>(mut self, modules: M) -> Self {
+        let transformed_modules = modules.into_iter().map(|module| module.to_string());
+        self.modules.extend(transformed_modules);
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
+    /// Sets the allocation acquired when maestro is not already running inside one
+    pub fn with_allocation(mut self, allocation: SlurmConfig) -> Self {
+        self.allocation = allocation;
+        self
+    }
+    pub fn map_allocation<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(SlurmConfig) -> SlurmConfig,
+    {
+        self.allocation = (f)(self.allocation);
+        self
+    }
+    pub fn with_cpus(mut self, cpus: u64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+    pub fn with_memory(mut self, memory: MemoryConfig) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+    pub fn with_gpus(mut self, gpus: u64) -> Self {
+        self.gpus = Some(gpus);
+        self
+    }
+    pub fn with_time(mut self, time: SlurmTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+/// An allocation shared by the executors with the same configuration
+#[derive(Default)]
+struct Allocation {
+    state: Mutex<AllocationState>,
+    /// Notified when a pending request is granted or fails
+    settled: Condvar,
+}
+
+#[derive(Default)]
+enum AllocationState {
+    #[default]
+    Vacant,
+    /// Requested through salloc by one process, which the others wait on
+    Pending,
+    Granted(String),
+}
+
+/// Allocations acquired during the session, keyed by the hash of their configuration
+static ALLOCATIONS: Mutex<Vec<(u64, Arc<Allocation>)>> = Mutex::new(Vec::new());
+
+/// Returns the allocation shared by executors with the same `config`
+fn allocation(config: &SlurmConfig) -> Arc<Allocation> {
+    let mut hasher = DefaultHasher::new();
+    config.hash(&mut hasher);
+    let key = hasher.finish();
+    let mut allocations = ALLOCATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+    match allocations.iter().find(|(known_key, _)| *known_key == key) {
+        Some((_, allocation)) => allocation.clone(),
+        None => {
+            let allocation = Arc::new(Allocation::default());
+            allocations.push((key, allocation.clone()));
+            allocation
+        }
+    }
+}
+
+/// Forgets `job_id` as the allocation for `config`, so the next step acquires another
+///
+/// Returns false if `job_id` is the allocation maestro itself runs inside, which cannot be replaced
+fn release(config: &SlurmConfig, job_id: &str) -> bool {
+    if env::var_os("SLURM_JOB_ID").is_some() {
+        return false;
+    }
+    let allocation = allocation(config);
+    let mut state = allocation
+        .state
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    // Another step may have replaced it already
+    if matches!(&*state, AllocationState::Granted(granted) if granted == job_id) {
+        *state = AllocationState::Vacant;
+    }
+    true
+}
+
+fn scancel(job_id: &str) {
+    let _ = Command::new("scancel")
+        .arg(job_id)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Whether srun's `stderr` reports that its allocation no longer exists
+fn allocation_gone(stderr: &str) -> bool {
+    [
+        "Invalid job id specified",
+        "Job has expired",
+        "already completing or completed",
+    ]
+    .iter()
+    .any(|message| stderr.contains(message))
+}
+
+/// Releases the allocations acquired during the session
+///
+/// Requests still pending are left to scancel their allocation once granted
+pub(crate) fn release_allocations() {
+    let mut allocations = ALLOCATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+    for (_, allocation) in allocations.drain(..) {
+        let mut state = allocation
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let AllocationState::Granted(job_id) = &*state {
+            scancel(job_id);
+            *state = AllocationState::Vacant;
+        }
+    }
+}
+
+impl Escalate for SlurmAllocationExecutor {
+    fn escalate(&self, escalation: &Escalation) -> Self {
+        let mut escalated = self.clone();
+        escalation.apply(
+            &mut escalated.cpus,
+            &mut escalated.memory,
+            &mut escalated.time,
+        );
+        escalated
+    }
+}
+
+impl Executor for SlurmAllocationExecutor {
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        run_reusable(self, process, |process| {
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir)
+            })
+        })
+    }
+}
+
+impl Reusable for SlurmAllocationExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        (
+            "SlurmAllocation",
+            &self.container,
+            self.cpus,
+            &self.memory,
+            self.gpus,
+            &self.time,
+        )
+            .hash(state);
+    }
+}
+
+impl SlurmAllocationExecutor {
+    fn exe_attempt(&self, process: &Process, workdir: &Path) -> Result<Vec<PathBuf>, AttemptError> {
+        let mut job_id = self.acquire(workdir)?;
+
+        let ((log_path, mut log_handle), (launcher_path, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
+        writeln!(
+            launcher_handle,
+            "echo \"${{SLURM_JOB_ID}}.${{SLURM_STEP_ID}}\" > {STEP_ID_FILE}"
+        )
+        .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+        let staging_mode = match self.container {
+            None => &self.staging_mode,
+            Some(_) => &StagingMode::Copy,
+        };
+        process.stage_inputs(&mut launcher_handle, workdir, staging_mode)?;
+        for module_name in &self.modules {
+            writeln!(launcher_handle, "module load {module_name}")
+                .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+        }
+        Process::write_execution(launcher_handle, process, &self.container)?;
+
+        let err_path = workdir.join(".maestro.err");
+        let mut reacquired = false;
+        let status = loop {
+            let _ = fs::remove_file(workdir.join(STEP_ID_FILE));
+            let _ = writeln!(log_handle, "{LP} Launching step in allocation {job_id}");
+            let err_start = fs::metadata(&err_path).map_or(0, |metadata| metadata.len());
+            let err_handle = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&err_path)?;
+            // srun runs in a process group of its own, so that it can be signalled on shutdown
+            let mut srun = self
+                .srun_command(process, &job_id)
+                .arg(&launcher_path)
+                .current_dir(workdir)
+                .stdin(Stdio::null())
+                .stdout(log_handle.try_clone()?)
+                .stderr(err_handle)
+                .process_group(0)
+                .spawn()
+                .map_err(|e| NodeError::msg(format!("Failed to spawn srun: {e}")))?;
+            let in_flight = InFlight::process_group(workdir, srun.id());
+            let status = srun
+                .wait()
+                .map_err(|e| NodeError::msg(format!("Failed to wait on srun: {e}")))?;
+            drop(in_flight);
+
+            // An allocation that ended, such as by reaching its time limit, is acquired anew
+            // and the step launched once more
+            let step_started = workdir.join(STEP_ID_FILE).exists();
+            if status.success() || step_started || reacquired {
+                break status;
+            }
+            let stderr = fs::read(&err_path).unwrap_or_default();
+            let stderr =
+                String::from_utf8_lossy(stderr.get(err_start as usize..).unwrap_or_default());
+            if !allocation_gone(&stderr) || !release(&self.allocation, &job_id) {
+                break status;
+            }
+            let _ = writeln!(
+                log_handle,
+                "{LP} Allocation {job_id} is no longer available, acquiring another"
+            );
+            job_id = self.acquire(workdir)?;
+            reacquired = true;
+        };
+
+        // srun exits before the step is recorded as finished, so its own exit status
+        // stands in for the exit code until sacct catches up
+        let srun_status = match status.code() {
+            Some(code) => (code as u32, 0),
+            None => (1, 0),
+        };
+        let step_id = fs::read_to_string(workdir.join(STEP_ID_FILE)).ok();
+        let step = match step_id.as_deref().map(str::trim) {
+            Some(step_id) => sacct_job(step_id, &mut log_handle)?,
+            None => {
+                let _ = writeln!(log_handle, "{LP} Step did not start");
+                None
+            }
+        };
+        if let Some(step) = &step {
+            step.metrics.write(workdir);
+        }
+        let (step_state, step_status) = match step {
+            Some(step) if step.state.is_finished() => (Some(step.state), step.exit),
+            _ => (None, Some(srun_status)),
+        };
+        resolve_job(
+            process,
+            workdir,
+            step_state,
+            step_status.or(Some(srun_status)),
+            &mut log_handle,
+            &log_path,
+        )
+    }
+
+    /// Prepares an srun command launching a single-task step within `job_id`;
+    /// the launcher is passed last
+    fn srun_command(&self, process: &Process, job_id: &str) -> Command {
+        let mut srun = Command::new("srun");
+        srun.arg(format!("--jobid={job_id}"))
+            .args(["--exclusive", "--nodes=1", "--ntasks=1"])
+            .arg(format!("--cpus-per-task={}", self.cpus.unwrap_or(1)))
+            .arg(format!("--job-name={}", process.name));
+        match self.memory {
+            Some(memory @ MemoryConfig::PerCpu(_)) => {
+                srun.arg(format!("--mem-per-cpu={memory}"));
+            }
+            Some(memory @ MemoryConfig::PerNode(_)) => {
+                srun.arg(format!("--mem={memory}"));
+            }
+            None => {}
+        }
+        if let Some(gpus) = self.gpus {
+            srun.arg(format!("--gpus={gpus}"));
+        }
+        if let Some(time) = self.time {
+            srun.arg(format!("--time={time}"));
+        }
+        srun
+    }
+
+    /// Returns the id of the allocation steps are launched into,
+    /// acquiring it if maestro is not already running inside one
+    ///
+    /// Processes needing the same allocation wait for the one requesting it,
+    /// which registers salloc for cancellation in `workdir`
+    fn acquire(&self, workdir: &Path) -> NodeResult<String> {
+        if let Ok(job_id) = env::var("SLURM_JOB_ID") {
+            return Ok(job_id);
+        }
+
+        let allocation = allocation(&self.allocation);
+        let mut state = allocation
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        loop {
+            match &*state {
+                AllocationState::Granted(job_id) => return Ok(job_id.clone()),
+                AllocationState::Pending => {
+                    state = allocation
+                        .settled
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                AllocationState::Vacant if shutdown::interrupted() => {
+                    return Err(NodeError::msg(
+                        "Not acquiring a Slurm allocation while shutting down".to_string(),
+                    ));
+                }
+                AllocationState::Vacant => break,
+            }
+        }
+        *state = AllocationState::Pending;
+        drop(state);
+
+        let granted = self.request_allocation(workdir);
+        let mut state = allocation
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        allocation.settled.notify_all();
+        match granted {
+            // The allocation would outlive the session once release_allocations has run
+            Ok(job_id) if shutdown::interrupted() => {
+                scancel(&job_id);
+                *state = AllocationState::Vacant;
+                Err(NodeError::msg(format!(
+                    "Released Slurm allocation {job_id}, granted while shutting down"
+                )))
+            }
+            Ok(job_id) => {
+                eprintln!("{LP} Acquired Slurm allocation {job_id}");
+                *state = AllocationState::Granted(job_id.clone());
+                Ok(job_id)
+            }
+            Err(e) => {
+                *state = AllocationState::Vacant;
+                Err(e)
+            }
+        }
+    }
+
+    /// Requests a new allocation through `salloc --no-shell`, returning its job id once granted
+    fn request_allocation(&self, workdir: &Path) -> NodeResult<String> {
+        // salloc runs in a process group of its own, so that shutdown can withdraw the request
+        let salloc = Command::new("salloc")
+            .args(["--no-shell", "--job-name=maestro"])
+            .args(self.allocation.flags())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .map_err(|e| NodeError::msg(format!("Failed to spawn salloc: {e}")))?;
+        let in_flight = InFlight::process_group(workdir, salloc.id());
+        let output = salloc
+            .wait_with_output()
+            .map_err(|e| NodeError::msg(format!("Failed to wait on salloc: {e}")))?;
+        drop(in_flight);
+
+        // salloc: Granted job allocation 123
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let granted = stderr
+            .lines()
+            .find_map(|line| line.split_once("Granted job allocation "))
+            .and_then(|(_, id)| id.trim().parse::<u64>().ok());
+        match granted {
+            Some(id) if output.status.success() => Ok(id.to_string()),
+            // A request withdrawn after its grant still leaves the allocation behind
+            Some(id) => {
+                scancel(&id.to_string());
+                Err(NodeError::msg(format!(
+                    "Failed to acquire a Slurm allocation: {}",
+                    stderr.trim()
+                )))
+            }
+            None => Err(NodeError::msg(format!(
+                "Failed to acquire a Slurm allocation: {}",
+                stderr.trim()
+            ))),
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use crate::{
+    LP, Process, WorkflowResult,
+    executors::{
+        Executor,
+        retry::{AttemptError, AttemptRecord, Escalate},
+        run_reusable_batch,
+        scheduler::{await_job, submit},
+        slurm::{
+            SlurmExecutor,
+            accounting::{SacctRecord, sacct},
+            resolve_job,
+        },
+    },
+    shutdown,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write as _},
+    os::unix::fs::OpenOptionsExt as _,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::LazyLock,
+    thread,
+    time::Duration,
+};
+
+type TaskOutcome = Result<Vec<PathBuf>, AttemptError>;
+
+/// How many times sacct is queried for tasks it has not recorded as finished
+const SACCT_LOOKUPS: u32 = 5;
+const SACCT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+impl SlurmExecutor {
+    /// Runs a batch of processes as a Slurm job array
+    ///
+    /// Each process becomes one array task with its own workdir. Batches larger than the
+    /// `MaxArraySize` of the cluster are split across several arrays. When `max_concurrent` is set,
+    /// at most that many tasks run at once. Results are returned in the order of `processes`
+    ///
+    /// Failed tasks are resubmitted together as a smaller array under the retry policy of the executor;
+    /// if any of them ran out of resources, the escalation applies to the whole resubmitted array.
+    /// Preempted tasks are resubmitted alongside them without counting as failed
+    ///
+    /// Over slurmrestd, each process is submitted as a job of its own instead
+    pub fn exe_array(
+        &self,
+        processes: Vec<Process>,
+        max_concurrent: Option<u32>,
+    ) -> Vec<WorkflowResult> {
+        if self.rest.is_some() {
+            return self.exe_each(processes, max_concurrent);
+        }
+        run_reusable_batch(self, processes, |processes| {
+            self.exe_array_tasks(processes, max_concurrent)
+        })
+    }
+
+    /// Runs the processes as tasks of one job array, resubmitting the unsuccessful ones
+    fn exe_array_tasks(
+        &self,
+        processes: Vec<Process>,
+        max_concurrent: Option<u32>,
+    ) -> Vec<WorkflowResult> {
+        let mut results: Vec<Option<WorkflowResult>> = processes.iter().map(|_| None).collect();
+        let mut pending = Vec::with_capacity(processes.len());
+        for (index, process) in processes.iter().enumerate() {
+            match process.create_workdir() {
+                Ok(workdir) => pending.push((index, workdir)),
+                Err(e) => results[index] = Some(Err(e)),
+            }
+        }
+
+        let mut current = self.clone();
+        let mut attempt_number = 1;
+        // Resubmissions after failures, which the backoff grows with
+        let mut failed_rounds = 0;
+        let mut records = vec![Vec::new(); processes.len()];
+        let mut failures = vec![0; processes.len()];
+        let mut preemptions = vec![0; processes.len()];
+        while !pending.is_empty() {
+            let outcomes = current.exe_array_attempt(&processes, &pending, max_concurrent);
+            let mut failed = Vec::new();
+            let mut exhausted = false;
+            // Preempted tasks are resubmitted without waiting out the backoff
+            let mut only_preempted = true;
+            for ((index, workdir), outcome) in pending.into_iter().zip(outcomes) {
+                records[index].push(AttemptRecord::new(attempt_number, &outcome));
+                AttemptRecord::write(&records[index], &workdir);
+                let (error, preempted) = match outcome {
+                    Ok(outputs) => {
+                        results[index] = Some(Ok(outputs));
+                        continue;
+                    }
+                    Err(AttemptError::Fatal(e)) => {
+                        results[index] = Some(Err(e));
+                        continue;
+                    }
+                    // Nothing is retried once the session is shutting down
+                    Err(e) if shutdown::interrupted() => {
+                        results[index] = Some(Err(e.into_inner()));
+                        continue;
+                    }
+                    Err(AttemptError::Preempted(e))
+                        if preemptions[index] < self.retry.preemption_retries =>
+                    {
+                        preemptions[index] += 1;
+                        (e, true)
+                    }
+                    Err(AttemptError::Failed(e) | AttemptError::Preempted(e)) => (e, false),
+                    Err(AttemptError::ResourcesExhausted(e)) => {
+                        exhausted = true;
+                        (e, false)
+                    }
+                };
+                if !preempted {
+                    failures[index] += 1;
+                    if failures[index] > self.retry.retries {
+                        results[index] = Some(Err(error));
+                        continue;
+                    }
+                }
+                match Process::archive_attempt(&workdir, attempt_number) {
+                    Ok(archive) => {
+                        println!(
+                            "{LP} Array task {} {} on attempt {attempt_number}: {error:?}\n{LP} Logs kept at {}",
+                            processes[index].name,
+                            if preempted { "was preempted" } else { "failed" },
+                            archive.display()
+                        );
+                        only_preempted &= preempted;
+                        failed.push((index, workdir));
+                    }
+                    Err(e) => results[index] = Some(Err(e)),
+                }
+            }
+
+            if !failed.is_empty() {
+                let backoff = if only_preempted {
+                    Duration::ZERO
+                } else {
+                    failed_rounds += 1;
+                    self.retry.backoff(failed_rounds)
+                };
+                println!(
+                    "{LP} Resubmitting {} unsuccessful array tasks in {}s",
+                    failed.len(),
+                    backoff.as_secs_f32()
+                );
+                if exhausted && let Some(escalation) = &self.retry.escalate {
+                    current = current.escalate(escalation);
+                }
+                thread::sleep(backoff);
+            }
+            pending = failed;
+            attempt_number += 1;
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.expect("Every array task should resolve to a result"))
+            .collect()
+    }
+
+    /// Runs each process as its own job, at most `max_concurrent` at once when set
+    fn exe_each(
+        &self,
+        processes: Vec<Process>,
+        max_concurrent: Option<u32>,
+    ) -> Vec<WorkflowResult> {
+        let batch_size = max_concurrent
+            .map_or(processes.len(), |max| max as usize)
+            .max(1);
+        let mut processes = processes.into_iter().peekable();
+        let mut results = Vec::new();
+        while processes.peek().is_some() {
+            let batch: Vec<_> = processes.by_ref().take(batch_size).collect();
+            thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .into_iter()
+                    .map(|process| scope.spawn(|| self.exe(process)))
+                    .collect();
+                results.extend(handles.into_iter().map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(NodeError::msg("Process panicked".to_string())))
+                }));
+            });
+        }
+        results
+    }
+
+    /// Submits the `pending` processes as array jobs and resolves the outcome of every task
+    fn exe_array_attempt(
+        &self,
+        processes: &[Process],
+        pending: &[(usize, PathBuf)],
+        max_concurrent: Option<u32>,
+    ) -> Vec<TaskOutcome> {
+        let mut outcomes: Vec<Option<TaskOutcome>> = pending.iter().map(|_| None).collect();
+        let mut tasks = Vec::with_capacity(pending.len());
+        for (slot, (index, workdir)) in pending.iter().enumerate() {
+            match self.write_launcher(&processes[*index], workdir) {
+                Ok((log_path, log_handle, _)) => tasks.push((slot, log_path, log_handle)),
+                Err(e) => outcomes[slot] = Some(Err(AttemptError::Fatal(e))),
+            }
+        }
+
+        // Slurm rejects task indices from MaxArraySize on, so larger batches span several arrays
+        let chunk_size = *MAX_ARRAY_SIZE;
+        let specs = array_specs(tasks.len(), chunk_size, max_concurrent);
+        let run_chunk = |(chunk, spec): (&mut [(usize, PathBuf, File)], &String)| {
+            let workdirs: Vec<&Path> = chunk
+                .iter()
+                .map(|(slot, _, _)| pending[*slot].1.as_path())
+                .collect();
+            let on_submit = |job_id: &str| {
+                for (task_id, (_, _, log_handle)) in chunk.iter_mut().enumerate() {
+                    let _ = writeln!(
+                        log_handle,
+                        "{LP} Submitted as task {task_id} of array job {job_id}"
+                    );
+                }
+            };
+            self.run_array(&workdirs, spec, on_submit)
+        };
+        let chunk_results: Vec<_> = match max_concurrent {
+            // Arrays run one after another, so that at most max_concurrent tasks run at once
+            Some(_) => tasks
+                .chunks_mut(chunk_size)
+                .zip(&specs)
+                .map(run_chunk)
+                .collect(),
+            None => thread::scope(|scope| {
+                let handles: Vec<_> = tasks
+                    .chunks_mut(chunk_size)
+                    .zip(&specs)
+                    .map(|chunk| scope.spawn(|| run_chunk(chunk)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle.join().unwrap_or_else(|_| {
+                            Err(NodeError::msg("Array submission panicked".to_string()))
+                        })
+                    })
+                    .collect()
+            }),
+        };
+
+        let mut tasks = tasks.into_iter();
+        for chunk_result in chunk_results {
+            let chunk = tasks.by_ref().take(chunk_size);
+            match chunk_result {
+                Ok(mut task_states) => {
+                    for (task_id, (slot, log_path, mut log_handle)) in chunk.enumerate() {
+                        let (index, workdir) = &pending[slot];
+                        let (job_state, job_status) = match task_states.remove(&task_id) {
+                            Some(record) => {
+                                record.log(&mut log_handle);
+                                record.metrics.write(workdir);
+                                (Some(record.state), record.exit)
+                            }
+                            None => (None, None),
+                        };
+                        outcomes[slot] = Some(resolve_job(
+                            &processes[*index],
+                            workdir,
+                            job_state,
+                            job_status,
+                            &mut log_handle,
+                            &log_path,
+                        ));
+                    }
+                }
+                Err(e) => {
+                    for (slot, _, mut log_handle) in chunk {
+                        let _ = writeln!(log_handle, "{LP} Array job failed: {e:?}");
+                        outcomes[slot] = Some(Err(AttemptError::Failed(NodeError::msg(format!(
+                            "Array job failed: {e:?}"
+                        )))));
+                    }
+                }
+            }
+        }
+
+        outcomes
+            .into_iter()
+            .map(|outcome| outcome.expect("Every array task should resolve to an outcome"))
+            .collect()
+    }
+
+    /// Submits one array task per workdir, with the `--array` option `array`,
+    /// and waits for the array to leave the queue
+    ///
+    /// Returns the sacct record of each task, keyed by task id
+    fn run_array<F: FnOnce(&str)>(
+        &self,
+        workdirs: &[&Path],
+        array: &str,
+        on_submit: F,
+    ) -> NodeResult<HashMap<usize, SacctRecord>> {
+        // Process workdirs sit directly in the session workdir
+        let session_dir = workdirs
+            .first()
+            .and_then(|workdir| workdir.parent())
+            .ok_or_else(|| NodeError::msg("Cannot submit an empty array".to_string()))?;
+        let array_dir = create_array_dir(session_dir)?;
+        let log_path = array_dir.join(".maestro.log");
+        let mut log_handle = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&log_path)
+            .map_err(|e| NodeError::msg(format!("Failed to open log file: {e}")))?;
+
+        // Every task enters its own workdir and runs the launcher written there
+        let launcher_path = array_dir.join(".maestro.launcher");
+        let mut launcher_handle = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o755)
+            .open(&launcher_path)
+            .map_err(|e| NodeError::msg(format!("Failed to open launcher file: {e}")))?;
+        let mut launcher = format!("#!/bin/bash\n{}\nworkdirs=(\n", self.config);
+        for workdir in workdirs {
+            launcher.push_str(&format!("  \"{}\"\n", workdir.display()));
+        }
+        launcher.push_str(
+            ")\ncd \"${workdirs[$SLURM_ARRAY_TASK_ID]}\"\nexec ./.maestro.launcher >> .maestro.log 2>> .maestro.err\n",
+        );
+        launcher_handle
+            .write_all(launcher.as_bytes())
+            .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+
+        let mut sbatch = Command::new("sbatch");
+        sbatch
+            .args(["-o", "slurm-%a.out", "-e", "slurm-%a.err", array])
+            .arg(&launcher_path)
+            .current_dir(&array_dir);
+        let job_id =
+            submit(self, sbatch, &mut log_handle, &log_path).map_err(AttemptError::into_inner)?;
+        on_submit(&job_id);
+        await_job(self, &job_id, workdirs, || {
+            let _ = writeln!(log_handle, ":: Array job execution started");
+        });
+        // Tasks that just left the queue may not be recorded as finished by sacct yet
+        let prefix = format!("{job_id}_");
+        let mut lookups = 1;
+        let task_records = loop {
+            let records = sacct(&job_id).map_err(|e| {
+                NodeError::msg(format!("Failed to query sacct for job information: {e}"))
+            })?;
+            let task_records: HashMap<usize, SacctRecord> = records
+                .into_iter()
+                .filter_map(|(id, record)| {
+                    let task_id = id.strip_prefix(&prefix)?.parse().ok()?;
+                    Some((task_id, record))
+                })
+                .collect();
+            let unrecorded = (0..workdirs.len())
+                .filter(|task_id| {
+                    !task_records
+                        .get(task_id)
+                        .is_some_and(|record| record.state.is_finished())
+                })
+                .count();
+            if unrecorded == 0 || lookups == SACCT_LOOKUPS || shutdown::interrupted() {
+                break task_records;
+            }
+            let _ = writeln!(
+                log_handle,
+                "{LP} sacct has yet to record {unrecorded} of {} tasks as finished; querying again",
+                workdirs.len()
+            );
+            thread::sleep(SACCT_RETRY_DELAY);
+            lookups += 1;
+        };
+        for record in task_records.values() {
+            record.log(&mut log_handle);
+        }
+        Ok(task_records)
+    }
+}
+
+/// The number of tasks one array may hold, as `MaxArraySize` in the Slurm configuration
+static MAX_ARRAY_SIZE: LazyLock<usize> = LazyLock::new(|| {
+    Command::new("scontrol")
+        .args(["show", "config"])
+        .output()
+        .ok()
+        .and_then(|output| parse_max_array_size(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or(DEFAULT_MAX_ARRAY_SIZE)
+});
+const DEFAULT_MAX_ARRAY_SIZE: usize = 1001;
+
+/// Reads `MaxArraySize` from the output of `scontrol show config`
+fn parse_max_array_size(config: &str) -> Option<usize> {
+    config.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        if key.trim() != "MaxArraySize" {
+            return None;
+        }
+        value.trim().parse().ok().filter(|&size| size > 0)
+    })
+}
+
+/// The `--array` option of each array `tasks` tasks are split into, at most `max_array_size` per array
+fn array_specs(tasks: usize, max_array_size: usize, max_concurrent: Option<u32>) -> Vec<String> {
+    let max_array_size = max_array_size.max(1);
+    (0..tasks)
+        .step_by(max_array_size)
+        .map(|start| {
+            let len = max_array_size.min(tasks - start);
+            let mut array = format!("--array=0-{}", len - 1);
+            if let Some(limit) = max_concurrent {
+                array.push_str(&format!("%{limit}"));
+            }
+            array
+        })
+        .collect()
+}
+
+/// Creates a fresh directory for an array launcher under `.maestro.arrays/` in `session_dir`
+fn create_array_dir(session_dir: &Path) -> NodeResult<PathBuf> {
+    let arrays_dir = session_dir.join(".maestro.arrays");
+    fs::create_dir_all(&arrays_dir)
+        .map_err(|e| NodeError::msg(format!("Failed to create array directory: {e}")))?;
+    let mut array_number = 1;
+    loop {
+        let candidate = arrays_dir.join(array_number.to_string());
+        match fs::create_dir(&candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => array_number += 1,
+            Err(e) => {
+                return Err(NodeError::msg(format!(
+                    "Failed to create array directory: {e}"
+                )));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_max_array_size_from_slurm_config() {
+        let config = "Configuration data as of 2025-01-01T08:00:00\n\
+            MailProg                = /bin/mail\n\
+            MaxArraySize            = 4001\n\
+            MaxDBDMsgs              = 20008\n";
+        assert_eq!(parse_max_array_size(config), Some(4001));
+        assert_eq!(parse_max_array_size("MaxJobCount = 10000\n"), None);
+    }
+
+    #[test]
+    fn splits_large_batches_into_arrays_within_max_array_size() {
+        assert_eq!(
+            array_specs(2000, DEFAULT_MAX_ARRAY_SIZE, None),
+            ["--array=0-1000", "--array=0-998"]
+        );
+        assert_eq!(
+            array_specs(2000, 500, Some(50)),
+            [
+                "--array=0-499%50",
+                "--array=0-499%50",
+                "--array=0-499%50",
+                "--array=0-499%50"
+            ]
+        );
+        assert_eq!(
+            array_specs(3, DEFAULT_MAX_ARRAY_SIZE, None),
+            ["--array=0-2"]
+        );
+    }
+}
@@ -0,0 +1,929 @@
+use crate::{
+    LP, Process,
+    executors::{
+        monitor::{JobMonitor, JobStatus},
+        retry::AttemptError,
+        slurm::{MemoryConfig, SlurmConfig, SlurmJobState, resolve_job},
+    },
+    metrics::ProcessMetrics,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    os::unix::fs::OpenOptionsExt as _,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, PoisonError},
+};
+
+/// The environment variable slurmrestd tokens are conventionally kept in
+const DEFAULT_TOKEN_VAR: &str = "SLURM_JWT";
+
+/// Connection settings for slurmrestd, through which jobs are submitted,
+/// polled and cancelled instead of the Slurm CLI when set
+#[derive(Clone, Deserialize, Hash, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SlurmRest {
+    /// The base URL of slurmrestd, such as `http://slurmctld:6820`
+    pub url: String,
+    /// The version of the Slurm REST API to use, at least `v0.0.40`
+    #[serde(default = "default_api_version")]
+    pub api_version: String,
+    /// The user to submit as, for tokens that are not tied to a user
+    pub user: Option<String>,
+    #[serde(default)]
+    pub token: SlurmToken,
+}
+fn default_api_version() -> String {
+    "v0.0.40".to_string()
+}
+
+/// Where the JWT authenticating against slurmrestd is read from
+#[derive(Clone, Deserialize, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SlurmToken {
+    Env(String),
+    File(PathBuf),
+}
+impl Default for SlurmToken {
+    fn default() -> Self {
+        Self::Env(DEFAULT_TOKEN_VAR.to_string())
+    }
+}
+
+impl SlurmRest {
+    pub fn new<S: ToString>(url: S) -> Self {
+        Self {
+            url: url.to_string(),
+            api_version: default_api_version(),
+            user: None,
+            token: SlurmToken::default(),
+        }
+    }
+    pub fn with_api_version<S: ToString>(mut self, api_version: S) -> Self {
+        self.api_version = api_version.to_string();
+        self
+    }
+    pub fn with_user<S: ToString>(mut self, user: S) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+    pub fn with_token_env<S: ToString>(mut self, var: S) -> Self {
+        self.token = SlurmToken::Env(var.to_string());
+        self
+    }
+    pub fn with_token_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.token = SlurmToken::File(path.into());
+        self
+    }
+
+    fn read_token(&self) -> io::Result<String> {
+        let token = match &self.token {
+            SlurmToken::Env(var) => env::var(var).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Failed to read ${var}: {e}"),
+                )
+            })?,
+            SlurmToken::File(path) => fs::read_to_string(path).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Failed to read token file {}: {e}", path.display()),
+                )
+            })?,
+        };
+        // Tokens are often stored as the SLURM_JWT=<token> line printed by scontrol token
+        let token = token.trim();
+        Ok(token
+            .strip_prefix(&format!("{DEFAULT_TOKEN_VAR}="))
+            .unwrap_or(token)
+            .to_string())
+    }
+
+    /// A curl command requesting `endpoint` of the Slurm API
+    ///
+    /// The token is handed to curl through a pipe, keeping it out of its arguments
+    fn curl(&self, method: &str, endpoint: &str) -> io::Result<Command> {
+        let mut headers = format!("header = \"X-SLURM-USER-TOKEN: {}\"\n", self.read_token()?);
+        if let Some(user) = &self.user {
+            headers.push_str(&format!("header = \"X-SLURM-USER-NAME: {user}\"\n"));
+        }
+        let (reader, mut writer) = io::pipe()?;
+        writer.write_all(headers.as_bytes())?;
+        drop(writer);
+
+        let mut curl = Command::new("curl");
+        curl.args([
+            "--silent",
+            "--show-error",
+            "--config",
+            "-",
+            "--request",
+            method,
+        ])
+        .arg(format!("{}/{endpoint}", self.url.trim_end_matches('/')))
+        .stdin(reader);
+        Ok(curl)
+    }
+
+    /// Requests `endpoint`, returning its JSON response
+    fn get(&self, endpoint: &str) -> io::Result<Value> {
+        let output = self.curl("GET", endpoint)?.output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "curl exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        serde_json::from_slice(&output.stdout).map_err(|e| {
+            io::Error::other(format!(
+                "Failed to parse slurmrestd response: {e}: {}",
+                String::from_utf8_lossy(&output.stdout).trim()
+            ))
+        })
+    }
+
+    /// Writes the job description of `process` into `workdir`
+    /// and prepares the curl command submitting it
+    pub(crate) fn submit_command(
+        &self,
+        config: &SlurmConfig,
+        process: &Process,
+        workdir: &Path,
+        launcher_path: &Path,
+    ) -> NodeResult<Command> {
+        let script = fs::read_to_string(launcher_path)
+            .map_err(|e| NodeError::msg(format!("Failed to read launcher: {e}")))?;
+        let mut job = config.job_description();
+        job.insert("name".to_string(), json!(process.name));
+        job.insert(
+            "current_working_directory".to_string(),
+            json!(workdir.display().to_string()),
+        );
+        job.insert(
+            "standard_output".to_string(),
+            json!(workdir.join(".maestro.log").display().to_string()),
+        );
+        job.insert(
+            "standard_error".to_string(),
+            json!(workdir.join(".maestro.err").display().to_string()),
+        );
+        job.insert("open_mode".to_string(), json!(["APPEND"]));
+        // Like sbatch, jobs inherit the environment they are submitted from, except for the token
+        let token_var = match &self.token {
+            SlurmToken::Env(var) => var.as_str(),
+            SlurmToken::File(_) => DEFAULT_TOKEN_VAR,
+        };
+        let environment: Vec<String> = env::vars()
+            .filter(|(var, _)| var != token_var && var != DEFAULT_TOKEN_VAR)
+            .map(|(var, value)| format!("{var}={value}"))
+            .collect();
+        job.insert("environment".to_string(), json!(environment));
+
+        // The description holds the environment, so it is kept private
+        let description_path = workdir.join(".maestro.job.json");
+        let mut description = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&description_path)
+            .map_err(|e| NodeError::msg(format!("Failed to open job description: {e}")))?;
+        description
+            .write_all(
+                json!({ "script": script, "job": job })
+                    .to_string()
+                    .as_bytes(),
+            )
+            .map_err(|e| NodeError::msg(format!("Failed to write job description: {e}")))?;
+
+        let mut curl = self
+            .curl("POST", &format!("slurm/{}/job/submit", self.api_version))
+            .map_err(|e| NodeError::msg(format!("Failed to prepare slurmrestd request: {e}")))?;
+        curl.args([
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+        ])
+        .arg(format!("@{}", description_path.display()))
+        .current_dir(workdir);
+        Ok(curl)
+    }
+
+    pub(crate) fn cancel(&self, job_id: &str) {
+        if let Ok(mut curl) = self.curl(
+            "DELETE",
+            &format!("slurm/{}/job/{job_id}", self.api_version),
+        ) {
+            let _ = curl.output();
+        }
+    }
+
+    /// The monitor shared by all Slurm executors using the same slurmrestd settings
+    pub(crate) fn monitor(&self) -> &'static JobMonitor {
+        static MONITORS: Mutex<Vec<(SlurmRest, &'static JobMonitor)>> = Mutex::new(Vec::new());
+        let mut monitors = MONITORS.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, monitor)) = monitors.iter().find(|(rest, _)| rest == self) {
+            return monitor;
+        }
+
+        let rest = self.clone();
+        let snapshot = Mutex::new(JobsSnapshot::default());
+        let monitor: &'static JobMonitor = Box::leak(Box::new(JobMonitor::new(
+            "slurmrestd",
+            move |job_ids: &[String]| {
+                let mut snapshot = snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+                rest.query_jobs(job_ids, &mut snapshot)
+            },
+        )));
+        monitors.push((self.clone(), monitor));
+        monitor
+    }
+
+    /// Queries the states of `job_ids` with a single request;
+    /// jobs absent from the returned map have left the queue
+    ///
+    /// Once every job has been seen, only the jobs updated since the previous query are listed
+    fn query_jobs(
+        &self,
+        job_ids: &[String],
+        snapshot: &mut JobsSnapshot,
+    ) -> io::Result<HashMap<String, JobStatus>> {
+        // Jobs not seen yet may have last changed before the previous query
+        let since = snapshot
+            .last_update
+            .filter(|_| job_ids.iter().all(|id| snapshot.states.contains_key(id)));
+        let endpoint = match since {
+            Some(since) => format!("slurm/{}/jobs?update_time={since}", self.api_version),
+            None => format!("slurm/{}/jobs", self.api_version),
+        };
+        let response = self.get(&endpoint)?;
+        let jobs = response["jobs"].as_array().ok_or_else(|| {
+            io::Error::other(format!(
+                "slurmrestd returned no jobs: {}",
+                errors(&response)
+            ))
+        })?;
+
+        if since.is_none() {
+            snapshot.states.clear();
+        }
+        snapshot.states.retain(|id, _| job_ids.contains(id));
+        for job in jobs {
+            let Some(id) = job["job_id"].as_u64().map(|id| id.to_string()) else {
+                continue;
+            };
+            if !job_ids.contains(&id) {
+                continue;
+            }
+            match job_status(job) {
+                Some(status) => snapshot.states.insert(id, status),
+                None => snapshot.states.remove(&id),
+            };
+        }
+        snapshot.last_update = optional_number(&response["last_update"]);
+        Ok(snapshot.states.clone())
+    }
+
+    /// Looks up the final state of a job that left the queue and resolves its outcome
+    ///
+    /// Jobs purged from the controller are looked up in the accounting database
+    pub(crate) fn collect_job(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        job_id: &str,
+        log_handle: &mut File,
+        log_path: &Path,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        let query = |endpoint: String, state: &str| -> io::Result<Option<(Vec<String>, Value)>> {
+            let response = self.get(&endpoint)?;
+            Ok(response["jobs"]
+                .as_array()
+                .and_then(|jobs| jobs.first())
+                .map(|job| (state_flags(&job[state]), job.clone())))
+        };
+        let job = query(
+            format!("slurm/{}/job/{job_id}", self.api_version),
+            "job_state",
+        )
+        .and_then(|job| match job {
+            Some(job) => Ok(Some(job)),
+            None => query(
+                format!("slurmdb/{}/job/{job_id}", self.api_version),
+                "state",
+            ),
+        })
+        .map_err(|e| {
+            NodeError::msg(format!(
+                "Failed to query slurmrestd for job information: {e}"
+            ))
+        })?;
+        let Some((flags, job)) = job else {
+            let _ = writeln!(log_handle, "{LP} Job {job_id} is unknown to slurmrestd");
+            return resolve_job(process, workdir, None, None, log_handle, log_path);
+        };
+
+        let job_status = parse_exit_code(&job["exit_code"]);
+        let _ = writeln!(
+            log_handle,
+            "{LP} Job information\nJobId: {job_id}\nState: {}\nExitCode: {}",
+            flags.join(","),
+            job_status
+                .map(|(code, signal)| format!("{code}:{signal}"))
+                .unwrap_or("unknown".to_string())
+        );
+        job_metrics(&job, job_status).write(workdir);
+        resolve_job(
+            process,
+            workdir,
+            flags.first().map(|state| SlurmJobState::parse(state)),
+            job_status,
+            log_handle,
+            log_path,
+        )
+    }
+}
+
+impl SlurmConfig {
+    /// Translates the configuration into the fields of a slurmrestd job description
+    ///
+    /// Additional options are passed on as fields named after their flag,
+    /// with dashes replaced by underscores
+    pub(crate) fn job_description(&self) -> Map<String, Value> {
+        let number = |number: u64| json!({ "set": true, "infinite": false, "number": number });
+        let mut job = Map::new();
+        if let Some(cpus) = self.cpus {
+            job.insert("cpus_per_task".to_string(), json!(cpus));
+        }
+        if let Some(gpus) = self.gpus {
+            job.insert(
+                "tres_per_job".to_string(),
+                json!(format!("gres/gpu:{gpus}")),
+            );
+        }
+        match self.memory {
+            Some(MemoryConfig::PerCpu(memory)) => {
+                job.insert("memory_per_cpu".to_string(), number(memory.as_mb()));
+            }
+            Some(MemoryConfig::PerNode(memory)) => {
+                job.insert("memory_per_node".to_string(), number(memory.as_mb()));
+            }
+            None => {}
+        }
+        if let Some(tasks) = self.tasks {
+            job.insert("tasks".to_string(), json!(tasks));
+        }
+        if let Some(nodes) = self.nodes {
+            job.insert("nodes".to_string(), json!(nodes.to_string()));
+        }
+        if let Some(partition) = &self.partition {
+            job.insert("partition".to_string(), json!(partition));
+        }
+        if let Some(time) = self.time {
+            // Time limits are given in minutes
+            job.insert(
+                "time_limit".to_string(),
+                number(time.as_secs().div_ceil(60)),
+            );
+        }
+        if let Some(account) = &self.account {
+            job.insert("account".to_string(), json!(account));
+        }
+        if let Some(mail_user) = &self.mail_user {
+            job.insert("mail_user".to_string(), json!(mail_user));
+        }
+        if let Some(mail_type) = &self.mail_type {
+            let flags: Vec<String> = mail_type.0.iter().map(ToString::to_string).collect();
+            job.insert("mail_type".to_string(), json!(flags));
+        }
+        if let Some(constraint) = &self.constraint {
+            job.insert("constraints".to_string(), json!(constraint));
+        }
+        if let Some(qos) = &self.qos {
+            job.insert("qos".to_string(), json!(qos));
+        }
+        if self.exclusive == Some(true) {
+            job.insert("shared".to_string(), json!(["none"]));
+        }
+        if let Some(gres) = &self.gres {
+            let resources: Vec<String> = gres
+                .split(',')
+                .map(|resource| format!("gres/{resource}"))
+                .collect();
+            job.insert("tres_per_node".to_string(), json!(resources.join(",")));
+        }
+        if let Some(reservation) = &self.reservation {
+            job.insert("reservation".to_string(), json!(reservation));
+        }
+        if let Some(exclude) = &self.exclude {
+            job.insert("excluded_nodes".to_string(), json!(exclude));
+        }
+        if let Some(begin) = &self.begin {
+            // slurmrestd expects a Unix timestamp; other forms are left for it to judge
+            let begin = match begin.parse::<u64>() {
+                Ok(timestamp) => number(timestamp),
+                Err(_) => json!(begin),
+            };
+            job.insert("begin_time".to_string(), begin);
+        }
+        if let Some(dependency) = &self.dependency {
+            job.insert("dependency".to_string(), json!(dependency));
+        }
+        if let Some(nice) = self.nice {
+            job.insert("nice".to_string(), json!(nice));
+        }
+        if let Some(requeue) = self.requeue {
+            job.insert("requeue".to_string(), json!(requeue));
+        }
+        for (flag, value) in &self.additional_options {
+            job.insert(flag.replace('-', "_"), json!(value));
+        }
+        job
+    }
+}
+
+/// What slurmrestd last reported about the monitored jobs
+#[derive(Default)]
+struct JobsSnapshot {
+    /// When the controller last updated its jobs, as of the previous query
+    last_update: Option<u64>,
+    /// The monitored jobs still in the queue
+    states: HashMap<String, JobStatus>,
+}
+
+/// The status of a job listed by slurmrestd, or `None` if it has finished
+fn job_status(job: &Value) -> Option<JobStatus> {
+    let flags = state_flags(&job["job_state"]);
+    let state = flags.first().map(|state| SlurmJobState::parse(state));
+    Some(match state {
+        _ if flags.iter().any(|flag| flag == "COMPLETING") => JobStatus::Running,
+        // Requeued jobs keep their id and return to pending
+        _ if flags.iter().any(|flag| flag.starts_with("REQUEUE")) => JobStatus::Pending,
+        Some(SlurmJobState::Pending) if job["state_reason"] == "DependencyNeverSatisfied" => {
+            JobStatus::DependencyNeverSatisfied
+        }
+        Some(SlurmJobState::Pending) => JobStatus::Pending,
+        Some(state) if state.is_finished() => return None,
+        _ => JobStatus::Running,
+    })
+}
+
+/// Parses the job id out of a slurmrestd submission response
+pub(crate) fn parse_job_id(stdout: &str) -> Option<String> {
+    let response: Value = serde_json::from_str(stdout).ok()?;
+    response["job_id"].as_u64().map(|id| id.to_string())
+}
+
+/// The state of a job, reported as a list of flags led by its base state
+fn state_flags(state: &Value) -> Vec<String> {
+    match state {
+        Value::String(state) => vec![state.clone()],
+        Value::Array(flags) => flags
+            .iter()
+            .filter_map(|flag| flag.as_str().map(str::to_string))
+            .collect(),
+        Value::Object(state) => state.get("current").map(state_flags).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Extracts the timing of a job, as reported by either the controller or the accounting database
+///
+/// slurmrestd does not report memory usage outside of job steps
+fn job_metrics(job: &Value, job_status: Option<(u32, u32)>) -> ProcessMetrics {
+    let time = |controller: &str, accounting: &str| {
+        optional_number(&job[controller]).or_else(|| optional_number(&job["time"][accounting]))
+    };
+    let (submit_time, start_time, end_time) = (
+        time("submit_time", "submission"),
+        time("start_time", "start"),
+        time("end_time", "end"),
+    );
+    let cpu_time_secs = job["time"]["total"].as_object().map(|total| {
+        let part = |key: &str| total.get(key).and_then(Value::as_f64).unwrap_or_default();
+        part("seconds") + part("microseconds") / 1e6
+    });
+    let mut metrics = ProcessMetrics {
+        wall_time_secs: start_time
+            .zip(end_time)
+            .filter(|(start, end)| *start > 0 && end >= start)
+            .map(|(start, end)| (end - start) as f64),
+        cpu_time_secs,
+        node: job["nodes"]
+            .as_str()
+            .filter(|nodes| !nodes.is_empty())
+            .map(str::to_string),
+        submit_time,
+        start_time,
+        end_time,
+        ..Default::default()
+    };
+    if let Some(status) = job_status {
+        metrics.set_exit_status(status);
+    }
+    metrics
+}
+
+/// Reads a number reported either plainly or as an object that may be unset
+fn optional_number(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(number) => number.as_u64(),
+        Value::Object(_) if value["set"] == true => value["number"].as_u64(),
+        _ => None,
+    }
+}
+
+/// Parses an exit code into its code and signal,
+/// reported either as a plain number or as an object with optional numbers
+fn parse_exit_code(exit_code: &Value) -> Option<(u32, u32)> {
+    match exit_code {
+        Value::Number(number) => number.as_u64().map(|code| (code as u32, 0)),
+        Value::Object(_) => Some((
+            optional_number(&exit_code["return_code"])? as u32,
+            optional_number(&exit_code["signal"]["id"]).unwrap_or(0) as u32,
+        )),
+        _ => None,
+    }
+}
+
+/// The errors reported in a slurmrestd response
+fn errors(response: &Value) -> String {
+    response["errors"]
+        .as_array()
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|error| error["description"].as_str().or(error["error"].as_str()))
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::slurm::{Memory, SlurmTime};
+    use std::{
+        io::{BufRead as _, BufReader, Read as _},
+        net::TcpListener,
+        process,
+        sync::Arc,
+        thread,
+    };
+
+    /// A request received by [`MockSlurmrestd`]
+    struct Request {
+        method: String,
+        path: String,
+        headers: Vec<String>,
+        body: String,
+    }
+
+    /// A slurmrestd stand-in answering each request with the JSON chosen by its handler
+    struct MockSlurmrestd {
+        url: String,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+    impl MockSlurmrestd {
+        fn start(handler: impl Fn(&str, &str) -> Value + Send + 'static) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let received = requests.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else { continue };
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let mut parts = line.split_whitespace();
+                    let method = parts.next().unwrap_or_default().to_string();
+                    let path = parts.next().unwrap_or_default().to_string();
+
+                    let mut headers = Vec::new();
+                    let mut length = 0;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        let header = header.trim_end();
+                        if header.is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = header.split_once(':')
+                            && name.eq_ignore_ascii_case("content-length")
+                        {
+                            length = value.trim().parse().unwrap();
+                        }
+                        headers.push(header.to_string());
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    let response = handler(&method, &path).to_string();
+                    received.lock().unwrap().push(Request {
+                        method,
+                        path,
+                        headers,
+                        body: String::from_utf8(body).unwrap(),
+                    });
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                        response.len()
+                    );
+                }
+            });
+            Self { url, requests }
+        }
+
+        fn paths(&self) -> Vec<String> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .iter()
+                .map(|request| request.path.clone())
+                .collect()
+        }
+    }
+
+    /// An empty directory unique to `test`
+    fn test_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("maestro-rest-{}-{test}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rest(mock: &MockSlurmrestd, dir: &Path) -> SlurmRest {
+        let token_path = dir.join("token");
+        fs::write(&token_path, "SLURM_JWT=secret\n").unwrap();
+        SlurmRest::new(&mock.url)
+            .with_user("maestro")
+            .with_token_file(token_path)
+    }
+
+    fn process() -> Process {
+        Process::new(
+            "rest".to_string(),
+            vec![],
+            vec![],
+            vec![],
+            "echo hello".into(),
+        )
+    }
+
+    #[test]
+    fn submits_job_description_and_parses_job_id() {
+        let mock = MockSlurmrestd::start(|_, _| json!({ "job_id": 4242, "errors": [] }));
+        let dir = test_dir("submit");
+        let launcher = dir.join(".maestro.sh");
+        fs::write(&launcher, "#!/bin/bash\necho hello\n").unwrap();
+
+        let config = SlurmConfig::default().with_cpus(2);
+        let output = rest(&mock, &dir)
+            .submit_command(&config, &process(), &dir, &launcher)
+            .unwrap()
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            parse_job_id(&String::from_utf8_lossy(&output.stdout)),
+            Some("4242".to_string())
+        );
+
+        let requests = mock.requests.lock().unwrap();
+        let [request] = requests.as_slice() else {
+            panic!("expected a single request");
+        };
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/slurm/v0.0.40/job/submit");
+        assert!(
+            request
+                .headers
+                .contains(&"X-SLURM-USER-TOKEN: secret".to_string())
+        );
+        assert!(
+            request
+                .headers
+                .contains(&"X-SLURM-USER-NAME: maestro".to_string())
+        );
+        let body: Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["script"], "#!/bin/bash\necho hello\n");
+        assert_eq!(body["job"]["name"], "rest");
+        assert_eq!(body["job"]["cpus_per_task"], 2);
+        assert_eq!(
+            body["job"]["current_working_directory"],
+            dir.display().to_string()
+        );
+        let environment = body["job"]["environment"].as_array().unwrap();
+        assert!(
+            environment
+                .iter()
+                .all(|var| !var.as_str().unwrap().starts_with("SLURM_JWT="))
+        );
+    }
+
+    #[test]
+    fn parse_job_id_rejects_failed_submissions() {
+        assert_eq!(parse_job_id(r#"{"job_id": 17}"#), Some("17".to_string()));
+        assert_eq!(
+            parse_job_id(r#"{"errors": [{"error": "Invalid partition"}]}"#),
+            None
+        );
+        assert_eq!(parse_job_id("not json"), None);
+    }
+
+    #[test]
+    fn query_jobs_maps_states_and_lists_only_updates() {
+        let mock = MockSlurmrestd::start(|_, path| {
+            if path.contains("update_time=") {
+                return json!({ "jobs": [], "last_update": { "set": true, "number": 1700000060 } });
+            }
+            json!({
+                "jobs": [
+                    { "job_id": 1, "job_state": ["PENDING"], "state_reason": "Priority" },
+                    { "job_id": 2, "job_state": ["RUNNING"] },
+                    { "job_id": 3, "job_state": ["COMPLETED", "COMPLETING"] },
+                    { "job_id": 4, "job_state": ["PENDING", "REQUEUED"] },
+                    { "job_id": 5, "job_state": ["FAILED", "REQUEUE_HOLD"] },
+                    { "job_id": 6, "job_state": ["PENDING"], "state_reason": "DependencyNeverSatisfied" },
+                    { "job_id": 7, "job_state": ["COMPLETED"] },
+                    { "job_id": 8, "job_state": ["RUNNING"] },
+                ],
+                "last_update": { "set": true, "number": 1700000000 },
+            })
+        });
+        let dir = test_dir("query");
+        let rest = rest(&mock, &dir);
+        let mut snapshot = JobsSnapshot::default();
+
+        let tracked: Vec<String> = (1..=7).map(|id| id.to_string()).collect();
+        let states = rest.query_jobs(&tracked, &mut snapshot).unwrap();
+        let expected = HashMap::from([
+            ("1".to_string(), JobStatus::Pending),
+            ("2".to_string(), JobStatus::Running),
+            ("3".to_string(), JobStatus::Running),
+            ("4".to_string(), JobStatus::Pending),
+            ("5".to_string(), JobStatus::Pending),
+            ("6".to_string(), JobStatus::DependencyNeverSatisfied),
+        ]);
+        assert_eq!(states, expected);
+
+        // Jobs that were all seen before are only looked up among the updated ones
+        let tracked: Vec<String> = (1..=6).map(|id| id.to_string()).collect();
+        assert_eq!(rest.query_jobs(&tracked, &mut snapshot).unwrap(), expected);
+        // A newly tracked job requires a full listing
+        let tracked = ["2".to_string(), "9".to_string()];
+        let states = rest.query_jobs(&tracked, &mut snapshot).unwrap();
+        assert_eq!(
+            states,
+            HashMap::from([("2".to_string(), JobStatus::Running)])
+        );
+
+        assert_eq!(
+            mock.paths(),
+            [
+                "/slurm/v0.0.40/jobs",
+                "/slurm/v0.0.40/jobs?update_time=1700000000",
+                "/slurm/v0.0.40/jobs",
+            ]
+        );
+    }
+
+    #[test]
+    fn query_jobs_reports_missing_jobs_list() {
+        let mock = MockSlurmrestd::start(
+            |_, _| json!({ "errors": [{ "error": "Authentication failure" }] }),
+        );
+        let dir = test_dir("query-error");
+        let error = rest(&mock, &dir)
+            .query_jobs(&["1".to_string()], &mut JobsSnapshot::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("Authentication failure"));
+    }
+
+    /// A mock knowing job 42 only through the accounting database, in `state`
+    fn accounting_only(state: &'static str) -> MockSlurmrestd {
+        MockSlurmrestd::start(move |_, path| {
+            if path.starts_with("/slurmdb/") {
+                json!({
+                    "jobs": [{
+                        "job_id": 42,
+                        "state": { "current": [state], "reason": "None" },
+                        "exit_code": {
+                            "status": ["SUCCESS"],
+                            "return_code": { "set": true, "infinite": false, "number": 0 },
+                            "signal": { "id": { "set": false, "infinite": false, "number": 0 } },
+                        },
+                        "nodes": "node01",
+                        "time": { "submission": 90, "start": 100, "end": 160 },
+                    }]
+                })
+            } else {
+                json!({ "jobs": [], "errors": [{ "error": "Invalid job id specified" }] })
+            }
+        })
+    }
+
+    fn collect(mock: &MockSlurmrestd, test: &str) -> (PathBuf, Result<Vec<PathBuf>, AttemptError>) {
+        let dir = test_dir(test);
+        let log_path = dir.join(".maestro.log");
+        let mut log_handle = File::create(&log_path).unwrap();
+        let outcome =
+            rest(mock, &dir).collect_job(&process(), &dir, "42", &mut log_handle, &log_path);
+        (dir, outcome)
+    }
+
+    #[test]
+    fn collect_job_falls_back_to_accounting() {
+        let mock = accounting_only("COMPLETED");
+        let (dir, outcome) = collect(&mock, "collect");
+        assert!(matches!(outcome, Ok(outputs) if outputs == [dir.clone()]));
+        assert_eq!(
+            mock.paths(),
+            ["/slurm/v0.0.40/job/42", "/slurmdb/v0.0.40/job/42"]
+        );
+        let metrics = fs::read_to_string(dir.join(".maestro.metrics.json")).unwrap();
+        let metrics: Value = serde_json::from_str(&metrics).unwrap();
+        assert_eq!(metrics["wall_time_secs"], 60.0);
+        assert_eq!(metrics["node"], "node01");
+    }
+
+    #[test]
+    fn collect_job_resolves_accounting_state() {
+        let mock = accounting_only("OUT_OF_MEMORY");
+        assert!(matches!(
+            collect(&mock, "collect-oom").1,
+            Err(AttemptError::ResourcesExhausted(_))
+        ));
+    }
+
+    #[test]
+    fn parses_exit_code_as_number_or_object() {
+        assert_eq!(parse_exit_code(&json!(0)), Some((0, 0)));
+        assert_eq!(parse_exit_code(&json!(3)), Some((3, 0)));
+        assert_eq!(
+            parse_exit_code(&json!({
+                "status": ["SIGNALED"],
+                "return_code": { "set": true, "infinite": false, "number": 0 },
+                "signal": { "id": { "set": true, "infinite": false, "number": 9 }, "name": "KILL" },
+            })),
+            Some((0, 9))
+        );
+        assert_eq!(
+            parse_exit_code(&json!({
+                "status": ["ERROR"],
+                "return_code": { "set": true, "infinite": false, "number": 1 },
+            })),
+            Some((1, 0))
+        );
+        assert_eq!(
+            parse_exit_code(&json!({
+                "status": ["PENDING"],
+                "return_code": { "set": false, "infinite": false, "number": 0 },
+            })),
+            None
+        );
+        assert_eq!(parse_exit_code(&Value::Null), None);
+    }
+
+    #[test]
+    fn job_description_translates_config() {
+        let config = SlurmConfig::default()
+            .with_cpus(4)
+            .with_memory(MemoryConfig::PerCpu(Memory::from_mb(2000)))
+            .with_gpus(1)
+            .with_partition("gpu")
+            .with_time(SlurmTime::new(0, 1, 30, 1).unwrap())
+            .with_exclusive(true)
+            .with_gres("gpu:a100:2,shard:1")
+            .with_begin("1700000000")
+            .with_arg("cpus-per-gpu", "2");
+        let job = Value::Object(config.job_description());
+        assert_eq!(
+            job,
+            json!({
+                "cpus_per_task": 4,
+                "memory_per_cpu": { "set": true, "infinite": false, "number": 2000 },
+                "tres_per_job": "gres/gpu:1",
+                "partition": "gpu",
+                "time_limit": { "set": true, "infinite": false, "number": 91 },
+                "shared": ["none"],
+                "tres_per_node": "gres/gpu:a100:2,gres/shard:1",
+                "begin_time": { "set": true, "infinite": false, "number": 1700000000u64 },
+                "cpus_per_gpu": "2",
+            })
+        );
+
+        let begin = SlurmConfig::default()
+            .with_begin("now+1hour")
+            .job_description();
+        assert_eq!(begin["begin_time"], "now+1hour");
+    }
+}
@@ -0,0 +1,556 @@
+use crate::{
+    Container, LP, Process,
+    executors::{
+        Executor, Reusable,
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+    },
+    process::{CheckTime, StagingMode},
+    shutdown::InFlight,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::Deserialize;
+use std::{
+    env,
+    hash::{Hash, Hasher},
+    io::Write as _,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    sync::{Mutex, PoisonError},
+};
+
+/// An executor that runs processes on a remote host over SSH
+///
+/// Inputs are uploaded into the remote workdir of each process and
+/// declared outputs are copied back once it finishes
+#[derive(Clone, Deserialize, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct SshExecutor {
+    pub(crate) host: String,
+    pub(crate) user: Option<String>,
+    pub(crate) port: Option<u16>,
+    pub(crate) identity_file: Option<PathBuf>,
+    #[serde(default = "default_remote_workdir")]
+    pub(crate) remote_workdir: PathBuf,
+    pub(crate) container: Option<Container>,
+    #[serde(default)]
+    pub(crate) staging_mode: StagingMode,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
+}
+fn default_remote_workdir() -> PathBuf {
+    PathBuf::from("maestro_work")
+}
+
+impl SshExecutor {
+    /// Initializes an executor for `host`, using the remote workdir `maestro_work`
+    /// in the home directory of the remote user
+    pub fn new<S: ToString>(host: S) -> Self {
+        Self {
+            host: host.to_string(),
+            user: None,
+            port: None,
+            identity_file: None,
+            remote_workdir: default_remote_workdir(),
+            container: None,
+            staging_mode: StagingMode::default(),
+            cache: false,
+            retry: RetryPolicy::default(),
+        }
+    }
+    pub fn with_user<S: ToString>(mut self, user: S) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+    pub fn with_identity_file<P: Into<PathBuf>>(mut self, identity_file: P) -> Self {
+        self.identity_file = Some(identity_file.into());
+        self
+    }
+    /// Sets the directory sessions are run in on the remote host;
+    /// relative paths are resolved against the home directory of the remote user
+    pub fn with_remote_workdir<P: Into<PathBuf>>(mut self, remote_workdir: P) -> Self {
+        self.remote_workdir = remote_workdir.into();
+        self
+    }
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+    pub fn with_staging_mode(mut self, staging_mode: StagingMode) -> Self {
+        self.staging_mode = staging_mode;
+        self
+    }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
+}
+
+impl Escalate for SshExecutor {
+    fn escalate(&self, _escalation: &Escalation) -> Self {
+        // Remote processes run without resource requests
+        self.clone()
+    }
+}
+
+/// The file in the remote workdir holding the process id of the launcher
+const PID_FILE: &str = ".maestro.pid";
+
+/// Master connections opened during the session, as ssh invocations that can address them
+static MASTERS: Mutex<Vec<(String, Vec<String>)>> = Mutex::new(Vec::new());
+
+/// Closes the master connections opened during the session
+pub(crate) fn close_connections() {
+    let mut masters = MASTERS.lock().unwrap_or_else(PoisonError::into_inner);
+    for (destination, options) in masters.drain(..) {
+        let _ = Command::new("ssh")
+            .args(&options)
+            .args(["-O", "exit"])
+            .arg(&destination)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+impl Executor for SshExecutor {
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        run_reusable(self, process, |process| {
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir)
+            })
+        })
+    }
+}
+
+impl Reusable for SshExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        ("Ssh", &self.container).hash(state);
+    }
+}
+
+impl SshExecutor {
+    fn exe_attempt(&self, process: &Process, workdir: &Path) -> Result<Vec<PathBuf>, AttemptError> {
+        self.connect()?;
+        let remote_dir = self.remote_dir(process)?;
+        let uploads_dir = remote_dir.join(".maestro.uploads");
+
+        let ((log_path, mut log_handle), (_, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
+        process.check_files(CheckTime::Input, None)?;
+        let staging_mode = match self.container {
+            None => &self.staging_mode,
+            Some(_) => &StagingMode::Copy,
+        };
+        // Inputs are staged from their uploaded copies on the remote host,
+        // addressed through the working directory of the launcher
+        let remote_uploads = Path::new("$PWD/.maestro.uploads");
+        process.stage_inputs_from(
+            &mut launcher_handle,
+            &remote_dir,
+            staging_mode,
+            |n, file| {
+                let file_name = file.file_name().unwrap_or(file.as_os_str());
+                Ok(remote_uploads.join(n.to_string()).join(file_name))
+            },
+        )?;
+        Process::write_execution(launcher_handle, process, &self.container)?;
+
+        let destination = self.destination();
+        let _ = writeln!(
+            log_handle,
+            "{LP} Uploading process {} to {destination}:{}",
+            process.name,
+            remote_dir.display()
+        );
+        self.upload(
+            workdir,
+            &[".maestro.sh", ".maestro.launcher"],
+            &remote_dir,
+            &format!("rm -rf {0} && mkdir -p {0}", quote(&remote_dir)),
+        )
+        .map_err(|e| AttemptError::Failed(NodeError::msg(e)))?;
+        for (n, (_, file)) in process.inputs.iter().enumerate() {
+            let file = file.canonicalize()?;
+            let (Some(parent), Some(file_name)) = (file.parent(), file.file_name()) else {
+                return Err(AttemptError::Fatal(NodeError::msg(format!(
+                    "Could not resolve file name of input {}",
+                    file.display()
+                ))));
+            };
+            let upload_dir = uploads_dir.join(n.to_string());
+            self.upload(
+                parent,
+                &[&file_name.to_string_lossy()],
+                &upload_dir,
+                &format!("mkdir -p {}", quote(&upload_dir)),
+            )
+            .map_err(|e| {
+                AttemptError::Failed(NodeError::msg(format!(
+                    "Failed to upload input {}: {e}",
+                    file.display()
+                )))
+            })?;
+        }
+
+        // sshd starts every command in a session of its own, so the launcher replacing the remote
+        // shell leads a process group, which is killed if the session shuts down
+        let _in_flight = InFlight::job(vec![workdir.to_path_buf()], {
+            let executor = self.clone();
+            let remote_dir = remote_dir.clone();
+            move || executor.kill_launcher(&remote_dir)
+        });
+        // The launcher output is streamed into the local log as the process runs
+        let status = self
+            .ssh()
+            .arg(&destination)
+            .arg(format!(
+                "cd {} && echo $$ > {PID_FILE} && exec ./.maestro.launcher",
+                quote(&remote_dir)
+            ))
+            .stdin(Stdio::null())
+            .stdout(log_handle.try_clone()?)
+            .stderr(log_handle.try_clone()?)
+            .status()
+            .map_err(|e| NodeError::msg(format!("Failed to spawn ssh: {e}")))?;
+
+        let mut files = vec![".maestro.out".to_string(), ".maestro.err".to_string()];
+        files.extend(
+            process
+                .outputs
+                .iter()
+                .map(|(_, output)| output.to_string_lossy().into_owned()),
+        );
+        if let Err(e) = self.download(&remote_dir, &files, workdir) {
+            let _ = writeln!(log_handle, "{LP} Failed to copy back outputs: {e}");
+        }
+
+        match status.code() {
+            Some(0) => {
+                let _ = writeln!(
+                    log_handle,
+                    ":: Process terminated successfully with exit code 0"
+                );
+            }
+            // Reserved by ssh for its own errors
+            Some(255) | None => {
+                let _ = writeln!(log_handle, "{LP} Lost connection to {destination}");
+                return Err(AttemptError::Failed(NodeError::msg(format!(
+                    "Lost connection to {destination}. Logs at {}",
+                    log_path.display()
+                ))));
+            }
+            Some(exit_code) => {
+                let _ = writeln!(
+                    log_handle,
+                    "{LP} Process failed!\nExit code: {exit_code}\nstderr at .maestro.err"
+                );
+                return Err(AttemptError::Failed(NodeError::msg(format!(
+                    "Remote process exited with non-zero exit code. Logs at {}; stderr at {}",
+                    log_path.display(),
+                    workdir.join(".maestro.err").display()
+                ))));
+            }
+        }
+
+        process
+            .check_files(CheckTime::Output, Some(workdir))
+            .map_err(AttemptError::Failed)?;
+        Ok(process.output_paths(workdir.to_path_buf()))
+    }
+
+    /// Terminates the process group of the launcher running in `remote_dir`
+    fn kill_launcher(&self, remote_dir: &Path) {
+        let _ = self
+            .ssh()
+            .arg(self.destination())
+            .arg(format!(
+                "kill -TERM -- -\"$(cat {}/{PID_FILE})\"",
+                quote(remote_dir)
+            ))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+
+    /// The remote workdir of `process`, mirroring its local session layout
+    fn remote_dir(&self, process: &Process) -> NodeResult<PathBuf> {
+        let session_dir = &process.session()?.path;
+        let session_id = session_dir.file_name().unwrap_or(session_dir.as_os_str());
+        Ok(self.remote_workdir.join(session_id).join(&process.name))
+    }
+
+    fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{user}@{}", self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    /// The options shared by every ssh invocation towards this host
+    fn options(&self) -> Vec<String> {
+        // %C hashes the host, port and user, so each host gets its own master connection
+        let control_path = env::temp_dir().join(format!("maestro-{}-%C", process::id()));
+        let mut options = vec![
+            "-o".to_string(),
+            "BatchMode=yes".to_string(),
+            "-o".to_string(),
+            format!("ControlPath={}", control_path.display()),
+        ];
+        if let Some(port) = self.port {
+            options.extend(["-o".to_string(), format!("Port={port}")]);
+        }
+        if let Some(identity_file) = &self.identity_file {
+            options.extend([
+                "-o".to_string(),
+                format!("IdentityFile={}", identity_file.display()),
+                "-o".to_string(),
+                "IdentitiesOnly=yes".to_string(),
+            ]);
+        }
+        options
+    }
+
+    fn ssh(&self) -> Command {
+        let mut command = Command::new("ssh");
+        command.args(self.options());
+        command
+    }
+
+    /// Opens the master connection to this host, unless one is already open
+    fn connect(&self) -> NodeResult<()> {
+        let destination = self.destination();
+        let options = self.options();
+        let mut masters = MASTERS.lock().unwrap_or_else(PoisonError::into_inner);
+        if masters.iter().any(|(known_destination, known_options)| {
+            *known_destination == destination && *known_options == options
+        }) {
+            return Ok(());
+        }
+
+        let output = self
+            .ssh()
+            .args(["-M", "-N", "-f", "-o", "ControlPersist=yes"])
+            .arg(&destination)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .output()
+            .map_err(|e| NodeError::msg(format!("Failed to spawn ssh: {e}")))?;
+        if !output.status.success() {
+            return Err(NodeError::msg(format!(
+                "Failed to connect to {destination}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        masters.push((destination, options));
+        Ok(())
+    }
+
+    /// Streams `files` from the local `dir` into `remote_dir` as a tar archive,
+    /// after running `prepare` on the remote host
+    fn upload(
+        &self,
+        dir: &Path,
+        files: &[&str],
+        remote_dir: &Path,
+        prepare: &str,
+    ) -> Result<(), String> {
+        let mut tar = Command::new("tar")
+            .arg("-C")
+            .arg(dir)
+            // Symlinked inputs are uploaded as the files they point to
+            .arg("-chf")
+            .arg("-")
+            .args(files)
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to spawn tar: {e}"))?;
+        let archive = tar.stdout.take().ok_or("Failed to capture tar output")?;
+        let output = self
+            .ssh()
+            .arg(self.destination())
+            .arg(format!("{prepare} && tar -C {} -xf -", quote(remote_dir)))
+            .stdin(archive)
+            .output()
+            .map_err(|e| format!("Failed to spawn ssh: {e}"))?;
+        let tar_status = tar
+            .wait()
+            .map_err(|e| format!("Failed to wait on tar: {e}"))?;
+        if !tar_status.success() {
+            return Err(format!("tar exited with {tar_status}"));
+        }
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(())
+    }
+
+    /// Streams those of `files` that exist in `remote_dir` back into the local `dir`
+    fn download(&self, remote_dir: &Path, files: &[String], dir: &Path) -> Result<(), String> {
+        let quoted_files: Vec<_> = files.iter().map(|file| quote(Path::new(file))).collect();
+        let script = format!(
+            "cd {} && set -- && for f in {}; do [ -e \"$f\" ] && set -- \"$@\" \"$f\"; done; [ $# -eq 0 ] || tar -cf - \"$@\"",
+            quote(remote_dir),
+            quoted_files.join(" ")
+        );
+        let mut ssh = self
+            .ssh()
+            .arg(self.destination())
+            .arg(script)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to spawn ssh: {e}"))?;
+        let archive = ssh.stdout.take().ok_or("Failed to capture ssh output")?;
+        let tar_output = Command::new("tar")
+            .arg("-C")
+            .arg(dir)
+            .arg("-xf")
+            .arg("-")
+            .stdin(archive)
+            .output()
+            .map_err(|e| format!("Failed to spawn tar: {e}"))?;
+        let ssh_output = ssh
+            .wait_with_output()
+            .map_err(|e| format!("Failed to wait on ssh: {e}"))?;
+        if !ssh_output.status.success() {
+            return Err(String::from_utf8_lossy(&ssh_output.stderr)
+                .trim()
+                .to_string());
+        }
+        if !tar_output.status.success() {
+            return Err(String::from_utf8_lossy(&tar_output.stderr)
+                .trim()
+                .to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Quotes `path` for use in a shell command
+pub(crate) fn quote<P: AsRef<Path>>(path: P) -> String {
+    format!(
+        "'{}'",
+        path.as_ref().to_string_lossy().replace('\'', r"'\''")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Session, config::MaestroConfig};
+    use std::{collections::HashMap, fs};
+
+    #[test]
+    fn quote_wraps_in_single_quotes() {
+        assert_eq!(quote("maestro_work/run"), "'maestro_work/run'");
+        assert_eq!(quote("with space"), "'with space'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[test]
+    fn quote_survives_the_shell() {
+        for path in [
+            "plain",
+            "with space",
+            "it's",
+            "''",
+            "$HOME/`id`/$(id)",
+            "tab\tand\nnewline",
+            "*?[a]",
+            r"back\slash",
+        ] {
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf %s {}", quote(path)))
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), path);
+        }
+    }
+
+    /// Runs a process through the sshd of this machine, which must let the current user
+    /// in with a key and without a passphrase
+    ///
+    /// `MAESTRO_TEST_SSH_PORT` and `MAESTRO_TEST_SSH_IDENTITY` set the port and key to connect with
+    #[test]
+    #[ignore = "requires an sshd on 127.0.0.1"]
+    fn runs_process_on_local_sshd() {
+        let dir = env::temp_dir().join(format!("maestro-ssh-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input file.txt");
+        fs::write(&input, "hello over ssh\n").unwrap();
+
+        let config = MaestroConfig {
+            executors: HashMap::new(),
+            args: HashMap::new(),
+            inputs: HashMap::new(),
+        };
+        let session = Session::new(config, dir.join("session")).unwrap();
+        let mut executor = SshExecutor::new("127.0.0.1").with_remote_workdir(dir.join("remote"));
+        if let Ok(port) = env::var("MAESTRO_TEST_SSH_PORT") {
+            executor = executor.with_port(port.parse().unwrap());
+        }
+        if let Ok(identity_file) = env::var("MAESTRO_TEST_SSH_IDENTITY") {
+            executor = executor.with_identity_file(identity_file);
+        }
+
+        let process = Process::new(
+            "shout".to_string(),
+            vec![("text".into(), input)],
+            vec![("suffix".into(), "!".to_string())],
+            vec![("shouted".into(), PathBuf::from("shouted.txt"))],
+            r#"echo "shouting"; echo "$(tr a-z A-Z < "$text")$suffix" > shouted.txt"#.into(),
+        )
+        .with_session(&session);
+        let outputs = executor.exe(process);
+        close_connections();
+        let outputs = outputs.unwrap();
+
+        let workdir = dir.join("session").join("shout");
+        assert_eq!(outputs, [workdir.join("shouted.txt"), workdir.clone()]);
+        assert_eq!(
+            fs::read_to_string(workdir.join("shouted.txt")).unwrap(),
+            "HELLO OVER SSH!\n"
+        );
+        assert_eq!(
+            fs::read_to_string(workdir.join(".maestro.out")).unwrap(),
+            "shouting\n"
+        );
+        // The output of the launcher is streamed into the local log
+        let log = fs::read_to_string(workdir.join(".maestro.log")).unwrap();
+        assert!(log.contains(":: Staging inputs"), "{log}");
+        assert!(
+            dir.join("remote")
+                .join("session")
+                .join("shout")
+                .join(".maestro.uploads")
+                .exists()
+        );
+    }
+}
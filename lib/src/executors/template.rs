@@ -0,0 +1,636 @@
+use crate::{
+    Container, LP, Process,
+    executors::{
+        Executor, Reusable,
+        monitor::{JobMonitor, JobStatus},
+        retry::{AttemptError, Escalate, Escalation, RetryPolicy},
+        run_reusable,
+        scheduler::{self, Scheduler},
+        slurm::{MemoryConfig, SlurmJobState, SlurmTime, resolve_job},
+        ssh::quote,
+    },
+    process::StagingMode,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// An executor that drives an arbitrary batch scheduler through configurable commands
+///
+/// Commands are run with `sh -c` from the workdir of the process,
+/// after their `{placeholders}` are substituted with shell-quoted values
+#[derive(Clone, Deserialize, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateExecutor {
+    /// Resource directives written at the top of the launcher
+    ///
+    /// Supports `{cpus}`, `{memory_mb}`, `{memory_gb}`, `{gpus}`, `{time}` (as `HH:MM:SS`),
+    /// `{time_mins}`, `{time_secs}`, `{name}` and `{workdir}`;
+    /// lines referring to unset resources are left out
+    #[serde(default)]
+    pub(crate) header: String,
+    /// The command submitting `{launcher}` from `{workdir}`
+    pub(crate) submit: String,
+    /// A regex matching the job id in the output of the submit command,
+    /// taken from its first capture group if it has one
+    #[serde(default = "default_job_id_regex")]
+    pub(crate) job_id_regex: String,
+    pub(crate) status: TemplateStatus,
+    /// The command cancelling `{job_id}`
+    pub(crate) cancel: String,
+    pub(crate) container: Option<Container>,
+    #[serde(default)]
+    pub(crate) cache: bool,
+    #[serde(default = "default_poll_rate")]
+    pub(crate) poll_rate: Duration,
+    #[serde(default)]
+    pub(crate) staging_mode: StagingMode,
+    #[serde(flatten)]
+    pub(crate) retry: RetryPolicy,
+    pub(crate) cpus: Option<u64>,
+    pub(crate) memory: Option<MemoryConfig>,
+    pub(crate) gpus: Option<u64>,
+    pub(crate) time: Option<SlurmTime>,
+}
+fn default_job_id_regex() -> String {
+    r"\d+".to_string()
+}
+const fn default_poll_rate() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// How the states of submitted jobs are queried
+///
+/// Jobs missing from the output of the status command have left the queue.
+/// Once none of the queried jobs is known anymore, the command should either
+/// still list them or succeed; a failing command with no matching lines is retried
+#[derive(Clone, Deserialize, Hash, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateStatus {
+    /// The command listing the states of the space-separated `{job_ids}`
+    pub(crate) command: String,
+    /// A regex matching a line of the status output,
+    /// with the named capture groups `id` and `state`
+    pub(crate) regex: String,
+    /// Maps scheduler states to their meaning; unmapped states count as running
+    ///
+    /// States mapped to `OutOfMemory` or `Timeout` explain jobs that were killed
+    /// before recording their exit status
+    #[serde(default)]
+    pub(crate) states: BTreeMap<String, TemplateState>,
+}
+
+/// The meaning of a scheduler state
+#[derive(Clone, Copy, Deserialize, Hash, PartialEq)]
+pub enum TemplateState {
+    Pending,
+    Running,
+    /// The job has left the queue
+    Finished,
+    /// The job has left the queue after exceeding its memory limit
+    OutOfMemory,
+    /// The job has left the queue after exceeding its time limit
+    Timeout,
+}
+
+impl TemplateStatus {
+    pub fn new<S1: ToString, S2: ToString>(command: S1, regex: S2) -> Self {
+        Self {
+            command: command.to_string(),
+            regex: regex.to_string(),
+            states: BTreeMap::new(),
+        }
+    }
+    pub fn with_state<S: ToString>(mut self, state: S, meaning: TemplateState) -> Self {
+        self.states.insert(state.to_string(), meaning);
+        self
+    }
+}
+
+impl TemplateExecutor {
+    /// Initializes an executor submitting with `submit`, tracking jobs with `status`
+    /// and cancelling them with `cancel`
+    pub fn new<S1: ToString, S2: ToString>(submit: S1, status: TemplateStatus, cancel: S2) -> Self {
+        Self {
+            header: String::new(),
+            submit: submit.to_string(),
+            job_id_regex: default_job_id_regex(),
+            status,
+            cancel: cancel.to_string(),
+            container: None,
+            cache: false,
+            poll_rate: default_poll_rate(),
+            staging_mode: StagingMode::default(),
+            retry: RetryPolicy::default(),
+            cpus: None,
+            memory: None,
+            gpus: None,
+            time: None,
+        }
+    }
+    pub fn with_header<S: ToString>(mut self, header: S) -> Self {
+        self.header = header.to_string();
+        self
+    }
+    pub fn with_job_id_regex<S: ToString>(mut self, regex: S) -> Self {
+        self.job_id_regex = regex.to_string();
+        self
+    }
+    pub fn with_container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+    pub fn with_poll_rate(mut self, rate: Duration) -> Self {
+        self.poll_rate = rate;
+        self
+    }
+    pub fn with_staging_mode(mut self, staging_mode: StagingMode) -> Self {
+        self.staging_mode = staging_mode;
+        self
+    }
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+    pub fn map_retry<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(RetryPolicy) -> RetryPolicy,
+    {
+        self.retry = (f)(self.retry);
+        self
+    }
+    pub fn with_cpus(mut self, cpus: u64) -> Self {
+        self.cpus = Some(cpus);
+        self
+    }
+    pub fn with_memory(mut self, memory: MemoryConfig) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+    pub fn with_gpus(mut self, gpus: u64) -> Self {
+        self.gpus = Some(gpus);
+        self
+    }
+    pub fn with_time(mut self, time: SlurmTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+}
+
+impl Escalate for TemplateExecutor {
+    fn escalate(&self, escalation: &Escalation) -> Self {
+        let mut escalated = self.clone();
+        escalation.apply(
+            &mut escalated.cpus,
+            &mut escalated.memory,
+            &mut escalated.time,
+        );
+        escalated
+    }
+}
+
+impl Executor for TemplateExecutor {
+    fn exe(&self, process: Process) -> NodeResult<Vec<PathBuf>> {
+        run_reusable(self, process, |process| {
+            // Surface malformed templates before anything is submitted
+            for pattern in [&self.job_id_regex, &self.status.regex] {
+                compiled(pattern)
+                    .map_err(|e| NodeError::msg(format!("Invalid regex {pattern}: {e}")))?;
+            }
+            fill(&self.status.command, &[("job_ids", Some(String::new()))])?;
+            fill(&self.cancel, &[("job_id", Some(String::new()))])?;
+            let workdir = process.create_workdir()?;
+            self.retry.run(&process, &workdir, self, |executor| {
+                executor.exe_attempt(&process, &workdir)
+            })
+        })
+    }
+}
+
+impl Reusable for TemplateExecutor {
+    fn cache(&self) -> bool {
+        self.cache
+    }
+    fn hash_execution<H: Hasher>(&self, state: &mut H) {
+        (
+            "Template",
+            &self.container,
+            self.cpus,
+            &self.memory,
+            self.gpus,
+            &self.time,
+        )
+            .hash(state);
+    }
+}
+
+impl TemplateExecutor {
+    fn exe_attempt(&self, process: &Process, workdir: &Path) -> Result<Vec<PathBuf>, AttemptError> {
+        let ((log_path, mut log_handle), (launcher_path, mut launcher_handle)) =
+            process.prep_script_workdir(workdir)?;
+        let header = self.render_header(process, workdir)?;
+        // The exit status is left behind for collection, as schedulers report it inconsistently
+        writeln!(
+            launcher_handle,
+            "{header}cd {}\nexec >> .maestro.log 2>> .maestro.err\ntrap 'echo $? > .maestro.exit' EXIT",
+            quote(workdir)
+        )
+        .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+
+        let staging_mode = match self.container {
+            None => &self.staging_mode,
+            Some(_) => &StagingMode::Copy,
+        };
+        process.stage_inputs(&mut launcher_handle, workdir, staging_mode)?;
+        Process::write_execution(launcher_handle, process, &self.container)?;
+
+        let submit_command = fill(
+            &self.submit,
+            &[
+                ("launcher", Some(quote(&launcher_path))),
+                ("workdir", Some(quote(workdir))),
+                ("name", Some(quote(&process.name))),
+            ],
+        )?;
+        scheduler::run_job(
+            self,
+            process,
+            workdir,
+            shell(&submit_command, Some(workdir)),
+            &mut log_handle,
+            &log_path,
+        )
+    }
+
+    /// Renders the header template, leaving out lines that refer to unset resources
+    fn render_header(&self, process: &Process, workdir: &Path) -> NodeResult<String> {
+        let memory_mb = self
+            .memory
+            .map(|memory| memory.total_mb(self.cpus.unwrap_or(1)));
+        let time_secs = self.time.map(|time| time.as_secs());
+        let values = [
+            ("cpus", self.cpus.map(|cpus| cpus.to_string())),
+            ("memory_mb", memory_mb.map(|mb| mb.to_string())),
+            (
+                "memory_gb",
+                memory_mb.map(|mb| mb.div_ceil(1024).to_string()),
+            ),
+            ("gpus", self.gpus.map(|gpus| gpus.to_string())),
+            ("time", self.time.map(|time| time.as_hms())),
+            (
+                "time_mins",
+                time_secs.map(|secs| secs.div_ceil(60).to_string()),
+            ),
+            ("time_secs", time_secs.map(|secs| secs.to_string())),
+            ("name", Some(process.name.clone())),
+            ("workdir", Some(workdir.display().to_string())),
+        ];
+
+        let mut header = String::new();
+        for line in self.header.lines() {
+            match fill(line, &values) {
+                Ok(line) => {
+                    header.push_str(&line);
+                    header.push('\n');
+                }
+                Err(TemplateError::Unset) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(header)
+    }
+
+    /// The monitor shared by all template executors with the same status configuration,
+    /// along with the limits it saw jobs exceed
+    fn status_monitor(&self) -> (&'static JobMonitor, Arc<ExceededLimits>) {
+        static MONITORS: Mutex<Vec<(TemplateStatus, &'static JobMonitor, Arc<ExceededLimits>)>> =
+            Mutex::new(Vec::new());
+        let mut monitors = MONITORS.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, monitor, exceeded)) = monitors
+            .iter()
+            .find(|(status, _, _)| *status == self.status)
+        {
+            return (monitor, exceeded.clone());
+        }
+
+        let exceeded = Arc::new(ExceededLimits::default());
+        let seen = exceeded.clone();
+        let status = self.status.clone();
+        let name = status
+            .command
+            .split_whitespace()
+            .next()
+            .unwrap_or("template")
+            .to_string();
+        let monitor: &'static JobMonitor = Box::leak(Box::new(JobMonitor::new(
+            name,
+            move |job_ids: &[String]| query_status(&status, job_ids, &seen),
+        )));
+        monitors.push((self.status.clone(), monitor, exceeded.clone()));
+        (monitor, exceeded)
+    }
+}
+
+impl Scheduler for TemplateExecutor {
+    fn monitor(&self) -> &'static JobMonitor {
+        self.status_monitor().0
+    }
+    fn poll_rate(&self) -> Duration {
+        self.poll_rate
+    }
+    fn parse_job_id(&self, stdout: &str) -> Option<String> {
+        let captures = compiled(&self.job_id_regex).ok()?.captures(stdout)?;
+        let id = captures.get(1).or(captures.get(0))?;
+        Some(id.as_str().to_string())
+    }
+    fn cancel(&self, job_id: &str) {
+        if let Ok(cancel_command) = fill(&self.cancel, &[("job_id", Some(quote(job_id)))]) {
+            let _ = shell(&cancel_command, None).status();
+        }
+    }
+    fn collect(
+        &self,
+        process: &Process,
+        workdir: &Path,
+        job_id: &str,
+        log_handle: &mut File,
+        log_path: &Path,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        let exit_code = fs::read_to_string(workdir.join(".maestro.exit"))
+            .ok()
+            .and_then(|code| code.trim().parse::<u32>().ok());
+        let exceeded = self.status_monitor().1.take(job_id);
+        if exit_code.is_none() {
+            let _ = writeln!(
+                log_handle,
+                "{LP} Job left the queue without recording an exit status"
+            );
+        }
+        // A job killed for exceeding a limit may have had its exit status recorded anyway
+        let job_state = exceeded.filter(|_| exit_code != Some(0));
+        resolve_job(
+            process,
+            workdir,
+            job_state,
+            exit_code.map(|code| (code, 0)),
+            log_handle,
+            log_path,
+        )
+    }
+}
+
+/// The jobs a status monitor saw in states mapped to `OutOfMemory` or `Timeout`
+#[derive(Default)]
+struct ExceededLimits(Mutex<HashMap<String, SlurmJobState>>);
+
+impl ExceededLimits {
+    fn record(&self, job_id: &str, state: SlurmJobState) {
+        let mut jobs = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        jobs.insert(job_id.to_string(), state);
+    }
+
+    /// The limit `job_id` was seen to exceed, forgetting the job
+    fn take(&self, job_id: &str) -> Option<SlurmJobState> {
+        let mut jobs = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        jobs.remove(job_id)
+    }
+}
+
+/// Compiles `pattern`, reusing the regex compiled for it earlier in the session
+fn compiled(pattern: &str) -> Result<Regex, regex::Error> {
+    static REGEXES: Mutex<Vec<(String, Regex)>> = Mutex::new(Vec::new());
+    let mut regexes = REGEXES.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, regex)) = regexes.iter().find(|(known, _)| known == pattern) {
+        return Ok(regex.clone());
+    }
+    let regex = Regex::new(pattern)?;
+    regexes.push((pattern.to_string(), regex.clone()));
+    Ok(regex)
+}
+
+/// Runs the status command for `job_ids`, mapping each matching line to the state of its job
+fn query_status(
+    status: &TemplateStatus,
+    job_ids: &[String],
+    exceeded: &ExceededLimits,
+) -> io::Result<HashMap<String, JobStatus>> {
+    let regex = compiled(&status.regex).map_err(io::Error::other)?;
+    let job_ids = job_ids.iter().map(quote).collect::<Vec<_>>().join(" ");
+    let command = fill(&status.command, &[("job_ids", Some(job_ids))])
+        .map_err(|_| io::Error::other("Malformed status command"))?;
+    let output = shell(&command, None).output()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut states = HashMap::new();
+    for captures in stdout.lines().filter_map(|line| regex.captures(line)) {
+        let (Some(id), Some(state)) = (captures.name("id"), captures.name("state")) else {
+            continue;
+        };
+        let job_status = match status.states.get(state.as_str()) {
+            Some(TemplateState::Pending) => JobStatus::Pending,
+            Some(TemplateState::Finished) => JobStatus::Finished,
+            Some(TemplateState::OutOfMemory) => {
+                exceeded.record(id.as_str(), SlurmJobState::OutOfMemory);
+                JobStatus::Finished
+            }
+            Some(TemplateState::Timeout) => {
+                exceeded.record(id.as_str(), SlurmJobState::Timeout);
+                JobStatus::Finished
+            }
+            Some(TemplateState::Running) | None => JobStatus::Running,
+        };
+        states.insert(id.as_str().to_string(), job_status);
+    }
+    if states.is_empty() && !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{command} exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(states)
+}
+
+/// Prepares `command` to run through `sh -c`
+fn shell(command: &str, current_dir: Option<&Path>) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    if let Some(dir) = current_dir {
+        shell.current_dir(dir);
+    }
+    shell
+}
+
+/// Why a template could not be filled
+enum TemplateError {
+    /// A placeholder refers to a value that is not set
+    Unset,
+    Unknown(String),
+}
+impl From<TemplateError> for NodeError {
+    fn from(e: TemplateError) -> Self {
+        match e {
+            TemplateError::Unset => NodeError::msg("Template refers to an unset value".to_string()),
+            TemplateError::Unknown(name) => {
+                NodeError::msg(format!("Unknown placeholder {{{name}}} in template"))
+            }
+        }
+    }
+}
+impl From<TemplateError> for AttemptError {
+    fn from(e: TemplateError) -> Self {
+        AttemptError::Failed(e.into())
+    }
+}
+
+/// Substitutes the `{placeholders}` of `template` with `values`
+///
+/// Braces around anything but a plain identifier, as in `awk '{print $1}'`, are kept as is
+fn fill(template: &str, values: &[(&str, Option<String>)]) -> Result<String, TemplateError> {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let name = rest[start + 1..]
+            .split_once('}')
+            .map(|(name, _)| name)
+            .filter(|name| {
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            });
+        let Some(name) = name else {
+            filled.push_str(&rest[..=start]);
+            rest = &rest[start + 1..];
+            continue;
+        };
+        let (_, value) = values
+            .iter()
+            .find(|(key, _)| *key == name)
+            .ok_or_else(|| TemplateError::Unknown(name.to_string()))?;
+        filled.push_str(&rest[..start]);
+        filled.push_str(value.as_deref().ok_or(TemplateError::Unset)?);
+        rest = &rest[start + name.len() + 2..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn executor(status_output: &str) -> TemplateExecutor {
+        let status = TemplateStatus::new(
+            format!("printf '{status_output}' # {{job_ids}}"),
+            r"^(?<id>\d+)\s+(?<state>\w+)$",
+        )
+        .with_state("Q", TemplateState::Pending)
+        .with_state("C", TemplateState::Finished)
+        .with_state("OOM", TemplateState::OutOfMemory)
+        .with_state("TO", TemplateState::Timeout);
+        TemplateExecutor::new("tsub {launcher}", status, "tdel {job_id}")
+            .with_job_id_regex(r"Job (\d+)\.")
+    }
+
+    fn collect(
+        executor: &TemplateExecutor,
+        job_id: &str,
+        exit: Option<&str>,
+    ) -> Result<Vec<PathBuf>, AttemptError> {
+        let workdir = env::temp_dir().join(format!("maestro-template-{}-{job_id}", process::id()));
+        let _ = fs::remove_dir_all(&workdir);
+        fs::create_dir_all(&workdir).unwrap();
+        if let Some(exit) = exit {
+            fs::write(workdir.join(".maestro.exit"), exit).unwrap();
+        }
+        let log_path = workdir.join(".maestro.log");
+        let mut log_handle = File::create(&log_path).unwrap();
+        let process = Process::new(job_id.to_string(), vec![], vec![], vec![], "true".into());
+        executor.collect(&process, &workdir, job_id, &mut log_handle, &log_path)
+    }
+
+    #[test]
+    fn parses_job_id_from_capture_group() {
+        let executor = executor("");
+        assert_eq!(
+            executor.parse_job_id("Job 4242.fakehost submitted"),
+            Some("4242".to_string())
+        );
+        assert_eq!(executor.parse_job_id("Submission failed"), None);
+        let whole_match = executor.with_job_id_regex(r"\d+");
+        assert_eq!(
+            whole_match.parse_job_id("Job 17 submitted"),
+            Some("17".to_string())
+        );
+    }
+
+    #[test]
+    fn maps_status_lines_to_job_states() {
+        let executor = executor(r"1 Q\n2 R\n3 C\n4 OOM\n5 TO\nnoise\n");
+        let ids: Vec<String> = (1..=6).map(|id| id.to_string()).collect();
+        let states = query_status(&executor.status, &ids, &ExceededLimits::default()).unwrap();
+        assert_eq!(
+            states,
+            HashMap::from([
+                ("1".to_string(), JobStatus::Pending),
+                ("2".to_string(), JobStatus::Running),
+                ("3".to_string(), JobStatus::Finished),
+                ("4".to_string(), JobStatus::Finished),
+                ("5".to_string(), JobStatus::Finished),
+            ])
+        );
+    }
+
+    #[test]
+    fn exceeded_limits_explain_missing_exit_status() {
+        let executor = executor(r"101 OOM\n102 TO\n103 C\n104 OOM\n");
+        let ids: Vec<String> = (101..=104).map(|id| id.to_string()).collect();
+        let (_, exceeded) = executor.status_monitor();
+        query_status(&executor.status, &ids, &exceeded).unwrap();
+
+        assert!(matches!(
+            collect(&executor, "101", None),
+            Err(AttemptError::ResourcesExhausted(_))
+        ));
+        assert!(matches!(
+            collect(&executor, "102", Some("143\n")),
+            Err(AttemptError::ResourcesExhausted(_))
+        ));
+        assert!(matches!(
+            collect(&executor, "103", None),
+            Err(AttemptError::Failed(_))
+        ));
+        // Jobs that exited successfully completed, whatever their final state
+        assert!(collect(&executor, "104", Some("0\n")).is_ok());
+    }
+
+    #[test]
+    fn fills_placeholders() {
+        let values = [("job_id", Some("'7'".to_string())), ("cpus", None)];
+        assert_eq!(
+            fill("tdel {job_id} | awk '{print $1}'", &values).ok(),
+            Some("tdel '7' | awk '{print $1}'".to_string())
+        );
+        assert!(matches!(
+            fill("#CPUS {cpus}", &values),
+            Err(TemplateError::Unset)
+        ));
+        assert!(matches!(
+            fill("{nodes}", &values),
+            Err(TemplateError::Unknown(name)) if name == "nodes"
+        ));
+    }
+}
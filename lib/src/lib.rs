@@ -1,28 +1,35 @@
 use crate::{
     config::MAESTRO_CONFIG,
-    session::{SESSION_WORKDIR, setup_session_workdir},
+    session::{DEFAULT_SESSION, SessionDir, SessionStatus, setup_session_workdir},
 };
 use dagger_lib::result::NodeResult;
 pub use inventory::submit as submit_request;
 pub use maestro_macros::main;
 use serde::Deserialize;
+pub use session::Session;
 use std::{
     borrow::Cow,
+    fmt::Debug,
     fs,
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
     process::exit,
-    sync::LazyLock,
+    sync::{Arc, LazyLock},
 };
 
+mod cache;
 /// Maestro.toml workflow configuration
 pub mod config;
 /// Execution environments
 pub mod executors;
+/// Resource usage of executed processes
+pub mod metrics;
 /// Import prelude
 pub mod prelude;
 /// Process primitives (executor-agnostic)
 pub mod process;
 mod session;
+mod shutdown;
 
 const LP: &str = "\x1b[0;34m::\x1b[0m";
 
@@ -39,10 +46,13 @@ pub struct Process {
     args: Vec<StrArg>,
     outputs: Vec<PathArg>,
     script: Cow<'static, str>,
+    cache: Option<bool>,
+    /// The session to run in, if not the default one
+    session: Option<Arc<SessionDir>>,
 }
 
 /// Defines a container environment for execution
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Hash)]
 pub enum Container {
     Docker(Cow<'static, str>),
     Apptainer(Cow<'static, str>),
@@ -118,6 +128,28 @@ macro_rules! inputs {
     }};
 }
 
+#[macro_export]
+/// Registers a user-defined executor type, selected in Maestro.toml by its name as `type`
+///
+/// The type must implement [`Executor`](executors::Executor) and [`Deserialize`], and is
+/// deserialized from the settings of each executor using it. Its name must not be one of
+/// the built-in executor types
+/// # Example
+/// ```rust
+/// register_executor!(LabExecutor);
+/// register_executor!("Lab", LabExecutor);
+/// ```
+macro_rules! register_executor {
+    ($executor:ident) => {
+        $crate::register_executor!(stringify!($executor), $executor);
+    };
+    ($name:expr, $executor:ty) => {
+        $crate::submit_request! {
+            $crate::executors::plugin::RegisteredExecutor::new::<$executor>($name)
+        }
+    };
+}
+
 #[doc(hidden)]
 pub fn initialize() {
     LazyLock::force(&MAESTRO_CONFIG);
@@ -159,13 +191,65 @@ pub fn initialize() {
             exit(1)
         }
     };
-    let _ = session::SESSION_WORKDIR.set(workdir);
+    let _ = DEFAULT_SESSION.set(Arc::new(workdir));
+    shutdown::install_handlers();
 }
 
 #[doc(hidden)]
 pub fn deinitialize() {
-    if let Some(dir) = SESSION_WORKDIR.get() {
-        let _ = fs::remove_file(dir.join(".maestro.active"));
+    // Jobs submitted ahead are tracked to completion before the session ends
+    executors::slurm::await_submitted(None);
+    executors::slurm::release_allocations();
+    executors::ssh::close_connections();
+    if let Some(dir) = DEFAULT_SESSION.get() {
+        let _ = fs::remove_file(dir.path.join(".maestro.active"));
+    }
+}
+
+/// A return type of a `#[maestro::main]` function, which tells whether the session succeeded
+pub trait MainOutcome {
+    /// The error the session failed with, if it did
+    fn error(&self) -> Option<String>;
+}
+
+impl MainOutcome for () {
+    fn error(&self) -> Option<String> {
+        None
+    }
+}
+
+impl<T, E: Debug> MainOutcome for Result<T, E> {
+    fn error(&self) -> Option<String> {
+        self.as_ref().err().map(|e| format!("{e:?}"))
+    }
+}
+
+/// Runs the body of a `#[maestro::main]` function, deinitializing the session however it ends
+///
+/// The outcome is recorded in the session workdir. Errors are returned for the runtime
+/// to report and exit on, while panics exit with the status of an uncaught panic
+#[doc(hidden)]
+pub fn run_main<T: MainOutcome>(main: impl FnOnce() -> T) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(main)) {
+        Ok(outcome) => {
+            deinitialize();
+            match outcome.error() {
+                Some(e) => session::record_status(SessionStatus::Failed, Some(&e)),
+                None => session::record_status(SessionStatus::Success, None),
+            }
+            outcome
+        }
+        Err(payload) => {
+            // The panic hook has already reported the panic
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("Box<dyn Any>");
+            deinitialize();
+            session::record_status(SessionStatus::Panicked, Some(message));
+            exit(101)
+        }
     }
 }
 
@@ -0,0 +1,94 @@
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The file in each process workdir that metrics are persisted to
+const METRICS_FILE: &str = ".maestro.metrics.json";
+
+/// Resource usage and timing of a process run, as far as its executor can tell
+///
+/// Timestamps are in seconds since the Unix epoch
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ProcessMetrics {
+    pub wall_time_secs: Option<f64>,
+    /// User and system time across all cpus
+    pub cpu_time_secs: Option<f64>,
+    pub peak_rss_bytes: Option<u64>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    /// The host the process ran on
+    pub node: Option<String>,
+    /// How often the scheduler requeued the job, such as after preemption
+    pub requeues: Option<u32>,
+    pub submit_time: Option<u64>,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+}
+
+impl ProcessMetrics {
+    /// Reads the metrics of a run from the outputs it returned, whose last entry is its workdir
+    pub fn from_outputs(outputs: &[PathBuf]) -> NodeResult<Option<ProcessMetrics>> {
+        match outputs.last() {
+            Some(workdir) => Self::read(workdir),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the metrics persisted in a process workdir, if its executor recorded any
+    pub fn read(workdir: &Path) -> NodeResult<Option<ProcessMetrics>> {
+        let contents = match fs::read_to_string(workdir.join(METRICS_FILE)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(NodeError::msg(format!("Failed to read metrics: {e}"))),
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|e| NodeError::msg(format!("Failed to parse metrics: {e}")))
+    }
+
+    /// Persists the metrics into `workdir`; failures are ignored, as metrics are informational
+    pub(crate) fn write(&self, workdir: &Path) {
+        if let Ok(json) = serde_json::to_string_pretty(self) {
+            let _ = fs::write(workdir.join(METRICS_FILE), json + "\n");
+        }
+    }
+
+    /// Records a Slurm exit code of the form `<code>:<signal>`
+    pub(crate) fn set_exit_status(&mut self, (code, signal): (u32, u32)) {
+        // Jobs killed by a signal report it in place of an exit code
+        self.exit_code = (signal == 0).then_some(code as i32);
+        self.signal = (signal != 0).then_some(signal as i32);
+    }
+
+    pub fn wall_time(&self) -> Option<Duration> {
+        self.wall_time_secs.map(Duration::from_secs_f64)
+    }
+    pub fn cpu_time(&self) -> Option<Duration> {
+        self.cpu_time_secs.map(Duration::from_secs_f64)
+    }
+}
+
+/// Converts a point in time into seconds since the Unix epoch
+pub(crate) fn unix_secs(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|since| since.as_secs())
+}
+
+/// The name of the host maestro runs on
+pub(crate) fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the buffer is valid for writes of its whole length
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return None;
+    }
+    let len = buffer
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(buffer.len());
+    Some(String::from_utf8_lossy(&buffer[..len]).into_owned())
+}
@@ -1,4 +1,4 @@
-pub use crate::{IntoArray, WorkflowResult, arg, inputs};
+pub use crate::{IntoArray, WorkflowResult, arg, inputs, metrics::ProcessMetrics};
 pub use dagger_lib::{
     self, dagger,
     parallelize::{parallelize, parallelize_with_time_limit},
@@ -1,13 +1,18 @@
-use crate::{Container, PathArg, Process, StrArg, session::SESSION_WORKDIR};
+use crate::{
+    Container, LP, PathArg, Process, StrArg,
+    executors::{retry::ATTEMPTS_FILE, slurm::is_submitted_output},
+    session::{DEFAULT_SESSION, Session, SessionDir},
+};
 use dagger_lib::result::{NodeError, NodeResult};
 use serde::Deserialize;
 use std::{
     borrow::Cow,
     fmt::Display,
     fs::{self, File, OpenOptions},
-    io::Write as _,
+    io::{self, Write as _},
     os::unix::fs::OpenOptionsExt as _,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 type PathAndHandle = (PathBuf, File);
@@ -26,17 +31,70 @@ impl Process {
             inputs,
             outputs,
             args,
+            cache: None,
+            session: None,
         }
     }
 
-    pub(crate) fn prep_script_workdir(
-        &mut self,
-    ) -> NodeResult<(PathBuf, PathAndHandle, PathAndHandle)> {
-        // Initialized in maestro::initialize
-        let session_dir = SESSION_WORKDIR.get().unwrap().to_path_buf();
+    /// Enables or disables result caching for this process,
+    /// overriding the setting of its executor
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Runs this process in `session` rather than in the default session
+    pub fn with_session(mut self, session: &Session) -> Self {
+        self.session = Some(session.dir.clone());
+        self
+    }
+
+    /// The session this process runs in
+    pub(crate) fn session(&self) -> NodeResult<&SessionDir> {
+        self.session
+            .as_deref()
+            .or_else(|| DEFAULT_SESSION.get().map(Arc::as_ref))
+            .ok_or_else(|| {
+                NodeError::msg(format!(
+                    "Process {} has no session to run in; call maestro::initialize or use a Session",
+                    self.name
+                ))
+            })
+    }
+
+    pub(crate) fn create_workdir(&self) -> NodeResult<PathBuf> {
+        let session = self.session()?;
+        let session_dir = &session.path;
 
         let dir = session_dir.join(&self.name);
-        if dir.exists() {
+        // A directory claimed earlier in this run belongs to another process of the same name
+        let first_claim = session.claim(&self.name);
+        if dir.exists() && session.resuming && first_claim {
+            // Left behind by an unfinished run of the resumed session
+            let stale_dir = session_dir.join(".maestro.stale");
+            fs::create_dir_all(&stale_dir).map_err(|e| {
+                NodeError::msg(format!("Failed to create stale process directory: {e}"))
+            })?;
+            let mut attempt = 1;
+            let destination = loop {
+                let candidate = stale_dir.join(format!("{}.{attempt}", self.name));
+                if !candidate.exists() {
+                    break candidate;
+                }
+                attempt += 1;
+            };
+            fs::rename(&dir, &destination).map_err(|e| {
+                NodeError::msg(format!(
+                    "Failed to move unfinished process directory {} aside: {e}",
+                    dir.display()
+                ))
+            })?;
+            println!(
+                "{LP} Rerunning unfinished process {}; previous attempt moved to {}",
+                self.name,
+                destination.display()
+            );
+        } else if dir.exists() {
             return Err(NodeError::msg(format!(
                 "Process working directory {} already exists! Use a unique process name to avoid collisions",
                 dir.display()
@@ -45,7 +103,42 @@ impl Process {
         fs::create_dir(&dir).map_err(|e| {
             NodeError::msg(format!("Failed to spawn process working directory at {e}"))
         })?;
+        Ok(dir)
+    }
+
+    /// Returns the outputs of a successful earlier run of this process
+    /// when resuming a session
+    pub(crate) fn completed_outputs(&self) -> Option<Vec<PathBuf>> {
+        let session = self.session().ok().filter(|session| session.resuming)?;
+        let dir = session.path.join(&self.name);
+        if !dir.join(".maestro.done").exists()
+            || self.check_files(CheckTime::Output, Some(&dir)).is_err()
+            || !session.claim(&self.name)
+        {
+            return None;
+        }
+        println!("{LP} Skipping completed process {}", self.name);
+        Some(self.output_paths(dir))
+    }
+
+    /// Marks the process in `workdir` as having completed successfully
+    pub(crate) fn record_completion(workdir: &Path) -> NodeResult<()> {
+        fs::write(workdir.join(".maestro.done"), "success\n")
+            .map_err(|e| NodeError::msg(format!("Failed to write completion record: {e}")))
+    }
+
+    /// Resolves the declared outputs against `workdir`, followed by `workdir` itself
+    pub(crate) fn output_paths(&self, workdir: PathBuf) -> Vec<PathBuf> {
+        let mut outputs: Vec<_> = self.outputs.iter().map(|(_, p)| workdir.join(p)).collect();
+        outputs.push(workdir);
+        outputs
+    }
 
+    /// Writes the script, log and launcher of an attempt into `dir`
+    pub(crate) fn prep_script_workdir(
+        &self,
+        dir: &Path,
+    ) -> NodeResult<(PathAndHandle, PathAndHandle)> {
         let script_path = dir.join(".maestro.sh");
         let mut script_file = OpenOptions::new()
             .write(true)
@@ -74,11 +167,29 @@ impl Process {
         writeln!(launcher_handle, "#!/bin/bash")
             .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
 
-        Ok((
-            dir,
-            (log_path, log_handle),
-            (launcher_path, launcher_handle),
-        ))
+        Ok(((log_path, log_handle), (launcher_path, launcher_handle)))
+    }
+
+    /// Moves the files of a failed attempt in `workdir` into an attempt subdirectory,
+    /// leaving the record of attempts in place
+    pub(crate) fn archive_attempt(workdir: &Path, attempt: u32) -> NodeResult<PathBuf> {
+        let attempts_dir = workdir.join(".maestro.attempts");
+        let destination = attempts_dir.join(attempt.to_string());
+        fs::create_dir_all(&destination)
+            .map_err(|e| NodeError::msg(format!("Failed to create attempt directory: {e}")))?;
+        for entry in fs::read_dir(workdir)? {
+            let entry = entry?;
+            if entry.path() == attempts_dir || entry.file_name() == ATTEMPTS_FILE {
+                continue;
+            }
+            fs::rename(entry.path(), destination.join(entry.file_name())).map_err(|e| {
+                NodeError::msg(format!(
+                    "Failed to archive {} of attempt {attempt}: {e}",
+                    entry.path().display()
+                ))
+            })?;
+        }
+        Ok(destination)
     }
 
     pub(crate) fn stage_inputs(
@@ -87,6 +198,23 @@ impl Process {
         workdir: &Path,
         staging_mode: &StagingMode,
     ) -> NodeResult<()> {
+        self.stage_inputs_from(launcher, workdir, staging_mode, |_, file| {
+            resolve_input(file)
+        })
+    }
+
+    /// Writes the staging of inputs into the launcher,
+    /// where `source` resolves the path input `n` is staged from
+    pub(crate) fn stage_inputs_from<F>(
+        &self,
+        launcher: &mut File,
+        workdir: &Path,
+        staging_mode: &StagingMode,
+        mut source: F,
+    ) -> NodeResult<()>
+    where
+        F: FnMut(usize, &Path) -> io::Result<PathBuf>,
+    {
         writeln!(launcher, "set -euo pipefail")
             .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
 
@@ -104,7 +232,7 @@ impl Process {
         self.check_files(CheckTime::Input, None)?;
 
         let stage_inputs = !matches!(staging_mode, StagingMode::None);
-        for (var, file) in &self.inputs {
+        for (n, (var, file)) in self.inputs.iter().enumerate() {
             let var = var.split_whitespace().collect::<Vec<_>>().join("_");
             let transformed_arg = if stage_inputs {
                 let file_name = file.file_name().ok_or(NodeError::msg(format!(
@@ -114,7 +242,7 @@ impl Process {
                 let destination = input_dir.join(format!("[{}]{}", var, file_name.display()));
                 destination.to_string_lossy().into_owned()
             } else {
-                file.canonicalize()?.to_string_lossy().into_owned()
+                source(n, file)?.to_string_lossy().into_owned()
             };
             writeln!(launcher, "export {}=\"{}\"", var, transformed_arg)
                 .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
@@ -123,7 +251,7 @@ impl Process {
                     launcher,
                     "{} \"{}\" \"${}\"",
                     staging_mode,
-                    file.canonicalize()?.display(),
+                    source(n, file)?.display(),
                     var
                 )
                 .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
@@ -154,7 +282,13 @@ impl Process {
                 } else {
                     p.clone()
                 };
-                if path.exists() { None } else { Some(path) }
+                // Outputs of jobs submitted ahead appear once those jobs finish
+                let pending = matches!(time, CheckTime::Input) && is_submitted_output(&path);
+                if path.exists() || pending {
+                    None
+                } else {
+                    Some(path)
+                }
             })
             .collect();
         // Some non-existent file
@@ -178,9 +312,21 @@ impl Process {
     }
 
     pub(crate) fn write_execution(
+        launcher_handle: File,
+        process: &Process,
+        container: &Option<Container>,
+    ) -> NodeResult<()> {
+        Self::write_limited_execution(launcher_handle, process, container, None, None)
+    }
+
+    /// Writes the execution of `process`, confining a docker or podman container
+    /// to `cpus` and `memory_mb` megabytes
+    pub(crate) fn write_limited_execution(
         mut launcher_handle: File,
         process: &Process,
         container: &Option<Container>,
+        cpus: Option<u64>,
+        memory_mb: Option<u64>,
     ) -> NodeResult<()> {
         let execution_str = "./.maestro.sh >> .maestro.out 2>> .maestro.err";
         let image = match container {
@@ -200,6 +346,16 @@ impl Process {
                         "{binary} run --rm -v $(pwd):/maestro -w /maestro "
                     )
                     .map_err(|e| NodeError::msg(format!("Failed to write to launcher: {e}")))?;
+                    if let Some(cpus) = cpus {
+                        write!(launcher_handle, "--cpus={cpus} ").map_err(|e| {
+                            NodeError::msg(format!("Failed to write to launcher: {e}"))
+                        })?;
+                    }
+                    if let Some(mb) = memory_mb {
+                        write!(launcher_handle, "--memory={mb}m --memory-swap={mb}m ").map_err(
+                            |e| NodeError::msg(format!("Failed to write to launcher: {e}")),
+                        )?;
+                    }
                     image
                 }
                 Container::Apptainer(image) => {
@@ -229,12 +385,21 @@ impl Process {
     }
 }
 
+/// Resolves an input to an absolute path, which need not exist yet
+/// if it is produced by a job submitted ahead
+pub(crate) fn resolve_input(file: &Path) -> io::Result<PathBuf> {
+    match file.canonicalize() {
+        Err(_) if is_submitted_output(file) => std::path::absolute(file),
+        result => result,
+    }
+}
+
 pub(crate) enum CheckTime {
     Input,
     Output,
 }
 
-#[derive(Clone, Copy, Deserialize, Default)]
+#[derive(Clone, Copy, Deserialize, Default, Hash)]
 pub enum StagingMode {
     Copy,
     #[default]
@@ -250,3 +415,100 @@ impl Display for StagingMode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Container, Process, Session, config::MaestroConfig};
+    use std::{collections::HashMap, env, fs, path::PathBuf, process};
+
+    fn session_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("maestro-process-{}-{test}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn config() -> MaestroConfig {
+        MaestroConfig {
+            executors: HashMap::new(),
+            args: HashMap::new(),
+            inputs: HashMap::new(),
+        }
+    }
+
+    fn process(session: &Session) -> Process {
+        Process::new("step".to_string(), vec![], vec![], vec![], "true".into())
+            .with_session(session)
+    }
+
+    #[test]
+    fn resuming_moves_aside_only_directories_of_earlier_runs() {
+        let dir = session_dir("resume");
+        fs::create_dir_all(dir.join("step")).unwrap();
+        fs::write(dir.join("step").join(".maestro.log"), "earlier run").unwrap();
+
+        let session = Session::resume(config(), &dir).unwrap();
+        let workdir = process(&session).create_workdir().unwrap();
+        assert_eq!(workdir, dir.join("step"));
+        assert!(!workdir.join(".maestro.log").exists());
+        assert!(
+            dir.join(".maestro.stale")
+                .join("step.1")
+                .join(".maestro.log")
+                .exists()
+        );
+
+        // A second process of the same name collides with the first rather than replacing it
+        assert!(process(&session).create_workdir().is_err());
+        assert!(!dir.join(".maestro.stale").join("step.2").exists());
+    }
+
+    #[test]
+    fn completed_processes_claim_their_directory() {
+        let dir = session_dir("completed");
+        fs::create_dir_all(dir.join("step")).unwrap();
+        Process::record_completion(&dir.join("step")).unwrap();
+
+        let session = Session::resume(config(), &dir).unwrap();
+        assert_eq!(
+            process(&session).completed_outputs(),
+            Some(vec![dir.join("step")])
+        );
+        assert_eq!(process(&session).completed_outputs(), None);
+        assert!(process(&session).create_workdir().is_err());
+        assert!(dir.join("step").join(".maestro.done").exists());
+    }
+
+    #[test]
+    fn new_sessions_reject_duplicate_names() {
+        let dir = session_dir("new");
+        let session = Session::new(config(), &dir).unwrap();
+        process(&session).create_workdir().unwrap();
+        assert!(process(&session).create_workdir().is_err());
+    }
+
+    #[test]
+    fn confines_docker_and_podman_containers_to_limits() {
+        let dir = session_dir("limits");
+        fs::create_dir_all(&dir).unwrap();
+        let session = Session::new(config(), &dir).unwrap();
+        let launcher = |container: Container| {
+            let path = dir.join("launcher");
+            let handle = fs::File::create(&path).unwrap();
+            let container = Some(container);
+            Process::write_limited_execution(
+                handle,
+                &process(&session),
+                &container,
+                Some(2),
+                Some(512),
+            )
+            .unwrap();
+            fs::read_to_string(path).unwrap()
+        };
+
+        let docker = launcher(Container::Docker("ubuntu".into()));
+        assert!(docker.contains("docker run --rm -v $(pwd):/maestro -w /maestro --cpus=2 --memory=512m --memory-swap=512m ubuntu"), "{docker}");
+        assert!(launcher(Container::Podman("ubuntu".into())).contains("--cpus=2 --memory=512m"));
+        assert!(!launcher(Container::Apptainer("ubuntu.sif".into())).contains("--cpus"));
+    }
+}
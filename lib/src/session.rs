@@ -1,50 +1,302 @@
-use crate::LP;
+use crate::{
+    LP, Process, WorkflowResult,
+    config::MaestroConfig,
+    executors::{self, GenericExecutor},
+    metrics::unix_secs,
+};
+use dagger_lib::result::{NodeError, NodeResult};
+use serde::Serialize;
 use session_gen::generate_session_id;
 use std::{
+    collections::HashSet,
     env,
     fs::{self},
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process,
-    sync::OnceLock,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+    time::SystemTime,
 };
 
-pub(crate) static SESSION_WORKDIR: OnceLock<PathBuf> = OnceLock::new();
+/// The file in the session workdir recording how the session ended
+const STATUS_FILE: &str = ".maestro.status.json";
+
+/// The workdir of a session, which the processes run in it share
+pub(crate) struct SessionDir {
+    pub(crate) path: PathBuf,
+    /// Whether the session resumes an earlier, interrupted one
+    pub(crate) resuming: bool,
+    /// The names of the processes given a workdir during this run
+    claimed: Mutex<HashSet<String>>,
+}
+
+impl SessionDir {
+    pub(crate) fn new(path: PathBuf, resuming: bool) -> Self {
+        Self {
+            path,
+            resuming,
+            claimed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Claims the workdir of the process `name` for this run,
+    /// returning false if it was already claimed
+    pub(crate) fn claim(&self, name: &str) -> bool {
+        self.claimed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.to_string())
+    }
+
+    /// Returns the directory holding cached process results, shared across sessions
+    pub(crate) fn cache_dir(&self) -> PathBuf {
+        self.path
+            .parent()
+            .unwrap_or(&self.path)
+            .join(".maestro_cache")
+    }
+}
+
+/// The session set up by `maestro::initialize`, which processes run in by default
+pub(crate) static DEFAULT_SESSION: OnceLock<Arc<SessionDir>> = OnceLock::new();
 
-pub(crate) fn setup_session_workdir() -> Result<PathBuf, io::Error> {
-    let session_id = env::var("MAESTRO_SESSION_ID").unwrap_or(generate_session_id());
+pub(crate) fn setup_session_workdir() -> Result<SessionDir, io::Error> {
+    let resume = env::var_os("MAESTRO_RESUME").is_some();
+    let session_id = match env::var("MAESTRO_SESSION_ID") {
+        Ok(v) => v,
+        Err(_) if resume => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "MAESTRO_SESSION_ID must be set to resume a session",
+            ));
+        }
+        Err(_) => generate_session_id(),
+    };
     let maestro_workdir = match env::var("MAESTRO_WORKDIR") {
         Ok(v) => PathBuf::from(v),
         Err(_) => env::current_dir()?.join("maestro_work"),
     };
     let session_workdir = maestro_workdir.join(&session_id);
-    fs::create_dir_all(&session_workdir)?;
-
-    // Check if there are any existing directories in the session working directory
-    if fs::read_dir(&session_workdir)?.any(|item| {
-        if let Ok(entry) = item
-            && let Ok(ty) = entry.file_type()
-            && ty.is_dir()
-        {
-            true
-        } else {
-            false
-        }
-    }) {
-        return Err(io::Error::new(
-            io::ErrorKind::DirectoryNotEmpty,
-            format!("Directory {} is not empty!", session_workdir.display()),
-        ));
-    }
+    prepare_workdir(&session_workdir, resume)?;
 
     fs::write(
         session_workdir.join(".maestro.active"),
         process::id().to_string(),
     )?;
+    let status = if resume {
+        "Resuming maestro session!"
+    } else {
+        "New maestro session initialized!"
+    };
     println!(
-        "{LP} New maestro session initialized!\n{LP} ID: {}\n{LP} Workdir: {}",
+        "{LP} {status}\n{LP} ID: {}\n{LP} Workdir: {}",
         session_id,
         session_workdir.display()
     );
-    Ok(session_workdir)
+    Ok(SessionDir::new(session_workdir, resume))
+}
+
+/// Creates a fresh session workdir, or checks that an existing one can be resumed
+fn prepare_workdir(session_workdir: &Path, resume: bool) -> Result<(), io::Error> {
+    let session_id = session_workdir
+        .file_name()
+        .unwrap_or(session_workdir.as_os_str())
+        .to_string_lossy();
+    if resume {
+        if !session_workdir.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "Cannot resume session {session_id}: {} does not exist",
+                    session_workdir.display()
+                ),
+            ));
+        }
+        if let Ok(pid) = fs::read_to_string(session_workdir.join(".maestro.active"))
+            && pid.trim() != process::id().to_string()
+            && process::Command::new("kill")
+                .args(["-0", pid.trim()])
+                .stderr(process::Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::ResourceBusy,
+                format!(
+                    "Session {session_id} is still running with PID {}",
+                    pid.trim()
+                ),
+            ));
+        }
+    } else {
+        fs::create_dir_all(session_workdir)?;
+
+        // Check if there are any existing directories in the session working directory
+        if fs::read_dir(session_workdir)?.any(|item| {
+            if let Ok(entry) = item
+                && let Ok(ty) = entry.file_type()
+                && ty.is_dir()
+            {
+                true
+            } else {
+                false
+            }
+        }) {
+            return Err(io::Error::new(
+                io::ErrorKind::DirectoryNotEmpty,
+                format!("Directory {} is not empty!", session_workdir.display()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A maestro session with its own configuration and workdir
+///
+/// Unlike the default session set up by `#[maestro::main]`, which reads Maestro.toml
+/// and exits on errors, sessions are built explicitly and report errors as results.
+/// Processes run in a session through [`Session::exe`] or [`Process::with_session`].
+/// Dropping a session waits for the Slurm jobs submitted ahead in it to finish
+///
+/// # Example
+/// ```rust,no_run
+/// # use maestro::{Session, prelude::*};
+/// # fn run() -> NodeResult<()> {
+/// let session = Session::from_path("Maestro.toml", "maestro_work/run1")?;
+/// let outputs = session.exe("default", process! {
+///     name = "hello",
+///     script = r#"echo "Hello, world!""#
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Session {
+    pub(crate) dir: Arc<SessionDir>,
+    config: MaestroConfig,
+}
+
+impl Session {
+    /// Starts a session in `workdir`, which is created if needed and must hold no process directories
+    pub fn new(config: MaestroConfig, workdir: impl Into<PathBuf>) -> NodeResult<Self> {
+        Self::open(config, workdir.into(), false)
+    }
+
+    /// Resumes the session in `workdir`, skipping processes that completed in an earlier run
+    pub fn resume(config: MaestroConfig, workdir: impl Into<PathBuf>) -> NodeResult<Self> {
+        Self::open(config, workdir.into(), true)
+    }
+
+    /// Starts a session in `workdir` configured by the file at `config_path`
+    pub fn from_path(
+        config_path: impl AsRef<Path>,
+        workdir: impl Into<PathBuf>,
+    ) -> NodeResult<Self> {
+        Self::new(MaestroConfig::from_path(config_path)?, workdir)
+    }
+
+    /// Starts a session in `workdir` configured by `config`, in the format of Maestro.toml
+    pub fn from_toml(config: &str, workdir: impl Into<PathBuf>) -> NodeResult<Self> {
+        Self::new(MaestroConfig::from_toml(config)?, workdir)
+    }
+
+    fn open(config: MaestroConfig, workdir: PathBuf, resume: bool) -> NodeResult<Self> {
+        prepare_workdir(&workdir, resume)
+            .map_err(|e| NodeError::msg(format!("Failed to setup session workdir: {e}")))?;
+        Ok(Self {
+            dir: Arc::new(SessionDir::new(workdir, resume)),
+            config,
+        })
+    }
+
+    pub fn workdir(&self) -> &Path {
+        &self.dir.path
+    }
+    pub fn config(&self) -> &MaestroConfig {
+        &self.config
+    }
+
+    /// The executor named `name` in the configuration
+    pub fn executor(&self, name: &str) -> NodeResult<&GenericExecutor> {
+        self.config.executors.get(name).ok_or_else(|| {
+            NodeError::msg(format!(
+                "Executor \"{name}\" is not defined in the session config"
+            ))
+        })
+    }
+
+    /// The value of the arg `name` in the configuration
+    pub fn arg(&self, name: &str) -> NodeResult<&str> {
+        self.config
+            .args
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| {
+                NodeError::msg(format!(
+                    "Arg \"{name}\" is not defined in the session config"
+                ))
+            })
+    }
+
+    /// The files of the input argument `name` in the configuration
+    pub fn inputs(&self, name: &str) -> NodeResult<Vec<&Path>> {
+        let files = self.config.inputs.get(name).ok_or_else(|| {
+            NodeError::msg(format!(
+                "Input argument \"{name}\" is not defined in the session config"
+            ))
+        })?;
+        files
+            .iter()
+            .map(|file| match Path::new(file) {
+                path if path.exists() => Ok(path),
+                _ => Err(NodeError::msg(format!(
+                    "Input file \"{file}\" does not exist!"
+                ))),
+            })
+            .collect()
+    }
+
+    /// Runs `process` in the session on the executor named `executor` in its configuration
+    pub fn exe(&self, executor: &str, process: Process) -> WorkflowResult {
+        self.executor(executor)?.exe(process.with_session(self))
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        executors::slurm::await_submitted(Some(&self.dir.path));
+    }
+}
+
+/// How a session ended
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SessionStatus {
+    Success,
+    /// The main function returned an error
+    Failed,
+    Panicked,
+    /// The session was shut down by a signal
+    Interrupted,
+}
+
+#[derive(Serialize)]
+struct StatusRecord<'a> {
+    status: SessionStatus,
+    message: Option<&'a str>,
+    end_time: Option<u64>,
+}
+
+/// Records the final status of the session in its workdir; failures are ignored
+pub(crate) fn record_status(status: SessionStatus, message: Option<&str>) {
+    let Some(dir) = DEFAULT_SESSION.get() else {
+        return;
+    };
+    let record = StatusRecord {
+        status,
+        message,
+        end_time: unix_secs(SystemTime::now()),
+    };
+    if let Ok(json) = serde_json::to_string_pretty(&record) {
+        let _ = fs::write(dir.path.join(STATUS_FILE), json + "\n");
+    }
 }
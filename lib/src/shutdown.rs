@@ -0,0 +1,227 @@
+use crate::{
+    LP,
+    executors::{self, local::signal_process_group},
+    session::{self, DEFAULT_SESSION, SessionStatus},
+};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{Read as _, Write as _},
+    os::fd::FromRawFd as _,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, AtomicI32, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The file marking a process that was cancelled as the session shut down
+pub(crate) const CANCELLED_FILE: &str = ".maestro.cancelled";
+/// How long local processes may take to exit after SIGTERM before they are killed
+const GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Work to undo when the session is interrupted
+enum Cancellation {
+    /// A job submitted to a scheduler, cancelled by calling the closure
+    Job(Box<dyn Fn() + Send>),
+    /// A spawned process group, terminated and killed if it outlives the grace period
+    ProcessGroup(u32),
+}
+
+struct Registered {
+    workdirs: Vec<PathBuf>,
+    cancellation: Cancellation,
+}
+
+impl Registered {
+    fn cancel(&self, signal: &str) {
+        match &self.cancellation {
+            Cancellation::Job(cancel) => cancel(),
+            Cancellation::ProcessGroup(pgid) => signal_process_group(*pgid, "TERM"),
+        }
+        for workdir in &self.workdirs {
+            let _ = fs::write(workdir.join(CANCELLED_FILE), format!("{signal}\n"));
+            if let Ok(mut log_handle) = fs::OpenOptions::new()
+                .append(true)
+                .open(workdir.join(".maestro.log"))
+            {
+                let _ = writeln!(
+                    log_handle,
+                    "{LP} Cancelled as the session received {signal}"
+                );
+            }
+        }
+    }
+}
+
+struct Registry {
+    next_id: u64,
+    entries: BTreeMap<u64, Registered>,
+    /// The signal the session is shutting down on, if it is
+    signal: Option<&'static str>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    next_id: 0,
+    entries: BTreeMap::new(),
+    signal: None,
+});
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// The write end of the pipe signal handlers report to
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keeps outstanding work registered for cancellation on shutdown until dropped
+pub(crate) struct InFlight(u64);
+
+impl InFlight {
+    /// Registers a submitted job running in `workdirs`, cancelled through `cancel` on shutdown
+    pub(crate) fn job<F: Fn() + Send + 'static>(workdirs: Vec<PathBuf>, cancel: F) -> Self {
+        Self::register(Registered {
+            workdirs,
+            cancellation: Cancellation::Job(Box::new(cancel)),
+        })
+    }
+
+    /// Registers a process group spawned in `workdir`
+    pub(crate) fn process_group(workdir: &Path, pgid: u32) -> Self {
+        Self::register(Registered {
+            workdirs: vec![workdir.to_path_buf()],
+            cancellation: Cancellation::ProcessGroup(pgid),
+        })
+    }
+
+    fn register(registered: Registered) -> Self {
+        let mut registry = registry();
+        // Work started while shutting down is cancelled right away
+        if let Some(signal) = registry.signal {
+            registered.cancel(signal);
+        }
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.entries.insert(id, registered);
+        Self(id)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        registry().entries.remove(&self.0);
+    }
+}
+
+/// Whether the session is shutting down after having been interrupted
+pub(crate) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    let byte = signal as u8;
+    // SAFETY: write is async-signal-safe, and the byte outlives the call
+    unsafe {
+        libc::write(
+            SIGNAL_PIPE.load(Ordering::Relaxed),
+            (&raw const byte).cast(),
+            1,
+        );
+    }
+}
+
+/// Installs SIGINT and SIGTERM handlers that shut the session down gracefully
+///
+/// Signals are relayed through a pipe to a dedicated thread, which cancels outstanding
+/// jobs and exits. A second signal terminates the session right away
+pub(crate) fn install_handlers() {
+    let mut fds = [0; 2];
+    // SAFETY: fds is valid for writes of two descriptors
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        eprintln!("{LP} Failed to install signal handlers; jobs will not be cancelled on exit");
+        return;
+    }
+    for fd in fds {
+        // SAFETY: fd is a descriptor that was just opened
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    SIGNAL_PIPE.store(fds[1], Ordering::Relaxed);
+    // SAFETY: the read end is owned by nothing else
+    let mut signals = unsafe { File::from_raw_fd(fds[0]) };
+    let spawned = thread::Builder::new()
+        .name("maestro-shutdown".to_string())
+        .spawn(move || {
+            let mut signal = [0u8];
+            if signals.read_exact(&mut signal).is_ok() {
+                shut_down(signal[0] as libc::c_int);
+            }
+        });
+    if let Err(e) = spawned {
+        eprintln!("{LP} Failed to spawn shutdown thread: {e}; jobs will not be cancelled on exit");
+        return;
+    }
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: sigaction is plain data, for which all zeroes is a valid value
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESETHAND | libc::SA_RESTART;
+        // SAFETY: the action is fully initialized and the handler is async-signal-safe
+        unsafe {
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Cancels all outstanding work, then exits with the conventional status for `signal`
+fn shut_down(signal: libc::c_int) {
+    let name = match signal {
+        libc::SIGINT => "SIGINT",
+        _ => "SIGTERM",
+    };
+    eprintln!(
+        "{LP} Received {name}; cancelling outstanding jobs (signal again to exit immediately)"
+    );
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    {
+        let mut registry = registry();
+        registry.signal = Some(name);
+        for registered in registry.entries.values() {
+            registered.cancel(name);
+        }
+    }
+
+    // Local processes are reaped by the threads waiting on them, which unregisters them
+    let deadline = Instant::now() + GRACE_PERIOD;
+    let process_groups = || -> Vec<u32> {
+        registry()
+            .entries
+            .values()
+            .filter_map(|registered| match registered.cancellation {
+                Cancellation::ProcessGroup(pgid) => Some(pgid),
+                Cancellation::Job(_) => None,
+            })
+            .collect()
+    };
+    while !process_groups().is_empty() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+    }
+    for pgid in process_groups() {
+        signal_process_group(pgid, "KILL");
+    }
+
+    executors::slurm::release_allocations();
+    executors::ssh::close_connections();
+    session::record_status(
+        SessionStatus::Interrupted,
+        Some(&format!("Received {name}")),
+    );
+    if let Some(dir) = DEFAULT_SESSION.get() {
+        let _ = fs::remove_file(dir.path.join(".maestro.active"));
+    }
+    process::exit(128 + signal);
+}
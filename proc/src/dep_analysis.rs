@@ -16,7 +16,9 @@ pub(crate) static DEPENDENCIES_FILE: LazyLock<Mutex<File>> = LazyLock::new(|| {
 
 #[derive(Serialize)]
 pub(crate) struct ProcessDependencies {
-    pub(crate) executor: String,
+    /// Absent for processes run by a `Session` or an executor chosen at runtime
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) executor: Option<String>,
     pub(crate) deps: Vec<String>,
 }
 
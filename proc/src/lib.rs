@@ -30,12 +30,13 @@ mod dep_analysis;
 
 struct ProcessDefinition {
     name: Option<Expr>,
-    executor: LitStr,
+    executor: Option<LitStr>,
     inputs: Punctuated<SynIdent, Comma>,
     args: Punctuated<SynIdent, Comma>,
     outputs: Punctuated<SynIdent, Comma>,
     dependencies: Punctuated<LitStr, Comma>,
     inline: bool,
+    cache: Option<bool>,
     literal: LitStr,
 }
 
@@ -48,6 +49,7 @@ mod kw {
     custom_keyword!(args);
     custom_keyword!(dependencies);
     custom_keyword!(inline);
+    custom_keyword!(cache);
     custom_keyword!(script);
 }
 
@@ -60,6 +62,7 @@ impl Parse for ProcessDefinition {
         let mut outputs = Punctuated::new();
         let mut dependencies = Punctuated::new();
         let mut inline = true;
+        let mut cache = None;
         let mut process = None;
 
         macro_rules! parse_list {
@@ -93,6 +96,10 @@ impl Parse for ProcessDefinition {
                 let _: kw::inline = input.parse()?;
                 let _: Eq = input.parse()?;
                 inline = input.parse::<LitBool>()?.value();
+            } else if input.peek(kw::cache) {
+                let _: kw::cache = input.parse()?;
+                let _: Eq = input.parse()?;
+                cache = Some(input.parse::<LitBool>()?.value());
             } else if input.peek(kw::script) {
                 let _: kw::script = input.parse()?;
                 let _: Eq = input.parse()?;
@@ -119,30 +126,26 @@ impl Parse for ProcessDefinition {
                 ));
             }
         };
-        let executor = match declared_executor {
-            Some(v) => v,
-            None => {
-                return Err(syn::Error::new(
-                    input.span(),
-                    "Missing required `executor` field!",
-                ));
-            }
-        };
-
         Ok(ProcessDefinition {
             name,
-            executor,
+            executor: declared_executor,
             inputs,
             args,
             outputs,
             dependencies,
             inline,
+            cache,
             literal,
         })
     }
 }
 
 /// A maestro process definition
+///
+/// With an `executor`, the process is run on the executor of that name in Maestro.toml
+/// and its result is returned. Without one, the `Process` itself is returned,
+/// to be run by a `Session` or an executor of your choice
+///
 /// # Example
 /// ```rust
 /// process! {
@@ -152,7 +155,8 @@ impl Parse for ProcessDefinition {
 ///     inputs = [input],
 ///     outputs = [output1, output2],
 ///     dependencies = ["some_dep"],
-///     process = r#"
+///     cache = true,
+///     script = r#"
 ///         echo "Hello, world!"
 ///     "#
 /// }
@@ -344,7 +348,7 @@ pub fn process(input: TokenStream) -> TokenStream {
     let process_span = literal.span();
     let mut root = HashMap::new();
     let dependencies = ProcessDependencies {
-        executor: definition.executor.value(),
+        executor: definition.executor.as_ref().map(LitStr::value),
         deps: dependencies.into_iter().collect(),
     };
     root.insert(
@@ -394,12 +398,15 @@ pub fn process(input: TokenStream) -> TokenStream {
         }
     }
 
-    let executor = definition.executor;
-    let executor_tokens = quote! {
-        maestro::submit_request! {
-            maestro::RequestedExecutor(#executor, file!(), line!(), column!())
-        };
-        maestro::config::MAESTRO_CONFIG.executors[#executor].exe(process)
+    let cache_tokens = definition.cache.map(|cache| quote! { .with_cache(#cache) });
+    let executor_tokens = match definition.executor {
+        Some(executor) => quote! {
+            maestro::submit_request! {
+                maestro::RequestedExecutor(#executor, file!(), line!(), column!())
+            };
+            maestro::config::MAESTRO_CONFIG.executors[#executor].exe(process)
+        },
+        None => quote! { process },
     };
 
     quote! {{
@@ -409,7 +416,7 @@ pub fn process(input: TokenStream) -> TokenStream {
             vec![#(#arg_pairs),*],
             vec![#(#output_pairs),*],
             ::std::borrow::Cow::Borrowed(#process_lit),
-        );
+        )#cache_tokens;
         #executor_tokens
     }}
     .into()
@@ -421,13 +428,16 @@ static GENERATED_HASHES: LazyLock<Mutex<FxHashSet<String>>> =
 #[proc_macro_attribute]
 /// An attribute macro to inject session initialization/deinitialization actions
 ///
-/// Should be attached to `main`
+/// Should be attached to `main`, which may return `()` or a `Result`.
+/// The session is deinitialized even if `main` panics, and its outcome is recorded
+/// in `.maestro.status.json` within the session workdir
 ///
 /// # Example
 /// ```rust
 /// #[maestro::main]
-/// fn main() {
+/// fn main() -> Result<(), String> {
 ///     // ...
+///     Ok(())
 /// }
 /// ```
 pub fn main(attrs: TokenStream, body: TokenStream) -> TokenStream {
@@ -465,25 +475,23 @@ pub fn main(attrs: TokenStream, body: TokenStream) -> TokenStream {
                 TokenTree::Group(Group::new(Delimiter::Parenthesis, TokenStream::new())),
                 TokenTree::Punct(Punct::new(';', Spacing::Alone)),
             ]);
-            new_body.extend([
-                TokenTree::Ident(Ident::new("let", Span::call_site())),
-                TokenTree::Ident(Ident::new("main_result", Span::call_site())),
-                TokenTree::Punct(Punct::new('=', Spacing::Alone)),
-                TokenTree::Group(Group::new(Delimiter::Brace, group.stream())),
-                TokenTree::Punct(Punct::new(';', Spacing::Alone)),
-            ]);
+            // The body runs in a closure so that early returns and panics still deinitialize
             new_body.extend([
                 TokenTree::Ident(Ident::new("maestro", Span::call_site())),
                 TokenTree::Punct(Punct::new(':', Spacing::Joint)),
                 TokenTree::Punct(Punct::new(':', Spacing::Alone)),
-                TokenTree::Ident(Ident::new("deinitialize", Span::call_site())),
-                TokenTree::Group(Group::new(Delimiter::Parenthesis, TokenStream::new())),
-                TokenTree::Punct(Punct::new(';', Spacing::Alone)),
+                TokenTree::Ident(Ident::new("run_main", Span::call_site())),
+                TokenTree::Group(Group::new(
+                    Delimiter::Parenthesis,
+                    [
+                        TokenTree::Punct(Punct::new('|', Spacing::Joint)),
+                        TokenTree::Punct(Punct::new('|', Spacing::Alone)),
+                        TokenTree::Group(Group::new(Delimiter::Brace, group.stream())),
+                    ]
+                    .into_iter()
+                    .collect(),
+                )),
             ]);
-            new_body.extend([TokenTree::Ident(Ident::new(
-                "main_result",
-                Span::call_site(),
-            ))]);
 
             *token = TokenTree::Group(Group::new(Delimiter::Brace, new_body.into_iter().collect()));
             break;
@@ -8,6 +8,29 @@ import pathlib
 import typing
 from enum import Enum
 
+class CondorExecutor:
+    def __init__(self) -> CondorExecutor: ...
+    def with_container(self, container: Container) -> None: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_poll_rate(self, rate: datetime.timedelta) -> None: ...
+    def with_staging_mode(self, mode: StagingMode) -> None: ...
+    def with_file_transfer(self, file_transfer: builtins.bool) -> None: ...
+    def with_cpus(self, cpus: builtins.int) -> None: ...
+    def with_memory(self, memory: MemoryConfig) -> None: ...
+    def with_disk(self, disk: Memory) -> None: ...
+    def with_gpus(self, gpus: builtins.int) -> None: ...
+    def with_time(self, time: datetime.timedelta) -> None: ...
+    def with_arg(self, command: builtins.str, value: builtins.str) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def with_escalation(
+        self,
+        memory: typing.Optional[builtins.float] = None,
+        time: typing.Optional[builtins.float] = None,
+        cpus: typing.Optional[builtins.int] = None,
+    ) -> None: ...
+    def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
+
 class Container:
     class Docker(Container):
         __match_args__ = ("_0",)
@@ -29,11 +52,86 @@ class Container:
 
 class GenericExecutor:
     def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
+    def exe_array(
+        self,
+        processes: typing.Sequence[Process],
+        max_concurrent: typing.Optional[builtins.int] = None,
+    ) -> builtins.list[typing.Any]:
+        r"""
+        Runs a batch of processes, returning their outputs in order;
+        failed processes are returned as exceptions
+        """
+
+class KubernetesExecutor:
+    def __init__(self, image: builtins.str) -> KubernetesExecutor: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_poll_rate(self, rate: datetime.timedelta) -> None: ...
+    def with_staging_mode(self, mode: StagingMode) -> None: ...
+    def with_namespace(self, namespace: builtins.str) -> None: ...
+    def with_context(self, context: builtins.str) -> None: ...
+    def with_kubeconfig(self, kubeconfig: builtins.str | os.PathLike | pathlib.Path) -> None: ...
+    def with_host_path(
+        self, path: typing.Optional[builtins.str | os.PathLike | pathlib.Path] = None
+    ) -> None: ...
+    def with_pvc(
+        self, claim: builtins.str, sub_path: typing.Optional[builtins.str] = None
+    ) -> None: ...
+    def with_cpus(self, cpus: builtins.int) -> None: ...
+    def with_memory(self, memory: MemoryConfig) -> None: ...
+    def with_gpus(self, gpus: builtins.int) -> None: ...
+    def with_time(self, time: datetime.timedelta) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def with_escalation(
+        self,
+        memory: typing.Optional[builtins.float] = None,
+        time: typing.Optional[builtins.float] = None,
+        cpus: typing.Optional[builtins.int] = None,
+    ) -> None: ...
+    def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
 
 class LocalExecutor:
     def __init__(self) -> LocalExecutor: ...
     def with_container(self, container: Container) -> None: ...
     def with_staging_mode(self, mode: StagingMode) -> None: ...
+    def with_cpus(self, cpus: builtins.int) -> None: ...
+    def with_memory(self, memory: MemoryConfig) -> None: ...
+    def with_time(self, time: datetime.timedelta) -> None: ...
+    def with_max_parallel(self, slots: builtins.int) -> None: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def with_escalation(
+        self,
+        memory: typing.Optional[builtins.float] = None,
+        time: typing.Optional[builtins.float] = None,
+        cpus: typing.Optional[builtins.int] = None,
+    ) -> None: ...
+    def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
+
+class LsfExecutor:
+    def __init__(self) -> LsfExecutor: ...
+    def with_container(self, container: Container) -> None: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_poll_rate(self, rate: datetime.timedelta) -> None: ...
+    def with_staging_mode(self, mode: StagingMode) -> None: ...
+    def with_module(self, module: builtins.str) -> None: ...
+    def with_modules(self, modules: typing.Sequence[builtins.str]) -> None: ...
+    def with_cpus(self, cpus: builtins.int) -> None: ...
+    def with_memory(self, memory: MemoryConfig) -> None: ...
+    def with_gpus(self, gpus: builtins.int) -> None: ...
+    def with_queue(self, queue: builtins.str) -> None: ...
+    def with_time(self, time: datetime.timedelta) -> None: ...
+    def with_project(self, project: builtins.str) -> None: ...
+    def with_arg(self, arg: builtins.str, value: builtins.str) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def with_escalation(
+        self,
+        memory: typing.Optional[builtins.float] = None,
+        time: typing.Optional[builtins.float] = None,
+        cpus: typing.Optional[builtins.int] = None,
+    ) -> None: ...
     def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
 
 class Memory:
@@ -74,6 +172,32 @@ class MemoryConfig:
 
     ...
 
+class PbsExecutor:
+    def __init__(self) -> PbsExecutor: ...
+    def with_container(self, container: Container) -> None: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_poll_rate(self, rate: datetime.timedelta) -> None: ...
+    def with_staging_mode(self, mode: StagingMode) -> None: ...
+    def with_module(self, module: builtins.str) -> None: ...
+    def with_modules(self, modules: typing.Sequence[builtins.str]) -> None: ...
+    def with_cpus(self, cpus: builtins.int) -> None: ...
+    def with_memory(self, memory: MemoryConfig) -> None: ...
+    def with_gpus(self, gpus: builtins.int) -> None: ...
+    def with_nodes(self, nodes: builtins.int) -> None: ...
+    def with_queue(self, queue: builtins.str) -> None: ...
+    def with_time(self, time: datetime.timedelta) -> None: ...
+    def with_account(self, account: builtins.str) -> None: ...
+    def with_arg(self, arg: builtins.str, value: builtins.str) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def with_escalation(
+        self,
+        memory: typing.Optional[builtins.float] = None,
+        time: typing.Optional[builtins.float] = None,
+        cpus: typing.Optional[builtins.int] = None,
+    ) -> None: ...
+    def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
+
 class Process:
     def __init__(
         self,
@@ -85,6 +209,60 @@ class Process:
         ],
         args: typing.Mapping[builtins.str, builtins.str],
     ) -> Process: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+
+class ProcessMetrics:
+    r"""
+    Resource usage and timing of a process run, as far as its executor can tell
+
+    Timestamps are in seconds since the Unix epoch
+    """
+    @property
+    def wall_time(self) -> typing.Optional[builtins.float]: ...
+    @property
+    def cpu_time(self) -> typing.Optional[builtins.float]: ...
+    @property
+    def peak_rss_bytes(self) -> typing.Optional[builtins.int]: ...
+    @property
+    def exit_code(self) -> typing.Optional[builtins.int]: ...
+    @property
+    def signal(self) -> typing.Optional[builtins.int]: ...
+    @property
+    def node(self) -> typing.Optional[builtins.str]: ...
+    @property
+    def requeues(self) -> typing.Optional[builtins.int]: ...
+    @property
+    def submit_time(self) -> typing.Optional[builtins.int]: ...
+    @property
+    def start_time(self) -> typing.Optional[builtins.int]: ...
+    @property
+    def end_time(self) -> typing.Optional[builtins.int]: ...
+
+class SlurmAllocationExecutor:
+    def __init__(self) -> SlurmAllocationExecutor: ...
+    def with_container(self, container: Container) -> None: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_staging_mode(self, mode: StagingMode) -> None: ...
+    def with_module(self, module: builtins.str) -> None: ...
+    def with_modules(self, modules: typing.Sequence[builtins.str]) -> None: ...
+    def with_allocation(self, allocation: SlurmConfig) -> None:
+        r"""
+        Sets the allocation acquired when not already running inside one
+        """
+    def with_cpus(self, cpus: builtins.int) -> None: ...
+    def with_memory(self, memory: MemoryConfig) -> None: ...
+    def with_gpus(self, gpus: builtins.int) -> None: ...
+    def with_time(self, time: datetime.timedelta) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def with_preemption_retries(self, preemption_retries: builtins.int) -> None: ...
+    def with_escalation(
+        self,
+        memory: typing.Optional[builtins.float] = None,
+        time: typing.Optional[builtins.float] = None,
+        cpus: typing.Optional[builtins.int] = None,
+    ) -> None: ...
+    def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
 
 class SlurmConfig:
     def __init__(self) -> SlurmConfig: ...
@@ -98,6 +276,16 @@ class SlurmConfig:
     def with_account(self, account: builtins.str) -> None: ...
     def with_mail_user(self, mail_user: builtins.str) -> None: ...
     def with_mail_type(self, mail_type: typing.Sequence[MailType]) -> None: ...
+    def with_constraint(self, constraint: builtins.str) -> None: ...
+    def with_qos(self, qos: builtins.str) -> None: ...
+    def with_exclusive(self, exclusive: builtins.bool) -> None: ...
+    def with_gres(self, gres: builtins.str) -> None: ...
+    def with_reservation(self, reservation: builtins.str) -> None: ...
+    def with_exclude(self, exclude: typing.Sequence[builtins.str]) -> None: ...
+    def with_begin(self, begin: builtins.str) -> None: ...
+    def with_dependency(self, dependency: builtins.str) -> None: ...
+    def with_nice(self, nice: builtins.int) -> None: ...
+    def with_requeue(self, requeue: builtins.bool) -> None: ...
     def with_additional_options(
         self, additional_options: typing.Sequence[tuple[builtins.str, builtins.str]]
     ) -> None: ...
@@ -105,11 +293,85 @@ class SlurmConfig:
 class SlurmExecutor:
     def __init__(self) -> SlurmExecutor: ...
     def with_container(self, container: Container) -> None: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_submit_ahead(self, submit_ahead: builtins.bool) -> None: ...
     def with_poll_rate(self, rate: datetime.timedelta) -> None: ...
     def with_staging_mode(self, mode: StagingMode) -> None: ...
     def with_module(self, module: builtins.str) -> None: ...
     def with_modules(self, modules: typing.Sequence[builtins.str]) -> None: ...
     def with_config(self, config: SlurmConfig) -> None: ...
+    def with_rest(
+        self,
+        url: builtins.str,
+        token_env: typing.Optional[builtins.str] = None,
+        token_file: typing.Optional[builtins.str | os.PathLike | pathlib.Path] = None,
+        user: typing.Optional[builtins.str] = None,
+        api_version: typing.Optional[builtins.str] = None,
+    ) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def with_preemption_retries(self, preemption_retries: builtins.int) -> None: ...
+    def with_escalation(
+        self,
+        memory: typing.Optional[builtins.float] = None,
+        time: typing.Optional[builtins.float] = None,
+        cpus: typing.Optional[builtins.int] = None,
+    ) -> None: ...
+    def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
+    def exe_array(
+        self,
+        processes: typing.Sequence[Process],
+        max_concurrent: typing.Optional[builtins.int] = None,
+    ) -> builtins.list[typing.Any]:
+        r"""
+        Runs a batch of processes, returning their outputs in order;
+        failed processes are returned as exceptions
+        """
+
+class SshExecutor:
+    def __init__(self, host: builtins.str) -> SshExecutor: ...
+    def with_user(self, user: builtins.str) -> None: ...
+    def with_port(self, port: builtins.int) -> None: ...
+    def with_identity_file(
+        self, identity_file: builtins.str | os.PathLike | pathlib.Path
+    ) -> None: ...
+    def with_remote_workdir(
+        self, remote_workdir: builtins.str | os.PathLike | pathlib.Path
+    ) -> None: ...
+    def with_container(self, container: Container) -> None: ...
+    def with_staging_mode(self, mode: StagingMode) -> None: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
+
+class TemplateExecutor:
+    def __init__(
+        self,
+        submit: builtins.str,
+        status_command: builtins.str,
+        status_regex: builtins.str,
+        cancel: builtins.str,
+        states: typing.Mapping[builtins.str, TemplateState] = {},
+    ) -> TemplateExecutor: ...
+    def with_header(self, header: builtins.str) -> None: ...
+    def with_job_id_regex(self, regex: builtins.str) -> None: ...
+    def with_container(self, container: Container) -> None: ...
+    def with_staging_mode(self, mode: StagingMode) -> None: ...
+    def with_poll_rate(self, rate: datetime.timedelta) -> None: ...
+    def with_cpus(self, cpus: builtins.int) -> None: ...
+    def with_memory(self, memory: MemoryConfig) -> None: ...
+    def with_gpus(self, gpus: builtins.int) -> None: ...
+    def with_time(self, time: datetime.timedelta) -> None: ...
+    def with_cache(self, cache: builtins.bool) -> None: ...
+    def with_retries(self, retries: builtins.int) -> None: ...
+    def with_retry_backoff(self, backoff: datetime.timedelta) -> None: ...
+    def with_escalation(
+        self,
+        memory: typing.Optional[builtins.float] = None,
+        time: typing.Optional[builtins.float] = None,
+        cpus: typing.Optional[builtins.int] = None,
+    ) -> None: ...
     def exe(self, process: Process) -> builtins.list[pathlib.Path]: ...
 
 class MailType(Enum):
@@ -132,6 +394,17 @@ class StagingMode(Enum):
     Symlink = ...
     NA = ...
 
+class TemplateState(Enum):
+    Pending = ...
+    Running = ...
+    Finished = ...
+
 def arg(name: builtins.str) -> builtins.str: ...
 def executor(name: builtins.str) -> GenericExecutor: ...
 def inputs(name: builtins.str) -> builtins.list[pathlib.Path]: ...
+def metrics(
+    outputs: typing.Sequence[builtins.str | os.PathLike | pathlib.Path],
+) -> typing.Optional[ProcessMetrics]:
+    r"""
+    Reads the resource usage recorded for a run from the outputs it returned
+    """
@@ -14,14 +14,29 @@ use pyo3_stub_gen::{
 use std::{borrow::Cow, collections::HashMap, path::PathBuf, time::Duration};
 use RustMaestro::{
     executors::{
+        condor::CondorExecutor as RustCondorExecutor,
+        kubernetes::{
+            KubernetesExecutor as RustKubernetesExecutor, KubernetesVolume as RustKubernetesVolume,
+        },
         local::LocalExecutor as RustLocalExecutor,
+        lsf::LsfExecutor as RustLsfExecutor,
+        pbs::PbsExecutor as RustPbsExecutor,
+        retry::Escalation as RustEscalation,
         slurm::{
             MailType as RustMailType, MailTypeList as RustMailTypeList, Memory as RustMemory,
-            MemoryConfig as RustMemoryConfig, SlurmConfig as RustSlurmConfig,
-            SlurmExecutor as RustSlurmExecutor, SlurmTime as RustSlurmTime,
+            MemoryConfig as RustMemoryConfig,
+            SlurmAllocationExecutor as RustSlurmAllocationExecutor, SlurmConfig as RustSlurmConfig,
+            SlurmExecutor as RustSlurmExecutor, SlurmRest as RustSlurmRest,
+            SlurmTime as RustSlurmTime,
+        },
+        ssh::SshExecutor as RustSshExecutor,
+        template::{
+            TemplateExecutor as RustTemplateExecutor, TemplateState as RustTemplateState,
+            TemplateStatus as RustTemplateStatus,
         },
         Executor, GenericExecutor as RustGenericExecutor,
     },
+    metrics::ProcessMetrics as RustProcessMetrics,
     prelude::NodeError as RustNodeError,
     process::StagingMode as RustStagingMode,
     Container as RustContainer, Process as RustProcess,
@@ -37,10 +52,20 @@ pub fn maestro(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<StagingMode>()?;
     m.add_class::<LocalExecutor>()?;
     m.add_class::<SlurmExecutor>()?;
+    m.add_class::<SlurmAllocationExecutor>()?;
+    m.add_class::<SshExecutor>()?;
+    m.add_class::<PbsExecutor>()?;
+    m.add_class::<LsfExecutor>()?;
+    m.add_class::<CondorExecutor>()?;
+    m.add_class::<KubernetesExecutor>()?;
+    m.add_class::<TemplateExecutor>()?;
+    m.add_class::<TemplateState>()?;
     m.add_class::<SlurmConfig>()?;
     m.add_class::<MemoryConfig>()?;
     m.add_class::<Memory>()?;
     m.add_class::<MailType>()?;
+    m.add_class::<ProcessMetrics>()?;
+    m.add_function(wrap_pyfunction!(metrics, m)?)?;
 
     Python::with_gil(|gil| -> PyResult<()> {
         let atexit = gil.import("atexit")?;
@@ -69,6 +94,35 @@ impl GenericExecutor {
     pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
         self.0.exe(process.0.clone()).map_err(NodeError)
     }
+    /// Runs a batch of processes, returning their outputs in order;
+    /// failed processes are returned as exceptions
+    #[pyo3(signature = (processes, max_concurrent=None))]
+    pub fn exe_array(
+        &self,
+        py: Python<'_>,
+        processes: Vec<Process>,
+        max_concurrent: Option<u32>,
+    ) -> PyResult<Vec<PyObject>> {
+        let processes = processes.into_iter().map(|process| process.0).collect();
+        into_py_results(py, self.0.exe_array(processes, max_concurrent))
+    }
+}
+
+fn into_py_results(
+    py: Python<'_>,
+    results: Vec<RustMaestro::WorkflowResult>,
+) -> PyResult<Vec<PyObject>> {
+    results
+        .into_iter()
+        .map(|result| match result {
+            Ok(outputs) => Ok(outputs.into_pyobject(py)?.into_any().unbind()),
+            Err(e) => Ok(PyErr::from(NodeError(e))
+                .value(py)
+                .clone()
+                .into_any()
+                .unbind()),
+        })
+        .collect()
 }
 
 #[pyfunction]
@@ -90,6 +144,67 @@ pub fn inputs(name: String) -> Vec<PathBuf> {
         .collect()
 }
 
+/// Reads the resource usage recorded for a run from the outputs it returned
+#[pyfunction]
+#[gen_stub_pyfunction]
+pub fn metrics(outputs: Vec<PathBuf>) -> Result<Option<ProcessMetrics>, NodeError> {
+    RustProcessMetrics::from_outputs(&outputs)
+        .map(|metrics| metrics.map(ProcessMetrics))
+        .map_err(NodeError)
+}
+
+/// Resource usage and timing of a process run, as far as its executor can tell
+///
+/// Timestamps are in seconds since the Unix epoch
+#[pyclass]
+#[gen_stub_pyclass]
+pub struct ProcessMetrics(RustProcessMetrics);
+
+#[pymethods]
+#[gen_stub_pymethods]
+impl ProcessMetrics {
+    #[getter]
+    pub fn wall_time(&self) -> Option<f64> {
+        self.0.wall_time_secs
+    }
+    #[getter]
+    pub fn cpu_time(&self) -> Option<f64> {
+        self.0.cpu_time_secs
+    }
+    #[getter]
+    pub fn peak_rss_bytes(&self) -> Option<u64> {
+        self.0.peak_rss_bytes
+    }
+    #[getter]
+    pub fn exit_code(&self) -> Option<i32> {
+        self.0.exit_code
+    }
+    #[getter]
+    pub fn signal(&self) -> Option<i32> {
+        self.0.signal
+    }
+    #[getter]
+    pub fn node(&self) -> Option<String> {
+        self.0.node.clone()
+    }
+    #[getter]
+    pub fn requeues(&self) -> Option<u32> {
+        self.0.requeues
+    }
+    #[getter]
+    pub fn submit_time(&self) -> Option<u64> {
+        self.0.submit_time
+    }
+    #[getter]
+    pub fn start_time(&self) -> Option<u64> {
+        self.0.start_time
+    }
+    #[getter]
+    pub fn end_time(&self) -> Option<u64> {
+        self.0.end_time
+    }
+}
+
 #[pyclass]
 #[gen_stub_pyclass_complex_enum]
 #[derive(Clone)]
@@ -137,6 +252,9 @@ impl Process {
             )))
         }
     }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
 }
 
 #[pyclass]
@@ -182,6 +300,41 @@ impl LocalExecutor {
     pub fn with_staging_mode(&mut self, mode: StagingMode) {
         self.0 = self.0.clone().with_staging_mode(mode.into());
     }
+    pub fn with_cpus(&mut self, cpus: u64) {
+        self.0 = self.0.clone().with_cpus(cpus);
+    }
+    pub fn with_memory(&mut self, memory: MemoryConfig) {
+        self.0 = self.0.clone().with_memory(memory.into());
+    }
+    pub fn with_time(&mut self, time: Duration) {
+        self.0 = self.0.clone().with_time(into_slurm_time(time));
+    }
+    pub fn with_max_parallel(&mut self, slots: u64) {
+        self.0 = self.0.clone().with_max_parallel(slots);
+    }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    #[pyo3(signature = (memory=None, time=None, cpus=None))]
+    pub fn with_escalation(&mut self, memory: Option<f64>, time: Option<f64>, cpus: Option<u64>) {
+        let escalation = RustEscalation { memory, time, cpus };
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_escalation(escalation));
+    }
     pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
         self.0.exe(process.0.clone()).map_err(NodeError)
     }
@@ -201,6 +354,12 @@ impl SlurmExecutor {
     pub fn with_container(&mut self, container: Container) {
         self.0 = self.0.clone().with_container(container.into());
     }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_submit_ahead(&mut self, submit_ahead: bool) {
+        self.0 = self.0.clone().with_submit_ahead(submit_ahead);
+    }
     pub fn with_poll_rate(&mut self, rate: Duration) {
         self.0 = self.0.clone().with_poll_rate(rate);
     }
@@ -216,9 +375,627 @@ impl SlurmExecutor {
     pub fn with_config(&mut self, config: SlurmConfig) {
         self.0 = self.0.clone().with_config(config.into())
     }
+    #[pyo3(signature = (url, token_env=None, token_file=None, user=None, api_version=None))]
+    pub fn with_rest(
+        &mut self,
+        url: String,
+        token_env: Option<String>,
+        token_file: Option<PathBuf>,
+        user: Option<String>,
+        api_version: Option<String>,
+    ) -> PyResult<()> {
+        let mut rest = RustSlurmRest::new(url);
+        match (token_env, token_file) {
+            (Some(_), Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "Only one of token_env and token_file can be set",
+                ))
+            }
+            (Some(var), None) => rest = rest.with_token_env(var),
+            (None, Some(path)) => rest = rest.with_token_file(path),
+            (None, None) => {}
+        }
+        if let Some(user) = user {
+            rest = rest.with_user(user);
+        }
+        if let Some(api_version) = api_version {
+            rest = rest.with_api_version(api_version);
+        }
+        self.0 = self.0.clone().with_rest(rest);
+        Ok(())
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    pub fn with_preemption_retries(&mut self, preemption_retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_preemption_retries(preemption_retries));
+    }
+    #[pyo3(signature = (memory=None, time=None, cpus=None))]
+    pub fn with_escalation(&mut self, memory: Option<f64>, time: Option<f64>, cpus: Option<u64>) {
+        let escalation = RustEscalation { memory, time, cpus };
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_escalation(escalation));
+    }
     pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
         self.0.exe(process.0.clone()).map_err(NodeError)
     }
+    /// Runs a batch of processes, returning their outputs in order;
+    /// failed processes are returned as exceptions
+    #[pyo3(signature = (processes, max_concurrent=None))]
+    pub fn exe_array(
+        &self,
+        py: Python<'_>,
+        processes: Vec<Process>,
+        max_concurrent: Option<u32>,
+    ) -> PyResult<Vec<PyObject>> {
+        let processes = processes.into_iter().map(|process| process.0).collect();
+        into_py_results(py, self.0.exe_array(processes, max_concurrent))
+    }
+}
+
+#[pyclass]
+#[gen_stub_pyclass]
+pub struct SlurmAllocationExecutor(RustSlurmAllocationExecutor);
+
+#[pymethods]
+#[gen_stub_pymethods]
+impl SlurmAllocationExecutor {
+    #[new]
+    pub fn __init__() -> SlurmAllocationExecutor {
+        SlurmAllocationExecutor(RustSlurmAllocationExecutor::default())
+    }
+    pub fn with_container(&mut self, container: Container) {
+        self.0 = self.0.clone().with_container(container.into());
+    }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_staging_mode(&mut self, mode: StagingMode) {
+        self.0 = self.0.clone().with_staging_mode(mode.into());
+    }
+    pub fn with_module(&mut self, module: String) {
+        self.0 = self.0.clone().with_module(module);
+    }
+    pub fn with_modules(&mut self, modules: Vec<String>) {
+        self.0 = self.0.clone().with_modules(modules);
+    }
+    /// Sets the allocation acquired when not already running inside one
+    pub fn with_allocation(&mut self, allocation: SlurmConfig) {
+        self.0 = self.0.clone().with_allocation(allocation.into());
+    }
+    pub fn with_cpus(&mut self, cpus: u64) {
+        self.0 = self.0.clone().with_cpus(cpus);
+    }
+    pub fn with_memory(&mut self, memory: MemoryConfig) {
+        self.0 = self.0.clone().with_memory(memory.into());
+    }
+    pub fn with_gpus(&mut self, gpus: u64) {
+        self.0 = self.0.clone().with_gpus(gpus);
+    }
+    pub fn with_time(&mut self, time: Duration) {
+        self.0 = self.0.clone().with_time(into_slurm_time(time));
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    pub fn with_preemption_retries(&mut self, preemption_retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_preemption_retries(preemption_retries));
+    }
+    #[pyo3(signature = (memory=None, time=None, cpus=None))]
+    pub fn with_escalation(&mut self, memory: Option<f64>, time: Option<f64>, cpus: Option<u64>) {
+        let escalation = RustEscalation { memory, time, cpus };
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_escalation(escalation));
+    }
+    pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
+        self.0.exe(process.0.clone()).map_err(NodeError)
+    }
+}
+
+#[pyclass]
+#[gen_stub_pyclass]
+pub struct SshExecutor(RustSshExecutor);
+
+#[pymethods]
+#[gen_stub_pymethods]
+impl SshExecutor {
+    #[new]
+    pub fn __init__(host: String) -> SshExecutor {
+        SshExecutor(RustSshExecutor::new(host))
+    }
+    pub fn with_user(&mut self, user: String) {
+        self.0 = self.0.clone().with_user(user);
+    }
+    pub fn with_port(&mut self, port: u16) {
+        self.0 = self.0.clone().with_port(port);
+    }
+    pub fn with_identity_file(&mut self, identity_file: PathBuf) {
+        self.0 = self.0.clone().with_identity_file(identity_file);
+    }
+    pub fn with_remote_workdir(&mut self, remote_workdir: PathBuf) {
+        self.0 = self.0.clone().with_remote_workdir(remote_workdir);
+    }
+    pub fn with_container(&mut self, container: Container) {
+        self.0 = self.0.clone().with_container(container.into());
+    }
+    pub fn with_staging_mode(&mut self, mode: StagingMode) {
+        self.0 = self.0.clone().with_staging_mode(mode.into());
+    }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
+        self.0.exe(process.0.clone()).map_err(NodeError)
+    }
+}
+
+#[pyclass]
+#[gen_stub_pyclass]
+#[derive(Clone)]
+pub struct PbsExecutor(RustPbsExecutor);
+
+#[pymethods]
+#[gen_stub_pymethods]
+impl PbsExecutor {
+    #[new]
+    pub fn __init__() -> PbsExecutor {
+        PbsExecutor(RustPbsExecutor::default())
+    }
+    pub fn with_container(&mut self, container: Container) {
+        self.0 = self.0.clone().with_container(container.into());
+    }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_poll_rate(&mut self, rate: Duration) {
+        self.0 = self.0.clone().with_poll_rate(rate);
+    }
+    pub fn with_staging_mode(&mut self, mode: StagingMode) {
+        self.0 = self.0.clone().with_staging_mode(mode.into());
+    }
+    pub fn with_module(&mut self, module: String) {
+        self.0 = self.0.clone().with_module(module);
+    }
+    pub fn with_modules(&mut self, modules: Vec<String>) {
+        self.0 = self.0.clone().with_modules(modules);
+    }
+    pub fn with_cpus(&mut self, cpus: u64) {
+        self.0 = self.0.clone().map_config(|config| config.with_cpus(cpus));
+    }
+    pub fn with_memory(&mut self, memory: MemoryConfig) {
+        self.0 = self
+            .0
+            .clone()
+            .map_config(|config| config.with_memory(memory.into()));
+    }
+    pub fn with_gpus(&mut self, gpus: u64) {
+        self.0 = self.0.clone().map_config(|config| config.with_gpus(gpus));
+    }
+    pub fn with_nodes(&mut self, nodes: u64) {
+        self.0 = self.0.clone().map_config(|config| config.with_nodes(nodes));
+    }
+    pub fn with_queue(&mut self, queue: String) {
+        self.0 = self.0.clone().map_config(|config| config.with_queue(queue));
+    }
+    pub fn with_time(&mut self, time: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_config(|config| config.with_time(into_slurm_time(time)));
+    }
+    pub fn with_account(&mut self, account: String) {
+        self.0 = self
+            .0
+            .clone()
+            .map_config(|config| config.with_account(account));
+    }
+    pub fn with_arg(&mut self, arg: String, value: String) {
+        self.0 = self
+            .0
+            .clone()
+            .map_config(|config| config.with_arg(arg, value));
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    #[pyo3(signature = (memory=None, time=None, cpus=None))]
+    pub fn with_escalation(&mut self, memory: Option<f64>, time: Option<f64>, cpus: Option<u64>) {
+        let escalation = RustEscalation { memory, time, cpus };
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_escalation(escalation));
+    }
+    pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
+        self.0.exe(process.0.clone()).map_err(NodeError)
+    }
+}
+
+#[pyclass]
+#[gen_stub_pyclass]
+#[derive(Clone)]
+pub struct LsfExecutor(RustLsfExecutor);
+
+#[pymethods]
+#[gen_stub_pymethods]
+impl LsfExecutor {
+    #[new]
+    pub fn __init__() -> LsfExecutor {
+        LsfExecutor(RustLsfExecutor::default())
+    }
+    pub fn with_container(&mut self, container: Container) {
+        self.0 = self.0.clone().with_container(container.into());
+    }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_poll_rate(&mut self, rate: Duration) {
+        self.0 = self.0.clone().with_poll_rate(rate);
+    }
+    pub fn with_staging_mode(&mut self, mode: StagingMode) {
+        self.0 = self.0.clone().with_staging_mode(mode.into());
+    }
+    pub fn with_module(&mut self, module: String) {
+        self.0 = self.0.clone().with_module(module);
+    }
+    pub fn with_modules(&mut self, modules: Vec<String>) {
+        self.0 = self.0.clone().with_modules(modules);
+    }
+    pub fn with_cpus(&mut self, cpus: u64) {
+        self.0 = self.0.clone().map_config(|config| config.with_cpus(cpus));
+    }
+    pub fn with_memory(&mut self, memory: MemoryConfig) {
+        self.0 = self
+            .0
+            .clone()
+            .map_config(|config| config.with_memory(memory.into()));
+    }
+    pub fn with_gpus(&mut self, gpus: u64) {
+        self.0 = self.0.clone().map_config(|config| config.with_gpus(gpus));
+    }
+    pub fn with_queue(&mut self, queue: String) {
+        self.0 = self.0.clone().map_config(|config| config.with_queue(queue));
+    }
+    pub fn with_time(&mut self, time: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_config(|config| config.with_time(into_slurm_time(time)));
+    }
+    pub fn with_project(&mut self, project: String) {
+        self.0 = self
+            .0
+            .clone()
+            .map_config(|config| config.with_project(project));
+    }
+    pub fn with_arg(&mut self, arg: String, value: String) {
+        self.0 = self
+            .0
+            .clone()
+            .map_config(|config| config.with_arg(arg, value));
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    #[pyo3(signature = (memory=None, time=None, cpus=None))]
+    pub fn with_escalation(&mut self, memory: Option<f64>, time: Option<f64>, cpus: Option<u64>) {
+        let escalation = RustEscalation { memory, time, cpus };
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_escalation(escalation));
+    }
+    pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
+        self.0.exe(process.0.clone()).map_err(NodeError)
+    }
+}
+
+#[pyclass]
+#[gen_stub_pyclass]
+#[derive(Clone)]
+pub struct CondorExecutor(RustCondorExecutor);
+
+#[pymethods]
+#[gen_stub_pymethods]
+impl CondorExecutor {
+    #[new]
+    pub fn __init__() -> CondorExecutor {
+        CondorExecutor(RustCondorExecutor::default())
+    }
+    pub fn with_container(&mut self, container: Container) {
+        self.0 = self.0.clone().with_container(container.into());
+    }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_poll_rate(&mut self, rate: Duration) {
+        self.0 = self.0.clone().with_poll_rate(rate);
+    }
+    pub fn with_staging_mode(&mut self, mode: StagingMode) {
+        self.0 = self.0.clone().with_staging_mode(mode.into());
+    }
+    pub fn with_file_transfer(&mut self, file_transfer: bool) {
+        self.0 = self.0.clone().with_file_transfer(file_transfer);
+    }
+    pub fn with_cpus(&mut self, cpus: u64) {
+        self.0 = self.0.clone().with_cpus(cpus);
+    }
+    pub fn with_memory(&mut self, memory: MemoryConfig) {
+        self.0 = self.0.clone().with_memory(memory.into());
+    }
+    pub fn with_disk(&mut self, disk: Memory) {
+        self.0 = self.0.clone().with_disk(disk.into());
+    }
+    pub fn with_gpus(&mut self, gpus: u64) {
+        self.0 = self.0.clone().with_gpus(gpus);
+    }
+    pub fn with_time(&mut self, time: Duration) {
+        self.0 = self.0.clone().with_time(into_slurm_time(time));
+    }
+    pub fn with_arg(&mut self, command: String, value: String) {
+        self.0 = self.0.clone().with_arg(command, value);
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    #[pyo3(signature = (memory=None, time=None, cpus=None))]
+    pub fn with_escalation(&mut self, memory: Option<f64>, time: Option<f64>, cpus: Option<u64>) {
+        let escalation = RustEscalation { memory, time, cpus };
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_escalation(escalation));
+    }
+    pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
+        self.0.exe(process.0.clone()).map_err(NodeError)
+    }
+}
+
+#[pyclass]
+#[gen_stub_pyclass]
+#[derive(Clone)]
+pub struct KubernetesExecutor(RustKubernetesExecutor);
+
+#[pymethods]
+#[gen_stub_pymethods]
+impl KubernetesExecutor {
+    #[new]
+    pub fn __init__(image: String) -> KubernetesExecutor {
+        KubernetesExecutor(
+            RustKubernetesExecutor::default()
+                .with_container(RustContainer::Docker(Cow::Owned(image))),
+        )
+    }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_poll_rate(&mut self, rate: Duration) {
+        self.0 = self.0.clone().with_poll_rate(rate);
+    }
+    pub fn with_staging_mode(&mut self, mode: StagingMode) {
+        self.0 = self.0.clone().with_staging_mode(mode.into());
+    }
+    pub fn with_namespace(&mut self, namespace: String) {
+        self.0 = self.0.clone().with_namespace(namespace);
+    }
+    pub fn with_context(&mut self, context: String) {
+        self.0 = self.0.clone().with_context(context);
+    }
+    pub fn with_kubeconfig(&mut self, kubeconfig: PathBuf) {
+        self.0 = self.0.clone().with_kubeconfig(kubeconfig);
+    }
+    #[pyo3(signature = (path=None))]
+    pub fn with_host_path(&mut self, path: Option<PathBuf>) {
+        self.0 = self
+            .0
+            .clone()
+            .with_volume(RustKubernetesVolume::HostPath { path });
+    }
+    #[pyo3(signature = (claim, sub_path=None))]
+    pub fn with_pvc(&mut self, claim: String, sub_path: Option<String>) {
+        self.0 = self
+            .0
+            .clone()
+            .with_volume(RustKubernetesVolume::Pvc { claim, sub_path });
+    }
+    pub fn with_cpus(&mut self, cpus: u64) {
+        self.0 = self.0.clone().with_cpus(cpus);
+    }
+    pub fn with_memory(&mut self, memory: MemoryConfig) {
+        self.0 = self.0.clone().with_memory(memory.into());
+    }
+    pub fn with_gpus(&mut self, gpus: u64) {
+        self.0 = self.0.clone().with_gpus(gpus);
+    }
+    pub fn with_time(&mut self, time: Duration) {
+        self.0 = self.0.clone().with_time(into_slurm_time(time));
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    #[pyo3(signature = (memory=None, time=None, cpus=None))]
+    pub fn with_escalation(&mut self, memory: Option<f64>, time: Option<f64>, cpus: Option<u64>) {
+        let escalation = RustEscalation { memory, time, cpus };
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_escalation(escalation));
+    }
+    pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
+        self.0.exe(process.0.clone()).map_err(NodeError)
+    }
+}
+
+#[pyclass]
+#[gen_stub_pyclass]
+#[derive(Clone)]
+pub struct TemplateExecutor(RustTemplateExecutor);
+
+#[pymethods]
+#[gen_stub_pymethods]
+impl TemplateExecutor {
+    #[new]
+    #[pyo3(signature = (submit, status_command, status_regex, cancel, states=HashMap::new()))]
+    pub fn __init__(
+        submit: String,
+        status_command: String,
+        status_regex: String,
+        cancel: String,
+        states: HashMap<String, TemplateState>,
+    ) -> TemplateExecutor {
+        let status = states.into_iter().fold(
+            RustTemplateStatus::new(status_command, status_regex),
+            |status, (state, meaning)| status.with_state(state, meaning.into()),
+        );
+        TemplateExecutor(RustTemplateExecutor::new(submit, status, cancel))
+    }
+    pub fn with_header(&mut self, header: String) {
+        self.0 = self.0.clone().with_header(header);
+    }
+    pub fn with_job_id_regex(&mut self, regex: String) {
+        self.0 = self.0.clone().with_job_id_regex(regex);
+    }
+    pub fn with_container(&mut self, container: Container) {
+        self.0 = self.0.clone().with_container(container.into());
+    }
+    pub fn with_staging_mode(&mut self, mode: StagingMode) {
+        self.0 = self.0.clone().with_staging_mode(mode.into());
+    }
+    pub fn with_poll_rate(&mut self, rate: Duration) {
+        self.0 = self.0.clone().with_poll_rate(rate);
+    }
+    pub fn with_cpus(&mut self, cpus: u64) {
+        self.0 = self.0.clone().with_cpus(cpus);
+    }
+    pub fn with_memory(&mut self, memory: MemoryConfig) {
+        self.0 = self.0.clone().with_memory(memory.into());
+    }
+    pub fn with_gpus(&mut self, gpus: u64) {
+        self.0 = self.0.clone().with_gpus(gpus);
+    }
+    pub fn with_time(&mut self, time: Duration) {
+        self.0 = self.0.clone().with_time(into_slurm_time(time));
+    }
+    pub fn with_cache(&mut self, cache: bool) {
+        self.0 = self.0.clone().with_cache(cache);
+    }
+    pub fn with_retries(&mut self, retries: u32) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retries(retries));
+    }
+    pub fn with_retry_backoff(&mut self, backoff: Duration) {
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_retry_backoff(backoff));
+    }
+    #[pyo3(signature = (memory=None, time=None, cpus=None))]
+    pub fn with_escalation(&mut self, memory: Option<f64>, time: Option<f64>, cpus: Option<u64>) {
+        let escalation = RustEscalation { memory, time, cpus };
+        self.0 = self
+            .0
+            .clone()
+            .map_retry(|retry| retry.with_escalation(escalation));
+    }
+    pub fn exe(&self, process: &Process) -> Result<Vec<PathBuf>, NodeError> {
+        self.0.exe(process.0.clone()).map_err(NodeError)
+    }
+}
+
+#[pyclass]
+#[gen_stub_pyclass_enum]
+#[derive(Clone, Copy)]
+pub enum TemplateState {
+    Pending,
+    Running,
+    Finished,
+}
+impl From<TemplateState> for RustTemplateState {
+    fn from(value: TemplateState) -> Self {
+        match value {
+            TemplateState::Pending => Self::Pending,
+            TemplateState::Running => Self::Running,
+            TemplateState::Finished => Self::Finished,
+        }
+    }
 }
 
 #[pyclass]
@@ -235,6 +1012,16 @@ pub struct SlurmConfig {
     account: Option<String>,
     mail_user: Option<String>,
     mail_type: Option<Vec<MailType>>,
+    constraint: Option<String>,
+    qos: Option<String>,
+    exclusive: Option<bool>,
+    gres: Option<String>,
+    reservation: Option<String>,
+    exclude: Option<Vec<String>>,
+    begin: Option<String>,
+    dependency: Option<String>,
+    nice: Option<i32>,
+    requeue: Option<bool>,
     additional_options: Vec<(String, String)>,
 }
 
@@ -275,6 +1062,36 @@ impl SlurmConfig {
     pub fn with_mail_type(&mut self, mail_type: Vec<MailType>) {
         self.mail_type = Some(mail_type);
     }
+    pub fn with_constraint(&mut self, constraint: String) {
+        self.constraint = Some(constraint);
+    }
+    pub fn with_qos(&mut self, qos: String) {
+        self.qos = Some(qos);
+    }
+    pub fn with_exclusive(&mut self, exclusive: bool) {
+        self.exclusive = Some(exclusive);
+    }
+    pub fn with_gres(&mut self, gres: String) {
+        self.gres = Some(gres);
+    }
+    pub fn with_reservation(&mut self, reservation: String) {
+        self.reservation = Some(reservation);
+    }
+    pub fn with_exclude(&mut self, exclude: Vec<String>) {
+        self.exclude = Some(exclude);
+    }
+    pub fn with_begin(&mut self, begin: String) {
+        self.begin = Some(begin);
+    }
+    pub fn with_dependency(&mut self, dependency: String) {
+        self.dependency = Some(dependency);
+    }
+    pub fn with_nice(&mut self, nice: i32) {
+        self.nice = Some(nice);
+    }
+    pub fn with_requeue(&mut self, requeue: bool) {
+        self.requeue = Some(requeue);
+    }
     pub fn with_additional_options(&mut self, additional_options: Vec<(String, String)>) {
         self.additional_options = additional_options;
     }
@@ -289,26 +1106,38 @@ impl From<SlurmConfig> for RustSlurmConfig {
             tasks: value.tasks,
             nodes: value.nodes,
             partition: value.partition,
-            time: value.time.map(|time| {
-                let total_secs = time.as_secs();
-                let days = total_secs / 86_400;
-                let hours = (total_secs % 86_400) / 3_600;
-                let mins = (total_secs % 3_600) / 60;
-                let secs = total_secs % 60;
-                RustSlurmTime::new(days as u16, hours as u16, mins as u8, secs as u8)
-                    .expect("Conversion should not be fallible")
-            }),
+            time: value.time.map(into_slurm_time),
             account: value.account,
             mail_user: value.mail_user,
             mail_type: value.mail_type.map(|types| {
                 let mail_types = types.into_iter().map(|ty| ty.into()).collect();
                 RustMailTypeList(mail_types)
             }),
+            constraint: value.constraint,
+            qos: value.qos,
+            exclusive: value.exclusive,
+            gres: value.gres,
+            reservation: value.reservation,
+            exclude: value.exclude,
+            begin: value.begin,
+            dependency: value.dependency,
+            nice: value.nice,
+            requeue: value.requeue,
             additional_options: value.additional_options,
         }
     }
 }
 
+fn into_slurm_time(time: Duration) -> RustSlurmTime {
+    let total_secs = time.as_secs();
+    let days = total_secs / 86_400;
+    let hours = (total_secs % 86_400) / 3_600;
+    let mins = (total_secs % 3_600) / 60;
+    let secs = total_secs % 60;
+    RustSlurmTime::new(days as u16, hours as u16, mins as u8, secs as u8)
+        .expect("Conversion should not be fallible")
+}
+
 #[pyclass]
 #[gen_stub_pyclass_complex_enum]
 #[derive(Clone, Copy)]
@@ -327,14 +1156,16 @@ pub enum Memory {
 impl From<MemoryConfig> for RustMemoryConfig {
     fn from(value: MemoryConfig) -> Self {
         match value {
-            MemoryConfig::PerCpu(quant) => RustMemoryConfig::PerCpu(match quant {
-                Memory::gb(gb) => RustMemory::from_gb(gb),
-                Memory::mb(mb) => RustMemory::from_mb(mb),
-            }),
-            MemoryConfig::PerNode(quant) => RustMemoryConfig::PerNode(match quant {
-                Memory::gb(gb) => RustMemory::from_gb(gb),
-                Memory::mb(mb) => RustMemory::from_mb(mb),
-            }),
+            MemoryConfig::PerCpu(quant) => RustMemoryConfig::PerCpu(quant.into()),
+            MemoryConfig::PerNode(quant) => RustMemoryConfig::PerNode(quant.into()),
+        }
+    }
+}
+impl From<Memory> for RustMemory {
+    fn from(value: Memory) -> Self {
+        match value {
+            Memory::gb(gb) => RustMemory::from_gb(gb),
+            Memory::mb(mb) => RustMemory::from_mb(mb),
         }
     }
 }